@@ -0,0 +1,146 @@
+#![allow(clippy::needless_return)]
+
+use std::fs;
+use std::io::{Cursor, Read};
+use std::path::Path;
+
+use flate2::read::GzDecoder;
+use zip::ZipArchive;
+
+use crate::error::EmuError;
+
+const ZIP_MAGIC: [u8; 4] = [0x50, 0x4B, 0x03, 0x04];
+const GZIP_MAGIC: [u8; 2] = [0x1F, 0x8B];
+
+// The largest cartridges are 8 MiB, anything that unpacks to more isn't a ROM
+const MAX_ROM_SIZE: u64 = 8 * 1024 * 1024;
+
+// Reads a ROM file, unpacking it first if it is a zip or gzip archive. Containers are detected by
+// their magic bytes rather than the extension. For zips the entry named rom_entry is used, or the
+// first .gb/.gbc entry if none is given
+pub fn read_rom_file(path: &str, rom_entry: Option<&str>) -> Result<Vec<u8>, EmuError> {
+    let data = fs::read(path)?;
+
+    if data.starts_with(&ZIP_MAGIC) {
+        return read_zip_entry(data, rom_entry);
+    }
+
+    if data.starts_with(&GZIP_MAGIC) {
+        debug!("Decompressing gzip ROM {}", path);
+        return read_limited(GzDecoder::new(&data[..]));
+    }
+
+    if rom_entry.is_some() {
+        warn!("{} is not an archive, ignoring the ROM entry name", path);
+    }
+    return Ok(data);
+}
+
+fn read_zip_entry(data: Vec<u8>, rom_entry: Option<&str>) -> Result<Vec<u8>, EmuError> {
+    let mut archive = ZipArchive::new(Cursor::new(data)).map_err(|error| EmuError::BadArchive(error.to_string()))?;
+
+    let mut index = None;
+    for entry_index in 0..archive.len() {
+        let entry = archive.by_index(entry_index).map_err(|error| EmuError::BadArchive(error.to_string()))?;
+        if entry.is_dir() {
+            continue;
+        }
+
+        let is_match = match rom_entry {
+            Some(name) => entry.name() == name,
+            None => is_rom_name(entry.name())
+        };
+        if is_match {
+            index = Some(entry_index);
+            break;
+        }
+    }
+
+    let index = match index {
+        Some(index) => index,
+        None => {
+            match rom_entry {
+                Some(name) => return Err(EmuError::BadArchive(format!("no entry named '{}'", name))),
+                None => return Err(EmuError::BadArchive(String::from("no .gb or .gbc file in the archive")))
+            }
+        }
+    };
+
+    let entry = archive.by_index(index).map_err(|error| EmuError::BadArchive(error.to_string()))?;
+    debug!("Loading ROM {} from zip", entry.name());
+    return read_limited(entry);
+}
+
+// Decompresses up to MAX_ROM_SIZE bytes. The size in the archive's header isn't trusted, the data
+// is cut off one byte past the limit so an oversized entry can be told apart from one right at it
+fn read_limited<R: Read>(reader: R) -> Result<Vec<u8>, EmuError> {
+    let mut buffer = Vec::new();
+    reader.take(MAX_ROM_SIZE + 1).read_to_end(&mut buffer)?;
+    if buffer.len() as u64 > MAX_ROM_SIZE {
+        return Err(EmuError::BadArchive(format!("ROM unpacks to more than {} MiB", MAX_ROM_SIZE / (1024 * 1024))));
+    }
+    return Ok(buffer);
+}
+
+fn is_rom_name(name: &str) -> bool {
+    match Path::new(name).extension().and_then(|extension| extension.to_str()) {
+        Some(extension) => return extension.eq_ignore_ascii_case("gb") || extension.eq_ignore_ascii_case("gbc"),
+        None => return false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::Write;
+
+    use flate2::Compression;
+    use flate2::write::GzEncoder;
+    use zip::ZipWriter;
+    use zip::write::FileOptions;
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::fast());
+        encoder.write_all(data).unwrap();
+        return encoder.finish().unwrap();
+    }
+
+    fn zip(name: &str, data: &[u8]) -> Vec<u8> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file(name, FileOptions::default()).unwrap();
+        writer.write_all(data).unwrap();
+        return writer.finish().unwrap().into_inner();
+    }
+
+    #[test]
+    fn gzip_at_the_size_limit() {
+        let rom = vec![0; MAX_ROM_SIZE as usize];
+        let data = read_limited(GzDecoder::new(&gzip(&rom)[..])).unwrap();
+        assert_eq!(data.len(), rom.len());
+    }
+
+    #[test]
+    fn gzip_over_the_size_limit() {
+        let rom = vec![0; MAX_ROM_SIZE as usize + 1];
+        match read_limited(GzDecoder::new(&gzip(&rom)[..])) {
+            Err(EmuError::BadArchive(_)) => {},
+            other => panic!("expected BadArchive, got {:?}", other.map(|data| data.len()))
+        }
+    }
+
+    #[test]
+    fn zip_over_the_size_limit() {
+        let rom = vec![0; MAX_ROM_SIZE as usize + 1];
+        match read_zip_entry(zip("game.gb", &rom), None) {
+            Err(EmuError::BadArchive(_)) => {},
+            other => panic!("expected BadArchive, got {:?}", other.map(|data| data.len()))
+        }
+    }
+
+    #[test]
+    fn zip_entry_by_name() {
+        let data = read_zip_entry(zip("game.gbc", &[1, 2, 3]), Some("game.gbc")).unwrap();
+        assert_eq!(data, vec![1, 2, 3]);
+    }
+}
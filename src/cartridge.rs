@@ -1,3 +1,8 @@
+#![allow(clippy::needless_return)]
+
+use crate::error::EmuError;
+use crate::mapper::{Mapper, RomOnly};
+
 // Cartridge Types
 pub const ROM_ONLY: u8            = 0x00;
 pub const MBC1: u8                = 0x01;
@@ -16,7 +21,7 @@ pub const MBC3: u8                = 0x11;
 pub const MBC3_RAM: u8            = 0x12;
 pub const MBC3_RAM_BATT: u8       = 0x13;
 pub const MBC5: u8                = 0x19;
-pub const MBC5_BATT: u8           = 0x1A;
+pub const MBC5_RAM: u8            = 0x1A;
 pub const MBC5_RAM_BATT: u8       = 0x1B;
 pub const MBC5_RUMBLE: u8         = 0x1C;
 pub const MBC5_RUMBLE_SRAM: u8    = 0x1D;
@@ -28,46 +33,508 @@ pub const MBC5_RUMB_SRAM_BATT: u8 = 0x1E;
 
 pub struct Cartridge {
     pub title: String,
+    pub manufacturer_code: String, // 4 character code on newer cartridges, empty on older ones
     pub gameboy_type: u8,       // Type of gameboy; 0x80 = CGB, 0x00 = Other/Not CGB
     pub is_super_gameboy: u8,   // Super Gameboy functionality; 0x00 = Gameboy, 0x03 = SGB
     pub cartridge_type: u8,
     pub rom_size: u8,           // Size of the ROM (How many banks)
     pub ram_size: u8,           // Size of the RAM (How many banks)
+    pub rom_size_bytes: usize,
+    pub ram_size_bytes: usize,
     pub region: u8,             // Region, 0 = Japanese, 1 = Non-Japanese
     pub licensee: u8,           // Licensee code, 0x33 = Check 0x144, 0x79 = Accolade, 0xA4 = Konami (SGB Won't work if != 0x33)
+    pub new_licensee: String,   // Two character licensee code at 0x144, used when licensee is 0x33
     pub version: u8,
-    pub checksum: u16
+    pub header_checksum: u8,
+    pub checksum: u16,
+    pub is_header_checksum_valid: bool,
+    pub is_checksum_valid: bool,
+    pub mapper: Box<dyn Mapper> // Owns the ROM and RAM and handles bank switching
 }
 
 impl Cartridge {
+    #[allow(clippy::new_without_default)]
     pub fn new() -> Self {
         debug!("Initializing Cartridge");
 
         Cartridge {
             title: String::from("                "),
+            manufacturer_code: String::new(),
             gameboy_type: 0,
             is_super_gameboy: 0,
             cartridge_type: ROM_ONLY,
             rom_size: 0,
             ram_size: 0,
+            rom_size_bytes: 0x8000,
+            ram_size_bytes: 0,
             region: 0,
             licensee: 0x33,
+            new_licensee: String::from("00"),
             version: 0,
-            checksum: 0
+            header_checksum: 0,
+            checksum: 0,
+            is_header_checksum_valid: true,
+            is_checksum_valid: true,
+            mapper: Box::new(RomOnly::new(vec![0; 0x8000]))
+        }
+    }
+
+    // Parses the header at 0x100 - 0x14F. The checksums are checked against the rest of the data,
+    // a mismatch is only logged as plenty of homebrew and patched ROMs don't bother fixing them.
+    // The mapper is left as ROM only, the caller installs the real one once it has the ROM data
+    pub fn from_bytes(rom: &[u8]) -> Result<Cartridge, EmuError> {
+        // Header ends at 0x14F
+        if rom.len() < 0x150 {
+            return Err(EmuError::TruncatedRom { expected: 0x150, actual: rom.len() });
+        }
+
+        let mut cartridge = Cartridge::new();
+        cartridge.gameboy_type = rom[0x143];
+        cartridge.is_super_gameboy = rom[0x146];
+        cartridge.cartridge_type = rom[0x147];
+        cartridge.rom_size = rom[0x148];
+        cartridge.ram_size = rom[0x149];
+        // The loader sizes the ROM from the file anyway, so a bad ROM size byte isn't fatal
+        cartridge.rom_size_bytes = match rom_bank_count(rom[0x148]) {
+            Ok(bank_count) => bank_count * 0x4000,
+            Err(error) => {
+                warn!("{}, using the file size instead", error);
+                rom.len()
+            }
+        };
+        // A bad RAM size byte isn't fatal either, no RAM is the safe guess. MBC2 ignores this anyway,
+        // its 512 x 4 bit RAM is built into the chip
+        cartridge.ram_size_bytes = match ram_size_bytes(rom[0x149]) {
+            Ok(size) => size,
+            Err(error) => {
+                warn!("{}, assuming the cartridge has no RAM", error);
+                0
+            }
+        };
+        cartridge.region = rom[0x14A];
+        cartridge.licensee = rom[0x14B];
+        cartridge.new_licensee = String::from_utf8_lossy(&rom[0x144..0x146]).to_string();
+        cartridge.version = rom[0x14C];
+        cartridge.header_checksum = rom[0x14D];
+        cartridge.checksum = (rom[0x14E] as u16) << 8 | rom[0x14F] as u16;
+
+        // The title was 16 characters on the original cartridges. CGB cartridges took the last byte
+        // for the CGB flag, and later ones the 4 before that for a manufacturer code
+        let is_cgb = rom[0x143] == 0x80 || rom[0x143] == 0xC0;
+        let manufacturer_code = &rom[0x13F..0x143];
+        let title_end;
+        if is_cgb && cartridge.licensee == 0x33 && manufacturer_code.iter().all(|c| c.is_ascii_uppercase() || c.is_ascii_digit()) {
+            cartridge.manufacturer_code = String::from_utf8_lossy(manufacturer_code).to_string();
+            title_end = 0x13F;
+        } else if is_cgb {
+            title_end = 0x143;
+        } else {
+            title_end = 0x144;
+        }
+        let title: Vec<u8> = rom[0x134..title_end].iter().cloned().take_while(|c| *c != 0).collect();
+        cartridge.title = String::from_utf8_lossy(&title).trim_end().to_string();
+
+        let mut header_checksum: u8 = 0;
+        for byte in rom[0x134..0x14D].iter() {
+            header_checksum = header_checksum.wrapping_sub(*byte).wrapping_sub(1);
+        }
+        cartridge.is_header_checksum_valid = header_checksum == cartridge.header_checksum;
+        if !cartridge.is_header_checksum_valid {
+            warn!("Header checksum mismatch, header says {:#04X} but the header adds up to {:#04X}", cartridge.header_checksum, header_checksum);
+        }
+
+        let mut checksum: u16 = 0;
+        for (address, byte) in rom.iter().enumerate() {
+            if address != 0x14E && address != 0x14F {
+                checksum = checksum.wrapping_add(*byte as u16);
+            }
+        }
+        cartridge.is_checksum_valid = checksum == cartridge.checksum;
+        if !cartridge.is_checksum_valid {
+            warn!("Global checksum mismatch, header says {:#06X} but the ROM adds up to {:#06X}", cartridge.checksum, checksum);
+        }
+
+        return Ok(cartridge);
+    }
+
+    // Cartridges with a battery keep their external RAM when the power is off
+    pub fn has_battery(&self) -> bool {
+        match self.cartridge_type {
+            MBC1_RAM_BATT | MBC2_BATT | MBC3_TIMER_BATT | MBC3_TIMER_RAM_BATT | MBC3_RAM_BATT |
+            MBC5_RAM_BATT | MBC5_RUMB_SRAM_BATT => return true,
+            _ => return false
+        }
+    }
+
+    pub fn has_rtc(&self) -> bool {
+        return self.cartridge_type == MBC3_TIMER_BATT || self.cartridge_type == MBC3_TIMER_RAM_BATT;
+    }
+
+    pub fn has_rumble(&self) -> bool {
+        match self.cartridge_type {
+            MBC5_RUMBLE | MBC5_RUMBLE_SRAM | MBC5_RUMB_SRAM_BATT => return true,
+            _ => return false
+        }
+    }
+
+    pub fn cartridge_type_name(&self) -> &'static str {
+        match self.cartridge_type {
+            0x00 => return "ROM ONLY",
+            0x01 => return "MBC1",
+            0x02 => return "MBC1+RAM",
+            0x03 => return "MBC1+RAM+BATTERY",
+            0x05 => return "MBC2",
+            0x06 => return "MBC2+BATTERY",
+            0x08 => return "ROM+RAM",
+            0x09 => return "ROM+RAM+BATTERY",
+            0x0B => return "MMM01",
+            0x0C => return "MMM01+RAM",
+            0x0D => return "MMM01+RAM+BATTERY",
+            0x0F => return "MBC3+TIMER+BATTERY",
+            0x10 => return "MBC3+TIMER+RAM+BATTERY",
+            0x11 => return "MBC3",
+            0x12 => return "MBC3+RAM",
+            0x13 => return "MBC3+RAM+BATTERY",
+            0x19 => return "MBC5",
+            0x1A => return "MBC5+RAM",
+            0x1B => return "MBC5+RAM+BATTERY",
+            0x1C => return "MBC5+RUMBLE",
+            0x1D => return "MBC5+RUMBLE+RAM",
+            0x1E => return "MBC5+RUMBLE+RAM+BATTERY",
+            0x20 => return "MBC6",
+            0x22 => return "MBC7+SENSOR+RUMBLE+RAM+BATTERY",
+            0xFC => return "POCKET CAMERA",
+            0xFD => return "BANDAI TAMA5",
+            0xFE => return "HuC3",
+            0xFF => return "HuC1+RAM+BATTERY",
+            _ => return "Unknown"
+        }
+    }
+
+    pub fn cgb_support_name(&self) -> &'static str {
+        match self.gameboy_type {
+            0x80 => return "CGB enhanced",
+            0xC0 => return "CGB only",
+            _ => return "DMG"
+        }
+    }
+
+    pub fn supports_sgb(&self) -> bool {
+        return self.is_super_gameboy == 0x03;
+    }
+
+    pub fn destination_name(&self) -> &'static str {
+        match self.region {
+            0x00 => return "Japan",
+            _ => return "Overseas"
         }
     }
 
+    pub fn licensee_name(&self) -> &'static str {
+        if self.licensee == 0x33 {
+            return new_licensee_name(&self.new_licensee);
+        }
+        return old_licensee_name(self.licensee);
+    }
+
     pub fn print_cartridge(&mut self) {
         debug!("Cartridge Data");
         debug!("Title: '{}'", self.title);
-        debug!("GB Type: {:#04X}", self.gameboy_type);
-        debug!("Is SGB: {:#04X}", self.is_super_gameboy);
-        debug!("Cart Type: {:#04X}", self.cartridge_type);
-        debug!("ROM Size: {:#04X}", self.rom_size);
-        debug!("RAM Size: {:#04X}", self.ram_size);
-        debug!("Region: {:#04X}", self.region);
-        debug!("Licensee: {:#04X}", self.licensee);
-        debug!("Version: {:#04X}", self.version);
-        debug!("CheckSum: {:#06X}", self.checksum);
-    }
-}
\ No newline at end of file
+        if !self.manufacturer_code.is_empty() {
+            debug!("Manufacturer: {}", self.manufacturer_code);
+        }
+        debug!("GB Type: {}", self.cgb_support_name());
+        debug!("Is SGB: {}", self.supports_sgb());
+        debug!("Cart Type: {}", self.cartridge_type_name());
+        debug!("ROM Size: {} KiB", self.rom_size_bytes / 1024);
+        debug!("RAM Size: {} KiB", self.ram_size_bytes / 1024);
+        debug!("Region: {}", self.destination_name());
+        debug!("Licensee: {}", self.licensee_name());
+        debug!("Version: {}", self.version);
+        debug!("Header CheckSum: {:#04X} ({})", self.header_checksum, if self.is_header_checksum_valid { "OK" } else { "mismatch" });
+        debug!("CheckSum: {:#06X} ({})", self.checksum, if self.is_checksum_valid { "OK" } else { "mismatch" });
+    }
+}
+
+// Number of 16k ROM banks for the ROM size byte in the header
+pub fn rom_bank_count(rom_size: u8) -> Result<usize, EmuError> {
+    match rom_size {
+        0 => return Ok(2),
+        1 => return Ok(4),
+        2 => return Ok(8),
+        3 => return Ok(16),
+        4 => return Ok(32),
+        5 => return Ok(64),
+        6 => return Ok(128),
+        7 => return Ok(256),
+        8 => return Ok(512),
+        _ => return Err(EmuError::BadRomSize(rom_size))
+    }
+}
+
+// Bytes of external RAM for the RAM size byte in the header
+pub fn ram_size_bytes(ram_size: u8) -> Result<usize, EmuError> {
+    match ram_size {
+        0 => return Ok(0),
+        1 => return Ok(2 * 1024),
+        2 => return Ok(8 * 1024),
+        3 => return Ok(32 * 1024),
+        4 => return Ok(128 * 1024),
+        5 => return Ok(64 * 1024),
+        _ => return Err(EmuError::BadRamSize(ram_size))
+    }
+}
+
+// Publisher for the licensee byte at 0x14B
+fn old_licensee_name(code: u8) -> &'static str {
+    match code {
+        0x00 => return "None",
+        0x01 => return "Nintendo",
+        0x08 => return "Capcom",
+        0x09 => return "HOT-B",
+        0x0A => return "Jaleco",
+        0x0B => return "Coconuts Japan",
+        0x0C => return "Elite Systems",
+        0x13 => return "EA (Electronic Arts)",
+        0x18 => return "Hudson Soft",
+        0x19 => return "ITC Entertainment",
+        0x1A => return "Yanoman",
+        0x1D => return "Japan Clary",
+        0x1F => return "Virgin Games Ltd.",
+        0x24 => return "PCM Complete",
+        0x25 => return "San-X",
+        0x28 => return "Kemco",
+        0x29 => return "SETA Corporation",
+        0x30 => return "Infogrames",
+        0x31 => return "Nintendo",
+        0x32 => return "Bandai",
+        0x34 => return "Konami",
+        0x35 => return "HectorSoft",
+        0x38 => return "Capcom",
+        0x39 => return "Banpresto",
+        0x3C => return "Entertainment Interactive",
+        0x3E => return "Gremlin",
+        0x41 => return "Ubi Soft",
+        0x42 => return "Atlus",
+        0x44 => return "Malibu Interactive",
+        0x46 => return "Angel",
+        0x47 => return "Spectrum HoloByte",
+        0x49 => return "Irem",
+        0x4A => return "Virgin Games Ltd.",
+        0x4D => return "Malibu Interactive",
+        0x4F => return "U.S. Gold",
+        0x50 => return "Absolute",
+        0x51 => return "Acclaim Entertainment",
+        0x52 => return "Activision",
+        0x53 => return "Sammy USA Corporation",
+        0x54 => return "GameTek",
+        0x55 => return "Park Place",
+        0x56 => return "LJN",
+        0x57 => return "Matchbox",
+        0x59 => return "Milton Bradley Company",
+        0x5A => return "Mindscape",
+        0x5B => return "Romstar",
+        0x5C => return "Naxat Soft",
+        0x5D => return "Tradewest",
+        0x60 => return "Titus Interactive",
+        0x61 => return "Virgin Games Ltd.",
+        0x67 => return "Ocean Software",
+        0x69 => return "EA (Electronic Arts)",
+        0x6E => return "Elite Systems",
+        0x6F => return "Electro Brain",
+        0x70 => return "Infogrames",
+        0x71 => return "Interplay Entertainment",
+        0x72 => return "Broderbund",
+        0x73 => return "Sculptured Software",
+        0x75 => return "The Sales Curve Limited",
+        0x78 => return "THQ",
+        0x79 => return "Accolade",
+        0x7A => return "Triffix Entertainment",
+        0x7C => return "MicroProse",
+        0x7F => return "Kemco",
+        0x80 => return "Misawa Entertainment",
+        0x83 => return "LOZC G.",
+        0x86 => return "Tokuma Shoten",
+        0x8B => return "Bullet-Proof Software",
+        0x8C => return "Vic Tokai Corp.",
+        0x8E => return "Ape Inc.",
+        0x8F => return "I'Max",
+        0x91 => return "Chunsoft Co.",
+        0x92 => return "Video System",
+        0x93 => return "Tsubaraya Productions",
+        0x95 => return "Varie",
+        0x96 => return "Yonezawa/S'Pal",
+        0x97 => return "Kemco",
+        0x99 => return "Arc",
+        0x9A => return "Nihon Bussan",
+        0x9B => return "Tecmo",
+        0x9C => return "Imagineer",
+        0x9D => return "Banpresto",
+        0x9F => return "Nova",
+        0xA1 => return "Hori Electric",
+        0xA2 => return "Bandai",
+        0xA4 => return "Konami",
+        0xA6 => return "Kawada",
+        0xA7 => return "Takara",
+        0xA9 => return "Technos Japan",
+        0xAA => return "Broderbund",
+        0xAC => return "Toei Animation",
+        0xAD => return "Toho",
+        0xAF => return "Namco",
+        0xB0 => return "Acclaim Entertainment",
+        0xB1 => return "ASCII Corporation or Nexsoft",
+        0xB2 => return "Bandai",
+        0xB4 => return "Square Enix",
+        0xB6 => return "HAL Laboratory",
+        0xB7 => return "SNK",
+        0xB9 => return "Pony Canyon",
+        0xBA => return "Culture Brain",
+        0xBB => return "Sunsoft",
+        0xBD => return "Sony Imagesoft",
+        0xBF => return "Sammy Corporation",
+        0xC0 => return "Taito",
+        0xC2 => return "Kemco",
+        0xC3 => return "Square",
+        0xC4 => return "Tokuma Shoten",
+        0xC5 => return "Data East",
+        0xC6 => return "Tonkin House",
+        0xC8 => return "Koei",
+        0xC9 => return "UFL",
+        0xCA => return "Ultra Games",
+        0xCB => return "VAP, Inc.",
+        0xCC => return "Use Corporation",
+        0xCD => return "Meldac",
+        0xCE => return "Pony Canyon",
+        0xCF => return "Angel",
+        0xD0 => return "Taito",
+        0xD1 => return "SOFEL",
+        0xD2 => return "Quest",
+        0xD3 => return "Sigma Enterprises",
+        0xD4 => return "ASK Kodansha Co.",
+        0xD6 => return "Naxat Soft",
+        0xD7 => return "Copya System",
+        0xD9 => return "Banpresto",
+        0xDA => return "Tomy",
+        0xDB => return "LJN",
+        0xDD => return "Nippon Computer Systems",
+        0xDE => return "Human Ent.",
+        0xDF => return "Altron",
+        0xE0 => return "Jaleco",
+        0xE1 => return "Towa Chiki",
+        0xE2 => return "Yutaka",
+        0xE3 => return "Varie",
+        0xE5 => return "Epoch",
+        0xE7 => return "Athena",
+        0xE8 => return "Asmik Ace Entertainment",
+        0xE9 => return "Natsume",
+        0xEA => return "King Records",
+        0xEB => return "Atlus",
+        0xEC => return "Epic/Sony Records",
+        0xEE => return "IGS",
+        0xF0 => return "A Wave",
+        0xF3 => return "Extreme Entertainment",
+        0xFF => return "LJN",
+        _ => return "Unknown"
+    }
+}
+
+// Publisher for the two character licensee code at 0x144, used when 0x14B is 0x33
+fn new_licensee_name(code: &str) -> &'static str {
+    match code {
+        "00" => return "None",
+        "01" => return "Nintendo Research & Development 1",
+        "08" => return "Capcom",
+        "13" => return "EA (Electronic Arts)",
+        "18" => return "Hudson Soft",
+        "19" => return "B-AI",
+        "20" => return "KSS",
+        "22" => return "Planning Office WADA",
+        "24" => return "PCM Complete",
+        "25" => return "San-X",
+        "28" => return "Kemco",
+        "29" => return "SETA Corporation",
+        "30" => return "Viacom",
+        "31" => return "Nintendo",
+        "32" => return "Bandai",
+        "33" => return "Ocean Software/Acclaim Entertainment",
+        "34" => return "Konami",
+        "35" => return "HectorSoft",
+        "37" => return "Taito",
+        "38" => return "Hudson Soft",
+        "39" => return "Banpresto",
+        "41" => return "Ubi Soft",
+        "42" => return "Atlus",
+        "44" => return "Malibu Interactive",
+        "46" => return "Angel",
+        "47" => return "Bullet-Proof Software",
+        "49" => return "Irem",
+        "50" => return "Absolute",
+        "51" => return "Acclaim Entertainment",
+        "52" => return "Activision",
+        "53" => return "Sammy USA Corporation",
+        "54" => return "Konami",
+        "55" => return "Hi Tech Expressions",
+        "56" => return "LJN",
+        "57" => return "Matchbox",
+        "58" => return "Mattel",
+        "59" => return "Milton Bradley Company",
+        "60" => return "Titus Interactive",
+        "61" => return "Virgin Games Ltd.",
+        "64" => return "Lucasfilm Games",
+        "67" => return "Ocean Software",
+        "69" => return "EA (Electronic Arts)",
+        "70" => return "Infogrames",
+        "71" => return "Interplay Entertainment",
+        "72" => return "Broderbund",
+        "73" => return "Sculptured Software",
+        "75" => return "The Sales Curve Limited",
+        "78" => return "THQ",
+        "79" => return "Accolade",
+        "80" => return "Misawa Entertainment",
+        "83" => return "LOZC G.",
+        "86" => return "Tokuma Shoten",
+        "87" => return "Tsukuda Original",
+        "91" => return "Chunsoft Co.",
+        "92" => return "Video System",
+        "93" => return "Ocean Software/Acclaim Entertainment",
+        "95" => return "Varie",
+        "96" => return "Yonezawa/S'Pal",
+        "97" => return "Kaneko",
+        "99" => return "Pack-In-Video",
+        "9H" => return "Bottom Up",
+        "A4" => return "Konami (Yu-Gi-Oh!)",
+        "BL" => return "MTO",
+        "DK" => return "Kodansha",
+        _ => return "Unknown"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cartridge_of_type(cartridge_type: u8) -> Cartridge {
+        let mut cartridge = Cartridge::new();
+        cartridge.cartridge_type = cartridge_type;
+        return cartridge;
+    }
+
+    #[test]
+    fn unknown_ram_size_falls_back_to_no_ram() {
+        let mut rom = vec![0; 0x8000];
+        rom[0x147] = MBC1_RAM;
+        rom[0x149] = 0x07;
+        let cartridge = Cartridge::from_bytes(&rom).unwrap();
+        assert_eq!(cartridge.ram_size_bytes, 0);
+    }
+
+    #[test]
+    fn mbc5_battery_types() {
+        assert!(!cartridge_of_type(MBC5).has_battery());
+        assert!(!cartridge_of_type(MBC5_RAM).has_battery());
+        assert!(cartridge_of_type(MBC5_RAM_BATT).has_battery());
+        assert!(!cartridge_of_type(MBC5_RUMBLE).has_battery());
+        assert!(!cartridge_of_type(MBC5_RUMBLE_SRAM).has_battery());
+        assert!(cartridge_of_type(MBC5_RUMB_SRAM_BATT).has_battery());
+    }
+}
@@ -1,6 +1,15 @@
-use std::process::exit;
+#![allow(clippy::needless_return)]
 
+use std::io::{self, Read, Write};
+
+use crate::disasm::{ACCUMULATOR_OPS, ALU, CONDITIONS, R16, R16_MEMORY, R16_STACK, R8, ROTATES};
+use crate::error::EmuError;
+use crate::interrupts::Interrupt;
 use crate::mmu::MMU;
+use crate::savestate::{StateReader, StateWriter};
+
+#[cfg(test)]
+mod tests;
 
 // Flag Bits
 const ZERO_BIT: u8        = 0x80;
@@ -8,17 +17,13 @@ const SUBTRACTION_BIT: u8 = 0x40;
 const HALF_CARRY_BIT: u8  = 0x20;
 const CARRY_BIT: u8       = 0x10;
 
-// Interrupts
-pub const VBLANK_INTERRUPT_BIT: u8 = 0x01;
-pub const LCD_INTERRUPT_BIT: u8    = 0x02;
-pub const TIMER_INTERRUPT_BIT: u8  = 0x04;
-pub const SERIAL_INTERRUPT_BIT: u8 = 0x08;
-pub const JOYPAD_INTERRUPT_BIT: u8 = 0x10;
+// Pushing PC and jumping to an interrupt vector takes 5 machine cycles
+const INTERRUPT_DISPATCH_MACHINE_CYCLES: u8 = 5;
 
-const OPERATION_BYTES: [u16; 256] = [
+pub(crate) const OPERATION_BYTES: [u16; 256] = [
 //  0  1  2  3  4  5  6  7  8  9  A  B  C  D  E  F
     1, 3, 1, 1, 1, 1, 2, 1, 3, 1, 1, 1, 1, 1, 2, 1, // 0
-    1, 3, 1, 1, 1, 1, 2, 1, 2, 1, 1, 1, 1, 1, 2, 1, // 1
+    2, 3, 1, 1, 1, 1, 2, 1, 2, 1, 1, 1, 1, 1, 2, 1, // 1
     2, 3, 1, 1, 1, 1, 2, 1, 2, 1, 1, 1, 1, 1, 2, 1, // 2
     2, 3, 1, 1, 1, 1, 2, 1, 2, 1, 1, 1, 1, 1, 2, 1, // 3
     1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, // 4
@@ -35,3183 +40,846 @@ const OPERATION_BYTES: [u16; 256] = [
     2, 1, 1, 1, 0, 1, 2, 1, 2, 1, 3, 1, 0, 0, 2, 1  // F
 ];
 
-const OPERATION_MACHINE_CYCLES: [u8; 256] = [
-//  0  1  2  3  4  5  6  7  8  9  A  B  C  D  E  F
-    1, 3, 2, 2, 1, 1, 2, 1, 5, 2, 2, 2, 1, 1, 2, 1, // 0
-    0, 3, 2, 2, 1, 1, 2, 1, 3, 2, 2, 2, 1, 1, 2, 1, // 1
-    2, 3, 2, 2, 1, 1, 2, 1, 2, 2, 2, 2, 1, 1, 2, 1, // 2
-    2, 3, 2, 2, 3, 3, 3, 1, 2, 2, 2, 2, 1, 1, 2, 1, // 3
-    1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1, // 4
-    1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1, // 5
-    1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1, // 6
-    2, 2, 2, 2, 2, 2, 1, 2, 1, 1, 1, 1, 1, 1, 2, 1, // 7
-    1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1, // 8
-    1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1, // 9
-    1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1, // A
-    1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1, // B
-    2, 3, 3, 4, 3, 4, 2, 4, 2, 4, 3, 0, 3, 6, 2, 4, // C
-    2, 3, 3, 0, 3, 4, 2, 4, 2, 4, 3, 0, 3, 0, 2, 4, // D
-    3, 3, 2, 0, 0, 4, 2, 4, 4, 1, 4, 0, 0, 0, 2, 4, // E
-    3, 3, 2, 1, 0, 4, 2, 4, 3, 2, 4, 1, 0, 0, 2, 4  // F
-];
-
-const OPERATION_MACHINE_CYCLES_BRANCHED: [u8; 256] = [
+pub(crate) const OPERATION_MACHINE_CYCLES: [u8; 256] = [
 //  0  1  2  3  4  5  6  7  8  9  A  B  C  D  E  F
     1, 3, 2, 2, 1, 1, 2, 1, 5, 2, 2, 2, 1, 1, 2, 1, // 0
-    0, 3, 2, 2, 1, 1, 2, 1, 3, 2, 2, 2, 1, 1, 2, 1, // 1
-    3, 3, 2, 2, 1, 1, 2, 1, 3, 2, 2, 2, 1, 1, 2, 1, // 2
-    3, 3, 2, 2, 3, 3, 3, 1, 3, 2, 2, 2, 1, 1, 2, 1, // 3
-    1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1, // 4
-    1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1, // 5
-    1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1, // 6
-    2, 2, 2, 2, 2, 2, 1, 2, 1, 1, 1, 1, 1, 1, 2, 1, // 7
-    1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1, // 8
-    1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1, // 9
-    1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1, // A
-    1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1, // B
-    5, 3, 4, 4, 6, 4, 2, 4, 5, 4, 4, 0, 6, 6, 2, 4, // C
-    5, 3, 4, 0, 6, 4, 2, 4, 5, 4, 4, 0, 6, 0, 2, 4, // D
-    3, 3, 2, 0, 0, 4, 2, 4, 4, 1, 4, 0, 0, 0, 2, 4, // E
-    3, 3, 2, 1, 0, 4, 2, 4, 3, 2, 4, 1, 0, 0, 2, 4  // F
-];
-
-#[derive(Copy, Clone)]
-struct HiLoRegister {
-    pub lo: u8,
-    pub hi: u8
-}
-
-union Register16 {
-    hilo: HiLoRegister,
-    word: u16
-}
-
-pub struct Clock {
-    m: u8,
-    t: u8
-}
-
-pub struct CPU {
-    af: Register16,
-    bc: Register16,
-    de: Register16,
-    hl: Register16,
-    pub stack_pointer: u16,
-    pub program_counter: u16,
-    pub skip_bios: bool,
-    pub clock: Clock,
-    pub interrupt_master_enable: bool
-}
-
-impl CPU {
-    pub fn new() -> Self {
-        debug!("Initializing CPU");
-
-        CPU {
-            af: Register16{word: 0},
-            bc: Register16{word: 0},
-            de: Register16{word: 0},
-            hl: Register16{word: 0},
-            stack_pointer: 0,
-            program_counter: 0,
-            skip_bios: false,
-            clock: Clock{ m: 0, t: 0 },
-            interrupt_master_enable: true
-        }
-    }
-
-    pub fn get_clock_m(&self) -> u8 {
-        return self.clock.m;
-    }
-
-    pub fn get_clock_t(&self) -> u8 {
-        return self.clock.t;
-    }
-
-    pub fn tick(&mut self, mmu: &mut MMU) -> u8 {
-        // Fetch opcode
-        let opcode = mmu.read_byte(self.program_counter);
-
-        // Call relevant function which emulates the opcode
-        match opcode {
-            0xCB => {
-                self.process_cb_opcode(mmu);
-            },
-            _ => {
-                self.process_opcode(mmu, opcode);
-            }
-        }
-
-        return opcode;
-    }
-
-    fn process_opcode(&mut self, mmu: &mut MMU, opcode: u8) {
-        let mut use_machine_cycles_branched: bool = false;
-        let mut increment_program_counter: bool = true;
-
-        match opcode {
-            0x00 => {
-                trace!("{:#04X}: NOP.", opcode);
-            },
-            0x01 => {
-                trace!("{:#04X}: LD BC,d16. BC:{:#06X} <- d16:{:#06X}", opcode, self.read_register_bc(), mmu.read_word(self.program_counter + 1));
-
-                self.write_register_bc(mmu.read_word(self.program_counter + 1));
-            },
-            0x02 => {
-                trace!("{:#04X}: LD (BC),A. BC:{:#06X} <- A: {:#04X}", opcode, self.read_register_bc(), self.read_register_a());
-
-                mmu.write_byte(self.read_register_bc(), self.read_register_a());
-            },
-            0x03 => {
-                trace!("{:#04X}: INC BC. BC:{:#06X} -> {:#06X}", opcode, self.read_register_bc(), self.read_register_bc().wrapping_add(1));
-
-                self.write_register_bc(self.read_register_bc().wrapping_add(1));
-            },
-            0x04 => {
-                trace!("{:#04X}: INC B. B:{:#04X} -> {:#04X}", opcode, self.read_register_b(), self.read_register_b().wrapping_add(1));
-
-                let result = self.increase_register_u8(self.read_register_b());
-                self.write_register_b(result);
-            },
-            0x05 => {
-                trace!("{:#04X}: DEC B. B:{:#04X} -> {:#04X}", opcode, self.read_register_b(), self.read_register_b().wrapping_sub(1));
-
-                let result = self.decrease_register_u8(self.read_register_b());
-                self.write_register_b(result);
-            },
-            0x06 => {
-                trace!("{:#04X}: LD B,d8. B:{:#04X} <- d8:{:#04X}", opcode, self.read_register_b(), mmu.read_byte(self.program_counter + 1));
-
-                self.write_register_b(mmu.read_byte(self.program_counter + 1));
-            },
-            0x07 => {
-                trace!("{:#04X}: RLC A. A:{:#04X}", opcode, self.read_register_a());
-
-                let result = self.rotate_left(self.read_register_a(), false);
-                self.write_register_a(result);
-            }
-            0x08 => {
-                trace!("{:#04X}: LD (a16),SP. (a16):{:#06X} <- SP:{:#06X}", opcode, mmu.read_word(self.program_counter + 1), self.stack_pointer);
-
-                let next_word = mmu.read_word(self.program_counter + 1);
-                mmu.write_word(next_word, self.stack_pointer);
-            },
-            0x09 => {
-                trace!("{:#04X}: ADD HL,BC. HL:{:#06X} + BC:{:#06X}", opcode, self.read_register_hl(), self.read_register_bc());
-
-                self.add_u16_to_hl(self.read_register_bc());
-            },
-            0x0A => {
-                trace!("{:#04X}: LD A,(BC). A:{:#04X} <- (BC):{:#04X}", opcode, self.read_register_a(), mmu.read_byte(self.read_register_bc()));
-
-                self.write_register_a(mmu.read_byte(self.read_register_bc()));
-            },
-            0x0B => {
-                trace!("{:#04X}: DEC BC. BC:{:#04X} -> {:#04X}", opcode, self.read_register_bc(), self.read_register_bc().wrapping_sub(1));
-
-                self.write_register_bc(self.read_register_bc().wrapping_sub(1));
-            },
-            0x0C => {
-                trace!("{:#04X}: INC C. C:{:#04X} -> {:#04X}", opcode, self.read_register_c(), self.read_register_c().wrapping_add(1));
-
-                let result = self.increase_register_u8(self.read_register_c());
-                self.write_register_c(result);
-            },
-            0x0D => {
-                trace!("{:#04X}: DEC C. C:{:#04X} -> {:#04X}", opcode, self.read_register_c(), self.read_register_c().wrapping_sub(1));
-
-                let result = self.decrease_register_u8(self.read_register_c());
-                self.write_register_c(result);
-            },
-            0x0E => {
-                trace!("{:#04X}: LD C,d8. C:{:#04X} <- d8:{:#04X}", opcode, self.read_register_c(), mmu.read_byte(self.program_counter + 1));
-
-                self.write_register_c(mmu.read_byte(self.program_counter + 1));
-            },
-            0x0F => {
-                trace!("{:#04X}: RRC A. A:{:#04X}", opcode, self.read_register_a());
-
-                let result = self.rotate_right(self.read_register_a(), false);
-                self.write_register_a(result);
-            },
-            0x10 => {
-                error!("{:#04X}: STOP.", opcode);
-                mmu.gpu.canvas.clear();
-                mmu.gpu.canvas.present();
-                // TODO - Stop Audio
-
-                // TODO
-                exit(1);
-
-                // if mmu.gpu.get_display_status() == 0 {
-                //     // TODO - GBC = Black screen, audio still on
-                // } else {
-                //     // TODO - GBC = White screen, audio still on
-                // }
-            },
-            0x11 => {
-                trace!("{:#04X}: LD DE,d16. DE:{:#06X} <- d16:{:#06X}", opcode, self.read_register_de(), mmu.read_word(self.program_counter + 1));
-
-                self.write_register_de(mmu.read_word(self.program_counter + 1));
-            },
-            0x12 => {
-                trace!("{:#04X}: LD (DE),A. DE:{:#06X} <- A: {:#04X}", opcode, self.read_register_de(), self.read_register_a());
-
-                mmu.write_byte(self.read_register_de(), self.read_register_a());
-            },
-            0x13 => {
-                trace!("{:#04X}: INC DE. DE:{:#06X} -> {:#06X}", opcode, self.read_register_de(), self.read_register_de().wrapping_add(1));
-
-                self.write_register_de(self.read_register_de().wrapping_add(1));
-            },
-            0x14 => {
-                trace!("{:#04X}: INC D. D:{:#04X} -> {:#04X}", opcode, self.read_register_d(), self.read_register_d().wrapping_add(1));
-
-                let result = self.increase_register_u8(self.read_register_d());
-                self.write_register_d(result);
-            },
-            0x15 => {
-                trace!("{:#04X}: DEC D. D:{:#04X} -> {:#04X}", opcode, self.read_register_d(), self.read_register_d().wrapping_sub(1));
-
-                let result = self.decrease_register_u8(self.read_register_d());
-                self.write_register_d(result);
-            },
-            0x16 => {
-                trace!("{:#04X}: LD D,d8. D:{:#04X} <- d8:{:#04X}", opcode, self.read_register_d(), mmu.read_byte(self.program_counter + 1));
-
-                self.write_register_d(mmu.read_byte(self.program_counter + 1));
-            },
-            0x17 => {
-                trace!("{:#04X}: RL A. A:{:#04X}", opcode, self.read_register_a());
-
-                let result = self.rotate_left_through_carry(self.read_register_a(), false);
-                self.write_register_a(result);
-            },
-            0x18 => {
-                trace!("{:#04X}: JR r8.", opcode);
-
-                let next_byte_signed: i8 = mmu.read_byte(self.program_counter + 1) as i8;
-                if next_byte_signed < 0 {
-                    trace!("Jumping to {:#06X}", self.program_counter - ((next_byte_signed + 2) * -1) as u16);
-                    self.program_counter -= ((next_byte_signed + 2) * -1) as u16;
-                } else {
-                    trace!("Jumping to {:#06X}", self.program_counter + (next_byte_signed + 2) as u16);
-                    self.program_counter += (next_byte_signed + 2) as u16;
-                }
-
-                increment_program_counter = false;
-            },
-            0x19 => {
-                trace!("{:#04X}: ADD HL,DE. HL:{:#06X} + DE:{:#06X}", opcode, self.read_register_hl(), self.read_register_de());
-
-                self.add_u16_to_hl(self.read_register_de());
-            },
-            0x1A => {
-                trace!("{:#04X}: LD A,(DE). A:{:#04X} <- (DE):{:#04X}", opcode, self.read_register_a(), mmu.read_byte(self.read_register_de()));
-
-                self.write_register_a(mmu.read_byte(self.read_register_de()));
-            },
-            0x1B => {
-                trace!("{:#04X}: DEC DE. DE:{:#04X} -> {:#04X}", opcode, self.read_register_de(), self.read_register_de().wrapping_sub(1));
-
-                self.write_register_de(self.read_register_de().wrapping_sub(1));
-            },
-            0x1C => {
-                trace!("{:#04X}: INC E. E:{:#04X} -> {:#04X}", opcode, self.read_register_e(), self.read_register_e().wrapping_add(1));
-
-                let result = self.increase_register_u8(self.read_register_e());
-                self.write_register_e(result);
-            },
-            0x1D => {
-                trace!("{:#04X}: DEC E. E:{:#04X} -> {:#04X}", opcode, self.read_register_e(), self.read_register_e().wrapping_sub(1));
-
-                let result = self.decrease_register_u8(self.read_register_e());
-                self.write_register_e(result);
-            },
-            0x1E => {
-                trace!("{:#04X}: LD E,d8. E:{:#04X} <- d8:{:#04X}", opcode, self.read_register_e(), mmu.read_byte(self.program_counter + 1));
-
-                self.write_register_e(mmu.read_byte(self.program_counter + 1));
-            },
-            0x1F => {
-                trace!("{:#04X}: RR A. A:{:#04X}", opcode, self.read_register_a());
-
-                let result = self.rotate_right_through_carry(self.read_register_a(), false);
-                self.write_register_a(result);
-            },
-            0x20 => {
-                trace!("{:#04X}: JR NZ,r8. Z:{:#04X}", opcode, self.read_flag(ZERO_BIT));
-
-                if self.read_flag(ZERO_BIT) == 0 {
-                    let next_byte_signed: i8 = mmu.read_byte(self.program_counter + 1) as i8;
-                    if next_byte_signed < 0 {
-                        trace!("Jumping to {:#06X}", self.program_counter - ((next_byte_signed + 2) * -1) as u16);
-                        self.program_counter -= ((next_byte_signed + 2) * -1) as u16;
-                    } else {
-                        trace!("Jumping to {:#06X}", self.program_counter + (next_byte_signed + 2) as u16);
-                        self.program_counter += (next_byte_signed + 2) as u16;
-                    }
-
-                    increment_program_counter = false;
-                    use_machine_cycles_branched = true;
-                }
-            },
-            0x21 => {
-                trace!("{:#04X}: LD HL,d16. HL:{:#06X} <- d16:{:#06X}", opcode, self.read_register_hl(), mmu.read_word(self.program_counter + 1));
-
-                self.write_register_hl(mmu.read_word(self.program_counter + 1));
-            },
-            0x22 => {
-                trace!("{:#04X}: LD (HL+),A. (HL):{:#06X} <- A:{:#04X}", opcode, mmu.read_byte(self.read_register_hl()), self.read_register_a());
-
-                mmu.write_byte(self.read_register_hl(), self.read_register_a());
-                self.write_register_hl(self.read_register_hl().wrapping_add(1));
-            },
-            0x23 => {
-                trace!("{:#04X}: INC HL. HL:{:#06X} -> {:#06X}", opcode, self.read_register_hl(), self.read_register_hl().wrapping_add(1));
-
-                self.write_register_hl(self.read_register_hl().wrapping_add(1));
-            },
-            0x24 => {
-                trace!("{:#04X}: INC H. H:{:#04X} -> {:#04X}", opcode, self.read_register_h(), self.read_register_h().wrapping_add(1));
-
-                let result = self.increase_register_u8(self.read_register_h());
-                self.write_register_h(result);
-            },
-            0x25 => {
-                trace!("{:#04X}: DEC H. H:{:#04X} -> {:#04X}", opcode, self.read_register_h(), self.read_register_h().wrapping_sub(1));
-
-                let result = self.decrease_register_u8(self.read_register_h());
-                self.write_register_h(result);
-            },
-            0x26 => {
-                trace!("{:#04X}: LD H,d8. H:{:#04X} <- d8:{:#04X}", opcode, self.read_register_h(), mmu.read_byte(self.program_counter + 1));
-
-                self.write_register_h(mmu.read_byte(self.program_counter + 1));
-            },
-            0x27 => {
-                trace!("{:#04X}: DAA. A:{:#04X}", opcode, self.read_register_a());
-
-                let mut a: i16 = self.read_register_a() as i16;
-
-                if self.read_flag(SUBTRACTION_BIT) == 0 {
-                    if self.read_flag(HALF_CARRY_BIT) == 1 || (a & 0xF) > 9 {
-                        a += 0x06;
-                    }
-
-                    if self.read_flag(CARRY_BIT) == 1 || (a > 0x9F) {
-                        a += 0x60;
-                    }
-                } else {
-                    if self.read_flag(HALF_CARRY_BIT) == 1 {
-                        a = (a - 6) & 0xFF;
-                    }
-
-                    if self.read_flag(CARRY_BIT) == 1 {
-                        a -= 0x60;
-                    }
-                }
-
-                self.unset_flag_bit(HALF_CARRY_BIT);
-                self.unset_flag_bit(ZERO_BIT);
-
-                if a & 0x100 == 0x100 {
-                    self.set_flag_bit(CARRY_BIT);
-                }
-
-                a &= 0xFF;
-
-                if a == 0 {
-                    self.set_flag_bit(ZERO_BIT);
-                } else {
-                    self.unset_flag_bit(ZERO_BIT);
-                }
-
-                self.write_register_a(a as u8);
-            },
-            0x28 => {
-                trace!("{:#04X}: JR Z,r8. Z:{:#04X}", opcode, self.read_flag(ZERO_BIT));
-
-                if self.read_flag(ZERO_BIT) == 1 {
-                    let next_byte_signed: i8 = mmu.read_byte(self.program_counter + 1) as i8;
-                    if next_byte_signed < 0 {
-                        trace!("Jumping to {:#06X}", self.program_counter - ((next_byte_signed + 2) * -1) as u16);
-                        self.program_counter -= ((next_byte_signed + 2) * -1) as u16;
-                    } else {
-                        trace!("Jumping to {:#06X}", self.program_counter + (next_byte_signed + 2) as u16);
-                        self.program_counter += (next_byte_signed + 2) as u16;
-                    }
-
-                    increment_program_counter = false;
-                    use_machine_cycles_branched = true;
-                }
-            },
-            0x29 => {
-                trace!("{:#04X}: ADD HL,HL. HL:{:#06X} + HL:{:#06X}", opcode, self.read_register_hl(), self.read_register_hl());
-
-                self.add_u16_to_hl(self.read_register_hl());
-            },
-            0x2A => {
-                trace!("{:#04X}: LD A,(HL+). A:{:#04X} <- (HL):{:#06X}", opcode, self.read_register_a(), mmu.read_byte(self.read_register_hl()));
-
-                let byte = mmu.read_byte(self.read_register_hl());
-                self.write_register_a(byte);
-                self.write_register_hl(self.read_register_hl().wrapping_add(1));
-            },
-            0x2B => {
-                trace!("{:#04X}: DEC HL. HL:{:#04X} -> {:#04X}", opcode, self.read_register_hl(), self.read_register_hl().wrapping_sub(1));
-
-                self.write_register_hl(self.read_register_hl().wrapping_sub(1));
-            },
-            0x2C => {
-                trace!("{:#04X}: INC L. L:{:#04X} -> {:#04X}", opcode, self.read_register_l(), self.read_register_l().wrapping_add(1));
-
-                let result = self.increase_register_u8(self.read_register_l());
-                self.write_register_l(result);
-            },
-            0x2D => {
-                trace!("{:#04X}: DEC L. L:{:#04X} -> {:#04X}", opcode, self.read_register_l(), self.read_register_l().wrapping_sub(1));
-
-                let result = self.decrease_register_u8(self.read_register_l());
-                self.write_register_l(result);
-            },
-            0x2E => {
-                trace!("{:#04X}: LD L,d8. L:{:#04X} <- d8:{:#04X}", opcode, self.read_register_l(), mmu.read_byte(self.program_counter + 1));
-
-                self.write_register_l(mmu.read_byte(self.program_counter + 1));
-            },
-            0x2F => {
-                trace!("{:#04X}: CPL A. A:{:#04X}", opcode, self.read_register_a());
-
-                self.write_register_a(!self.read_register_a());
-                self.set_flag_bit(HALF_CARRY_BIT);
-                self.set_flag_bit(SUBTRACTION_BIT);
-            },
-            0x30 => {
-                trace!("{:#04X}: JR NC,r8. C:{:#04X}", opcode, self.read_flag(CARRY_BIT));
-
-                if self.read_flag(CARRY_BIT) == 0 {
-                    let next_byte_signed: i8 = mmu.read_byte(self.program_counter + 1) as i8;
-                    if next_byte_signed < 0 {
-                        trace!("Jumping to {:#06X}", self.program_counter - ((next_byte_signed + 2) * -1) as u16);
-                        self.program_counter -= ((next_byte_signed + 2) * -1) as u16;
-                    } else {
-                        trace!("Jumping to {:#06X}", self.program_counter + (next_byte_signed + 2) as u16);
-                        self.program_counter += (next_byte_signed + 2) as u16;
-                    }
-
-                    increment_program_counter = false;
-                    use_machine_cycles_branched = true;
-                }
-            },
-            0x31 => {
-                trace!("{:#04X}: LD SP,d16. SP:{:#06X} <- d16:{:#06X}", opcode, self.stack_pointer, mmu.read_word(self.program_counter + 1));
-                self.stack_pointer = mmu.read_word(self.program_counter + 1);
-            },
-            0x32 => {
-                trace!("{:#04X}: LD (HL-),A. (HL):{:#06X} <- A:{:#04X}", opcode, mmu.read_byte(self.read_register_hl()), self.read_register_a());
-
-                mmu.write_byte(self.read_register_hl(), self.read_register_a());
-                self.write_register_hl(self.read_register_hl().wrapping_sub(1));
-            },
-            0x33 => {
-                trace!("{:#04X}: INC SP. SP:{:#06X} -> {:#06X}", opcode, self.stack_pointer, self.stack_pointer.wrapping_add(1));
-
-                self.stack_pointer = self.stack_pointer.wrapping_add(1);
-            },
-            0x34 => {
-                trace!("{:#04X}: INC (HL). (HL):{:#04X} -> {:#04X}", opcode, mmu.read_byte(self.read_register_hl()), mmu.read_byte(self.read_register_hl()) + 1);
-
-                let hl_byte = mmu.read_byte(self.read_register_hl());
-                let result = self.increase_register_u8(hl_byte);
-                mmu.write_byte(self.read_register_hl(), result);
-            },
-            0x35 => {
-                trace!("{:#04X}: DEC (HL). (HL):{:#04X} -> {:#04X}", opcode, mmu.read_byte(self.read_register_hl()), mmu.read_byte(self.read_register_hl()) - 1);
-
-                let hl_byte = mmu.read_byte(self.read_register_hl());
-                let result = self.decrease_register_u8(hl_byte);
-                mmu.write_byte(self.read_register_hl(), result);
-            },
-            0x36 => {
-                trace!("{:#04X}: LD (HL),d8. HL:{:#06X} <- d8:{:#04X}", opcode, self.read_register_hl(), mmu.read_byte(self.program_counter + 1));
-
-                let next_byte = mmu.read_byte(self.program_counter + 1);
-                mmu.write_byte(self.read_register_hl(), next_byte);
-            },
-            0x37 => {
-                trace!("{:#04X}: SCF. (Set Carry Flag, Unset Half Carry and Subtraction Flags)", opcode);
-
-                self.set_flag_bit(CARRY_BIT);
-                self.unset_flag_bit(HALF_CARRY_BIT);
-                self.unset_flag_bit(SUBTRACTION_BIT);
-            },
-            0x38 => {
-                trace!("{:#04X}: JR C,r8. C:{:#04X}", opcode, self.read_flag(CARRY_BIT));
-
-                if self.read_flag(CARRY_BIT) == 1 {
-                    let next_byte_signed: i8 = mmu.read_byte(self.program_counter + 1) as i8;
-                    if next_byte_signed < 0 {
-                        trace!("Jumping to {:#06X}", self.program_counter - ((next_byte_signed + 2) * -1) as u16);
-                        self.program_counter -= ((next_byte_signed + 2) * -1) as u16;
-                    } else {
-                        trace!("Jumping to {:#06X}", self.program_counter + (next_byte_signed + 2) as u16);
-                        self.program_counter += (next_byte_signed + 2) as u16;
-                    }
-
-                    increment_program_counter = false;
-                    use_machine_cycles_branched = true;
-                }
-            },
-            0x39 => {
-                trace!("{:#04X}: ADD HL,SP. HL:{:#06X} + SP:{:#06X}", opcode, self.read_register_hl(), self.stack_pointer);
-
-                self.add_u16_to_hl(self.stack_pointer);
-            },
-            0x3A => {
-                trace!("{:#04X}: LD A,(HL-). A:{:#04X} <- (HL):{:#06X}", opcode, self.read_register_a(), mmu.read_byte(self.read_register_hl()));
-
-                let byte = mmu.read_byte(self.read_register_hl());
-                self.write_register_a(byte);
-                self.write_register_hl(self.read_register_hl().wrapping_sub(1));
-            },
-            0x3B => {
-                trace!("{:#04X}: DEC SP. SP:{:#04X} -> {:#04X}", opcode, self.stack_pointer, self.stack_pointer.wrapping_sub(1));
-
-                self.stack_pointer = self.stack_pointer.wrapping_sub(1);
-            },
-            0x3C => {
-                trace!("{:#04X}: INC A. A:{:#04X} -> {:#04X}", opcode, self.read_register_a(), self.read_register_a().wrapping_add(1));
-
-                let result = self.increase_register_u8(self.read_register_a());
-                self.write_register_a(result);
-            },
-            0x3D => {
-                trace!("{:#04X}: DEC A. A:{:#04X} -> {:#04X}", opcode, self.read_register_a(), self.read_register_a().wrapping_sub(1));
-
-                let result = self.decrease_register_u8(self.read_register_a());
-                self.write_register_a(result);
-            },
-            0x3E => {
-                trace!("{:#04X}: LD A,d8. A:{:#04X} <- d8:{:#04X}", opcode, self.read_register_a(), mmu.read_byte(self.program_counter + 1));
-
-                self.write_register_a(mmu.read_byte(self.program_counter + 1));
-            },
-            0x3F => {
-                trace!("{:#04X}: CCF.", opcode);
-
-                if self.read_flag(CARRY_BIT) == 1 {
-                    self.unset_flag_bit(CARRY_BIT);
-                } else {
-                    self.set_flag_bit(CARRY_BIT);
-                }
-
-                self.unset_flag_bit(HALF_CARRY_BIT);
-                self.unset_flag_bit(SUBTRACTION_BIT);
-            },
-            0x40 => {
-                trace!("{:#04X}: LD B,B. B:{:#04X} <- B:{:#04X}", opcode, self.read_register_b(), self.read_register_b());
-
-                self.write_register_b(self.read_register_b());
-            },
-            0x41 => {
-                trace!("{:#04X}: LD B,C. B:{:#04X} <- C:{:#04X}", opcode, self.read_register_b(), self.read_register_c());
-
-                self.write_register_b(self.read_register_c());
-            },
-            0x42 => {
-                trace!("{:#04X}: LD B,D. B:{:#04X} <- D:{:#04X}", opcode, self.read_register_b(), self.read_register_d());
-
-                self.write_register_b(self.read_register_d());
-            },
-            0x43 => {
-                trace!("{:#04X}: LD B,E. B:{:#04X} <- E:{:#04X}", opcode, self.read_register_b(), self.read_register_e());
-
-                self.write_register_b(self.read_register_e());
-            },
-            0x44 => {
-                trace!("{:#04X}: LD B,H. B:{:#04X} <- H:{:#04X}", opcode, self.read_register_b(), self.read_register_h());
-
-                self.write_register_b(self.read_register_h());
-            },
-            0x45 => {
-                trace!("{:#04X}: LD B,L. B:{:#04X} <- L:{:#04X}", opcode, self.read_register_b(), self.read_register_l());
-
-                self.write_register_b(self.read_register_l());
-            },
-            0x46 => {
-                trace!("{:#04X}: LD B,(HL). B:{:#04X} <- (HL):{:#04X}", opcode, self.read_register_b(), mmu.read_byte(self.read_register_hl()));
-
-                self.write_register_b(mmu.read_byte(self.read_register_hl()));
-            },
-            0x47 => {
-                trace!("{:#04X}: LD B,A. B:{:#04X} <- A:{:#04X}", opcode, self.read_register_b(), self.read_register_a());
-
-                self.write_register_b(self.read_register_a());
-            },
-            0x48 => {
-                trace!("{:#04X}: LD C,B. C:{:#04X} <- B:{:#04X}", opcode, self.read_register_c(), self.read_register_b());
-
-                self.write_register_c(self.read_register_b());
-            },
-            0x49 => {
-                trace!("{:#04X}: LD C,C. C:{:#04X} <- C:{:#04X}", opcode, self.read_register_c(), self.read_register_c());
-
-                self.write_register_c(self.read_register_c());
-            },
-            0x4A => {
-                trace!("{:#04X}: LD C,D. C:{:#04X} <- D:{:#04X}", opcode, self.read_register_c(), self.read_register_d());
-
-                self.write_register_c(self.read_register_d());
-            },
-            0x4B => {
-                trace!("{:#04X}: LD C,E. C:{:#04X} <- E:{:#04X}", opcode, self.read_register_c(), self.read_register_e());
-
-                self.write_register_c(self.read_register_e());
-            },
-            0x4C => {
-                trace!("{:#04X}: LD C,H. C:{:#04X} <- H:{:#04X}", opcode, self.read_register_c(), self.read_register_h());
-
-                self.write_register_c(self.read_register_h());
-            },
-            0x4D => {
-                trace!("{:#04X}: LD C,L. C:{:#04X} <- L:{:#04X}", opcode, self.read_register_c(), self.read_register_l());
-
-                self.write_register_c(self.read_register_l());
-            },
-            0x4E => {
-                trace!("{:#04X}: LD C,(HL). C:{:#04X} <- (HL):{:#04X}", opcode, self.read_register_c(), mmu.read_byte(self.read_register_hl()));
-
-                self.write_register_c(mmu.read_byte(self.read_register_hl()));
-            },
-            0x4F => {
-                trace!("{:#04X}: LD C,A. C:{:#04X} <- A:{:#04X}", opcode, self.read_register_c(), self.read_register_a());
-
-                self.write_register_c(self.read_register_a());
-            },
-            0x50 => {
-                trace!("{:#04X}: LD D,B. D:{:#04X} <- B:{:#04X}", opcode, self.read_register_d(), self.read_register_b());
-
-                self.write_register_d(self.read_register_b());
-            },
-            0x51 => {
-                trace!("{:#04X}: LD D,C. D:{:#04X} <- C:{:#04X}", opcode, self.read_register_d(), self.read_register_c());
-
-                self.write_register_d(self.read_register_c());
-            },
-            0x52 => {
-                trace!("{:#04X}: LD D,D. D:{:#04X} <- D:{:#04X}", opcode, self.read_register_d(), self.read_register_d());
-
-                self.write_register_d(self.read_register_d());
-            },
-            0x53 => {
-                trace!("{:#04X}: LD D,E. D:{:#04X} <- E:{:#04X}", opcode, self.read_register_d(), self.read_register_e());
-
-                self.write_register_d(self.read_register_e());
-            },
-            0x54 => {
-                trace!("{:#04X}: LD D,H. D:{:#04X} <- H:{:#04X}", opcode, self.read_register_d(), self.read_register_h());
-
-                self.write_register_d(self.read_register_h());
-            },
-            0x55 => {
-                trace!("{:#04X}: LD D,L. D:{:#04X} <- L:{:#04X}", opcode, self.read_register_d(), self.read_register_l());
-
-                self.write_register_d(self.read_register_l());
-            },
-            0x56 => {
-                trace!("{:#04X}: LD D,(HL). D:{:#04X} <- (HL):{:#04X}", opcode, self.read_register_d(), mmu.read_byte(self.read_register_hl()));
-
-                self.write_register_d(mmu.read_byte(self.read_register_hl()));
-            },
-            0x57 => {
-                trace!("{:#04X}: LD D,A. D:{:#04X} <- A:{:#04X}", opcode, self.read_register_d(), self.read_register_a());
-
-                self.write_register_d(self.read_register_a());
-            },
-            0x58 => {
-                trace!("{:#04X}: LD E,B. E:{:#04X} <- B:{:#04X}", opcode, self.read_register_e(), self.read_register_b());
-
-                self.write_register_e(self.read_register_b());
-            },
-            0x59 => {
-                trace!("{:#04X}: LD E,C. E:{:#04X} <- C:{:#04X}", opcode, self.read_register_e(), self.read_register_c());
-
-                self.write_register_e(self.read_register_c());
-            },
-            0x5A => {
-                trace!("{:#04X}: LD E,D. E:{:#04X} <- D:{:#04X}", opcode, self.read_register_e(), self.read_register_d());
-
-                self.write_register_e(self.read_register_d());
-            },
-            0x5B => {
-                trace!("{:#04X}: LD E,E. E:{:#04X} <- E:{:#04X}", opcode, self.read_register_e(), self.read_register_e());
-
-                self.write_register_e(self.read_register_e());
-            },
-            0x5C => {
-                trace!("{:#04X}: LD E,H. E:{:#04X} <- H:{:#04X}", opcode, self.read_register_e(), self.read_register_h());
-
-                self.write_register_e(self.read_register_h());
-            },
-            0x5D => {
-                trace!("{:#04X}: LD E,L. E:{:#04X} <- L:{:#04X}", opcode, self.read_register_e(), self.read_register_l());
-
-                self.write_register_e(self.read_register_l());
-            },
-            0x5E => {
-                trace!("{:#04X}: LD E,(HL). E:{:#04X} <- (HL):{:#04X}", opcode, self.read_register_e(), mmu.read_byte(self.read_register_hl()));
-
-                self.write_register_e(mmu.read_byte(self.read_register_hl()));
-            },
-            0x5F => {
-                trace!("{:#04X}: LD E,A. E:{:#04X} <- A:{:#04X}", opcode, self.read_register_e(), self.read_register_a());
-
-                self.write_register_e(self.read_register_a());
-            },
-            0x60 => {
-                trace!("{:#04X}: LD H,B. H:{:#04X} <- B:{:#04X}", opcode, self.read_register_h(), self.read_register_b());
-
-                self.write_register_h(self.read_register_b());
-            },
-            0x61 => {
-                trace!("{:#04X}: LD H,C. H:{:#04X} <- C:{:#04X}", opcode, self.read_register_h(), self.read_register_c());
-
-                self.write_register_h(self.read_register_c());
-            },
-            0x62 => {
-                trace!("{:#04X}: LD H,D. H:{:#04X} <- D:{:#04X}", opcode, self.read_register_h(), self.read_register_d());
-
-                self.write_register_h(self.read_register_d());
-            },
-            0x63 => {
-                trace!("{:#04X}: LD H,E. H:{:#04X} <- E:{:#04X}", opcode, self.read_register_h(), self.read_register_e());
-
-                self.write_register_h(self.read_register_e());
-            },
-            0x64 => {
-                trace!("{:#04X}: LD H,H. H:{:#04X} <- H:{:#04X}", opcode, self.read_register_h(), self.read_register_h());
-
-                self.write_register_h(self.read_register_h());
-            },
-            0x65 => {
-                trace!("{:#04X}: LD H,L. H:{:#04X} <- L:{:#04X}", opcode, self.read_register_h(), self.read_register_l());
-
-                self.write_register_h(self.read_register_l());
-            },
-            0x66 => {
-                trace!("{:#04X}: LD H,(HL). H:{:#04X} <- (HL):{:#04X}", opcode, self.read_register_h(), mmu.read_byte(self.read_register_hl()));
-
-                self.write_register_h(mmu.read_byte(self.read_register_hl()));
-            },
-            0x67 => {
-                trace!("{:#04X}: LD H,A. H:{:#04X} <- A:{:#04X}", opcode, self.read_register_h(), self.read_register_a());
-
-                self.write_register_h(self.read_register_a());
-            },
-            0x68 => {
-                trace!("{:#04X}: LD L,B. L:{:#04X} <- B:{:#04X}", opcode, self.read_register_l(), self.read_register_b());
-
-                self.write_register_l(self.read_register_b());
-            },
-            0x69 => {
-                trace!("{:#04X}: LD L,C. L:{:#04X} <- C:{:#04X}", opcode, self.read_register_l(), self.read_register_c());
-
-                self.write_register_l(self.read_register_c());
-            },
-            0x6A => {
-                trace!("{:#04X}: LD L,D. L:{:#04X} <- D:{:#04X}", opcode, self.read_register_l(), self.read_register_d());
-
-                self.write_register_l(self.read_register_d());
-            },
-            0x6B => {
-                trace!("{:#04X}: LD L,E. L:{:#04X} <- E:{:#04X}", opcode, self.read_register_l(), self.read_register_e());
-
-                self.write_register_l(self.read_register_e());
-            },
-            0x6C => {
-                trace!("{:#04X}: LD L,H. L:{:#04X} <- H:{:#04X}", opcode, self.read_register_l(), self.read_register_h());
-
-                self.write_register_l(self.read_register_h());
-            },
-            0x6D => {
-                trace!("{:#04X}: LD L,L. L:{:#04X} <- L:{:#04X}", opcode, self.read_register_l(), self.read_register_l());
-
-                self.write_register_l(self.read_register_l());
-            },
-            0x6E => {
-                trace!("{:#04X}: LD L,(HL). L:{:#04X} <- (HL):{:#04X}", opcode, self.read_register_l(), mmu.read_byte(self.read_register_hl()));
-
-                self.write_register_l(mmu.read_byte(self.read_register_hl()));
-            },
-            0x6F => {
-                trace!("{:#04X}: LD L,A. L:{:#04X} <- A:{:#04X}", opcode, self.read_register_l(), self.read_register_a());
-
-                self.write_register_l(self.read_register_a());
-            },
-            0x70 => {
-                trace!("{:#04X}: LD (HL),B. HL:{:#06X} <- B:{:#04X}", opcode, self.read_register_hl(), self.read_register_b());
-
-                mmu.write_byte(self.read_register_hl(), self.read_register_b());
-            },
-            0x71 => {
-                trace!("{:#04X}: LD (HL),C. HL:{:#06X} <- C:{:#04X}", opcode, self.read_register_hl(), self.read_register_c());
-
-                mmu.write_byte(self.read_register_hl(), self.read_register_c());
-            },
-            0x72 => {
-                trace!("{:#04X}: LD (HL),D. HL:{:#06X} <- D:{:#04X}", opcode, self.read_register_hl(), self.read_register_d());
-
-                mmu.write_byte(self.read_register_hl(), self.read_register_d());
-            },
-            0x73 => {
-                trace!("{:#04X}: LD (HL),E. HL:{:#06X} <- E:{:#04X}", opcode, self.read_register_hl(), self.read_register_e());
-
-                mmu.write_byte(self.read_register_hl(), self.read_register_e());
-            },
-            0x74 => {
-                trace!("{:#04X}: LD (HL),H. HL:{:#06X} <- H:{:#04X}", opcode, self.read_register_hl(), self.read_register_h());
-
-                mmu.write_byte(self.read_register_hl(), self.read_register_h());
-            },
-            0x75 => {
-                trace!("{:#04X}: LD (HL),L. HL:{:#06X} <- L:{:#04X}", opcode, self.read_register_hl(), self.read_register_l());
-
-                mmu.write_byte(self.read_register_hl(), self.read_register_l());
-            },
-            0x76 => {
-                error!("{:#04X}: HALT. {}", opcode, mmu.interrupt_enable_register);
-
-                increment_program_counter = false;
-            },
-            0x77 => {
-                trace!("{:#04X}: LD (HL),A. HL:{:#06X} <- A: {:#04X}", opcode, self.read_register_hl(), self.read_register_a());
-
-                mmu.write_byte(self.read_register_hl(), self.read_register_a());
-            },
-            0x78 => {
-                trace!("{:#04X}: LD A,B. A:{:#04X} <- B:{:#04X}", opcode, self.read_register_a(), self.read_register_b());
-
-                self.write_register_a(self.read_register_b());
-            },
-            0x79 => {
-                trace!("{:#04X}: LD A,C. A:{:#04X} <- C:{:#04X}", opcode, self.read_register_a(), self.read_register_c());
-
-                self.write_register_a(self.read_register_c());
-            },
-            0x7A => {
-                trace!("{:#04X}: LD A,D. A:{:#04X} <- D:{:#04X}", opcode, self.read_register_a(), self.read_register_d());
-
-                self.write_register_a(self.read_register_d());
-            },
-            0x7B => {
-                trace!("{:#04X}: LD A,E. A:{:#04X} <- E:{:#04X}", opcode, self.read_register_a(), self.read_register_e());
-
-                self.write_register_a(self.read_register_e());
-            },
-            0x7C => {
-                trace!("{:#04X}: LD A,H. A:{:#04X} <- H:{:#04X}", opcode, self.read_register_a(), self.read_register_h());
-
-                self.write_register_a(self.read_register_h());
-            },
-            0x7D => {
-                trace!("{:#04X}: LD A,L. A:{:#04X} <- L:{:#04X}", opcode, self.read_register_a(), self.read_register_l());
-
-                self.write_register_a(self.read_register_l());
-            },
-            0x7E => {
-                trace!("{:#04X}: LD A,(HL). A:{:#04X} <- (HL):{:#04X}", opcode, self.read_register_a(), mmu.read_byte(self.read_register_hl()));
-
-                self.write_register_a(mmu.read_byte(self.read_register_hl()));
-            },
-            0x7F => {
-                trace!("{:#04X}: LD A,A. A:{:#04X} <- A:{:#04X}", opcode, self.read_register_a(), self.read_register_a());
-
-                self.write_register_a(self.read_register_a());
-            },
-            0x80 => {
-                trace!("{:#04X}: ADD A,B. A = {:#04X} + B:{:#04X}", opcode, self.read_register_a(), self.read_register_b());
-
-                self.add_u8_to_a(self.read_register_b());
-            },
-            0x81 => {
-                trace!("{:#04X}: ADD A,C. A = {:#04X} + C:{:#04X}", opcode, self.read_register_a(), self.read_register_c());
-
-                self.add_u8_to_a(self.read_register_c());
-            },
-            0x82 => {
-                trace!("{:#04X}: ADD A,D. A = {:#04X} + D:{:#04X}", opcode, self.read_register_a(), self.read_register_d());
-
-                self.add_u8_to_a(self.read_register_d());
-            },
-            0x83 => {
-                trace!("{:#04X}: ADD A,E. A = {:#04X} + E:{:#04X}", opcode, self.read_register_a(), self.read_register_e());
-
-                self.add_u8_to_a(self.read_register_e());
-            },
-            0x84 => {
-                trace!("{:#04X}: ADD A,H. A = {:#04X} + H:{:#04X}", opcode, self.read_register_a(), self.read_register_h());
-
-                self.add_u8_to_a(self.read_register_h());
-            },
-            0x85 => {
-                trace!("{:#04X}: ADD A,L. A = {:#04X} + L:{:#04X}", opcode, self.read_register_a(), self.read_register_l());
-
-                self.add_u8_to_a(self.read_register_l());
-            },
-            0x86 => {
-                trace!("{:#04X}: ADD A,(HL). A = {:#04X} + {:#04X}", opcode, self.read_register_a(), mmu.read_byte(self.read_register_hl()));
-
-                self.add_u8_to_a(mmu.read_byte(self.read_register_hl()));
-            },
-            0x87 => {
-                trace!("{:#04X}: ADD A,A. A = {:#04X} + A:{:#04X}", opcode, self.read_register_a(), self.read_register_a());
-
-                self.add_u8_to_a(self.read_register_a());
-            },
-            0x88 => {
-                trace!("{:#04X}: ADC A,B. A = {:#04X} + B:{:#04X}", opcode, self.read_register_a(), self.read_register_b());
-
-                self.add_u8_and_carry_to_a(self.read_register_b());
-            },
-            0x89 => {
-                trace!("{:#04X}: ADC A,C. A = {:#04X} + C:{:#04X}", opcode, self.read_register_a(), self.read_register_c());
-
-                self.add_u8_and_carry_to_a(self.read_register_c());
-            },
-            0x8A => {
-                trace!("{:#04X}: ADC A,D. A = {:#04X} + D:{:#04X}", opcode, self.read_register_a(), self.read_register_d());
-
-                self.add_u8_and_carry_to_a(self.read_register_d());
-            },
-            0x8B => {
-                trace!("{:#04X}: ADC A,E. A = {:#04X} + E:{:#04X}", opcode, self.read_register_a(), self.read_register_e());
-
-                self.add_u8_and_carry_to_a(self.read_register_e());
-            },
-            0x8C => {
-                trace!("{:#04X}: ADC A,H. A = {:#04X} + H:{:#04X}", opcode, self.read_register_a(), self.read_register_h());
-
-                self.add_u8_and_carry_to_a(self.read_register_h());
-            },
-            0x8D => {
-                trace!("{:#04X}: ADC A,L. A = {:#04X} + L:{:#04X}", opcode, self.read_register_a(), self.read_register_l());
-
-                self.add_u8_and_carry_to_a(self.read_register_l());
-            },
-            0x8E => {
-                trace!("{:#04X}: ADC A,(HL). A = {:#04X} + (HL):{:#04X}", opcode, self.read_register_a(), mmu.read_byte(self.read_register_hl()));
-
-                self.add_u8_and_carry_to_a(mmu.read_byte(self.read_register_hl()));
-            },
-            0x8F => {
-                trace!("{:#04X}: ADC A,A. A = {:#04X} + A:{:#04X}", opcode, self.read_register_a(), self.read_register_a());
-
-                self.add_u8_and_carry_to_a(self.read_register_a());
-            },
-            0x90 => {
-                trace!("{:#04X}: SUB B. A:{:#04X} - B:{:#04X}", opcode, self.read_register_a(), self.read_register_b());
-
-                self.subtract_u8_from_a(self.read_register_b());
-            },
-            0x91 => {
-                trace!("{:#04X}: SUB C. A:{:#04X} - C:{:#04X}", opcode, self.read_register_a(), self.read_register_c());
-
-                self.subtract_u8_from_a(self.read_register_c());
-            },
-            0x92 => {
-                trace!("{:#04X}: SUB D. A:{:#04X} - D:{:#04X}", opcode, self.read_register_a(), self.read_register_d());
-
-                self.subtract_u8_from_a(self.read_register_d());
-            },
-            0x93 => {
-                trace!("{:#04X}: SUB E. A:{:#04X} - E:{:#04X}", opcode, self.read_register_a(), self.read_register_e());
-
-                self.subtract_u8_from_a(self.read_register_e());
-            },
-            0x94 => {
-                trace!("{:#04X}: SUB H. A:{:#04X} - H:{:#04X}", opcode, self.read_register_a(), self.read_register_h());
-
-                self.subtract_u8_from_a(self.read_register_h());
-            },
-            0x95 => {
-                trace!("{:#04X}: SUB L. A:{:#04X} - L:{:#04X}", opcode, self.read_register_a(), self.read_register_l());
-
-                self.subtract_u8_from_a(self.read_register_l());
-            },
-            0x96 => {
-                trace!("{:#04X}: SUB (HL). A:{:#04X} - (HL):{:#04X}", opcode, self.read_register_a(), mmu.read_byte(self.read_register_hl()));
-
-                self.subtract_u8_from_a(mmu.read_byte(self.read_register_hl()));
-            },
-            0x97 => {
-                trace!("{:#04X}: SUB A. A:{:#04X} - A:{:#04X}", opcode, self.read_register_a(), self.read_register_a());
-
-                self.subtract_u8_from_a(self.read_register_a());
-            },
-            0x98 => {
-                trace!("{:#04X}: SBC B. A:{:#04X} - B:{:#04X}", opcode, self.read_register_a(), self.read_register_b());
-
-                self.subtract_u8_and_carry_from_a(self.read_register_b());
-            },
-            0x99 => {
-                trace!("{:#04X}: SBC C. A:{:#04X} - C:{:#04X}", opcode, self.read_register_a(), self.read_register_c());
-
-                self.subtract_u8_and_carry_from_a(self.read_register_c());
-            },
-            0x9A => {
-                trace!("{:#04X}: SBC D. A:{:#04X} - D:{:#04X}", opcode, self.read_register_a(), self.read_register_d());
-
-                self.subtract_u8_and_carry_from_a(self.read_register_d());
-            },
-            0x9B => {
-                trace!("{:#04X}: SBC E. A:{:#04X} - E:{:#04X}", opcode, self.read_register_a(), self.read_register_e());
-
-                self.subtract_u8_and_carry_from_a(self.read_register_e());
-            },
-            0x9C => {
-                trace!("{:#04X}: SBC H. A:{:#04X} - H:{:#04X}", opcode, self.read_register_a(), self.read_register_h());
-
-                self.subtract_u8_and_carry_from_a(self.read_register_h());
-            },
-            0x9D => {
-                trace!("{:#04X}: SBC L. A:{:#04X} - L:{:#04X}", opcode, self.read_register_a(), self.read_register_l());
-
-                self.subtract_u8_and_carry_from_a(self.read_register_l());
-            },
-            0x9E => {
-                trace!("{:#04X}: SBC (HL). A:{:#04X} - (HL):{:#04X}", opcode, self.read_register_a(), mmu.read_byte(self.read_register_hl()));
-
-                self.subtract_u8_and_carry_from_a(mmu.read_byte(self.read_register_hl()));
-            },
-            0x9F => {
-                trace!("{:#04X}: SBC A. A:{:#04X} - A:{:#04X}", opcode, self.read_register_a(), self.read_register_a());
-
-                self.subtract_u8_and_carry_from_a(self.read_register_a());
-            },
-            0xA0 => {
-                trace!("{:#04X}: AND B. B:{:#04X} -> {:#04X}", opcode, self.read_register_a(), self.read_register_a() & self.read_register_b());
-
-                self.and_with_register_a(self.read_register_b());
-            },
-            0xA1 => {
-                trace!("{:#04X}: AND C. C:{:#04X} -> {:#04X}", opcode, self.read_register_a(), self.read_register_a() & self.read_register_c());
-
-                self.and_with_register_a(self.read_register_c());
-            },
-            0xA2 => {
-                trace!("{:#04X}: AND D. D:{:#04X} -> {:#04X}", opcode, self.read_register_a(), self.read_register_a() & self.read_register_d());
-
-                self.and_with_register_a(self.read_register_d());
-            },
-            0xA3 => {
-                trace!("{:#04X}: AND E. E:{:#04X} -> {:#04X}", opcode, self.read_register_a(), self.read_register_a() & self.read_register_e());
-
-                self.and_with_register_a(self.read_register_e());
-            },
-            0xA4 => {
-                trace!("{:#04X}: AND H. H:{:#04X} -> {:#04X}", opcode, self.read_register_a(), self.read_register_a() & self.read_register_h());
-
-                self.and_with_register_a(self.read_register_h());
-            },
-            0xA5 => {
-                trace!("{:#04X}: AND L. L:{:#04X} -> {:#04X}", opcode, self.read_register_a(), self.read_register_a() & self.read_register_l());
-
-                self.and_with_register_a(self.read_register_l());
-            },
-            0xA6 => {
-                trace!("{:#04X}: AND (HL). B:{:#04X} -> {:#04X}", opcode, self.read_register_a(), self.read_register_a() & mmu.read_byte(self.read_register_hl()));
-
-                self.and_with_register_a(mmu.read_byte(self.read_register_hl()));
-            },
-            0xA7 => {
-                trace!("{:#04X}: AND A. A:{:#04X} -> {:#04X}", opcode, self.read_register_a(), self.read_register_a() & self.read_register_a());
-
-                self.and_with_register_a(self.read_register_a());
-            },
-            0xA8 => {
-                trace!("{:#04X}: XOR B. B:{:#04X} -> {:#04X}", opcode, self.read_register_a(), self.read_register_a() ^ self.read_register_b());
-
-                self.xor_with_register_a(self.read_register_b());
-            },
-            0xA9 => {
-                trace!("{:#04X}: XOR C. C:{:#04X} -> {:#04X}", opcode, self.read_register_a(), self.read_register_a() ^ self.read_register_c());
-
-                self.xor_with_register_a(self.read_register_c());
-            },
-            0xAA => {
-                trace!("{:#04X}: XOR D. D:{:#04X} -> {:#04X}", opcode, self.read_register_a(), self.read_register_a() ^ self.read_register_d());
-
-                self.xor_with_register_a(self.read_register_d());
-            },
-            0xAB => {
-                trace!("{:#04X}: XOR E. E:{:#04X} -> {:#04X}", opcode, self.read_register_a(), self.read_register_a() ^ self.read_register_e());
-
-                self.xor_with_register_a(self.read_register_e());
-            },
-            0xAC => {
-                trace!("{:#04X}: XOR H. H:{:#04X} -> {:#04X}", opcode, self.read_register_a(), self.read_register_a() ^ self.read_register_h());
-
-                self.xor_with_register_a(self.read_register_h());
-            },
-            0xAD => {
-                trace!("{:#04X}: XOR L. L:{:#04X} -> {:#04X}", opcode, self.read_register_a(), self.read_register_a() ^ self.read_register_l());
-
-                self.xor_with_register_a(self.read_register_l());
-            },
-            0xAE => {
-                trace!("{:#04X}: XOR (HL). (HL):{:#04X} -> {:#04X}", opcode, self.read_register_a(), self.read_register_a() ^ mmu.read_byte(self.read_register_hl()));
-
-                self.xor_with_register_a(mmu.read_byte(self.read_register_hl()));
-            },
-            0xAF => {
-                trace!("{:#04X}: XOR A. A:{:#04X} -> {:#04X}", opcode, self.read_register_a(), self.read_register_a() ^ self.read_register_a());
-
-                self.xor_with_register_a(self.read_register_a());
-            },
-            0xB0 => {
-                trace!("{:#04X}: OR B. B:{:#04X} -> {:#04X}", opcode, self.read_register_a(), self.read_register_a() | self.read_register_b());
-
-                self.or_with_register_a(self.read_register_b());
-            },
-            0xB1 => {
-                trace!("{:#04X}: OR C. C:{:#04X} -> {:#04X}", opcode, self.read_register_a(), self.read_register_a() | self.read_register_c());
-
-                self.or_with_register_a(self.read_register_c());
-            },
-            0xB2 => {
-                trace!("{:#04X}: OR D. D:{:#04X} -> {:#04X}", opcode, self.read_register_a(), self.read_register_a() | self.read_register_d());
-
-                self.or_with_register_a(self.read_register_d());
-            },
-            0xB3 => {
-                trace!("{:#04X}: OR E. E:{:#04X} -> {:#04X}", opcode, self.read_register_a(), self.read_register_a() | self.read_register_e());
-
-                self.or_with_register_a(self.read_register_e());
-            },
-            0xB4 => {
-                trace!("{:#04X}: OR H. H:{:#04X} -> {:#04X}", opcode, self.read_register_a(), self.read_register_a() | self.read_register_h());
-
-                self.or_with_register_a(self.read_register_h());
-            },
-            0xB5 => {
-                trace!("{:#04X}: OR L. L:{:#04X} -> {:#04X}", opcode, self.read_register_a(), self.read_register_a() | self.read_register_l());
-
-                self.or_with_register_a(self.read_register_l());
-            },
-            0xB6 => {
-                trace!("{:#04X}: OR (HL). (HL):{:#04X} -> {:#04X}", opcode, self.read_register_a(), self.read_register_a() | mmu.read_byte(self.read_register_hl()));
-
-                self.or_with_register_a(mmu.read_byte(self.read_register_hl()));
-            },
-            0xB7 => {
-                trace!("{:#04X}: OR A. A:{:#04X} -> {:#04X}", opcode, self.read_register_a(), self.read_register_a() | self.read_register_a());
-
-                self.or_with_register_a(self.read_register_a());
-            },
-            0xB8 => {
-                trace!("{:#04X}: CP B. A:{:#04X} B:{:#04X}", opcode, self.read_register_a(), self.read_register_b());
-
-                self.compare_with_register_a(self.read_register_b());
-            },
-            0xB9 => {
-                trace!("{:#04X}: CP C. A:{:#04X} C:{:#04X}", opcode, self.read_register_a(), self.read_register_c());
-
-                self.compare_with_register_a(self.read_register_c());
-            },
-            0xBA => {
-                trace!("{:#04X}: CP D. A:{:#04X} D:{:#04X}", opcode, self.read_register_a(), self.read_register_d());
-
-                self.compare_with_register_a(self.read_register_d());
-            },
-            0xBB => {
-                trace!("{:#04X}: CP E. A:{:#04X} E:{:#04X}", opcode, self.read_register_a(), self.read_register_e());
-
-                self.compare_with_register_a(self.read_register_e());
-            },
-            0xBC => {
-                trace!("{:#04X}: CP H. A:{:#04X} H:{:#04X}", opcode, self.read_register_a(), self.read_register_h());
-
-                self.compare_with_register_a(self.read_register_h());
-            },
-            0xBD => {
-                trace!("{:#04X}: CP L. A:{:#04X} L:{:#04X}", opcode, self.read_register_a(), self.read_register_l());
-
-                self.compare_with_register_a(self.read_register_l());
-            },
-            0xBE => {
-                trace!("{:#04X}: CP (HL). A:{:#04X} (HL):{:#04X}", opcode, self.read_register_a(), mmu.read_byte(self.read_register_hl()));
-
-                self.compare_with_register_a(mmu.read_byte(self.read_register_hl()));
-            },
-            0xBF => {
-                trace!("{:#04X}: CP A. A:{:#04X} A:{:#04X}", opcode, self.read_register_a(), self.read_register_a());
-
-                self.compare_with_register_a(self.read_register_a());
-            },
-            0xC0 => {
-                trace!("{:#04X}: RET NZ.", opcode);
-
-                if self.read_flag(ZERO_BIT) == 0 {
-                    trace!("Returning to {:#06X}", mmu.read_word(self.stack_pointer));
-                    self.program_counter = mmu.read_word(self.stack_pointer);
-                    self.stack_pointer += 2;
-                    increment_program_counter = false;
-                }
-            },
-            0xC1 => {
-                trace!("{:#04X}: POP BC. BC:{:#06X} <- {:#06X} SP:{:#06X}", opcode, self.read_register_bc(), mmu.read_word(self.stack_pointer), self.stack_pointer);
-
-                self.write_register_bc(mmu.read_word(self.stack_pointer));
-                self.stack_pointer += 2;
-            },
-            0xC2 => {
-                trace!("{:#04X}: JP NZ,a16.", opcode);
-
-                if self.read_flag(ZERO_BIT) == 0 {
-                    trace!("Jumping to {:#06X}", mmu.read_word(self.program_counter + 1));
-                    self.program_counter = mmu.read_word(self.program_counter + 1);
-                    increment_program_counter = false;
-                    use_machine_cycles_branched = true;
-                }
-            },
-            0xC3 => {
-                trace!("{:#04X}: JP a16. Jumping to {:#06X}", opcode, mmu.read_word(self.program_counter + 1));
-
-                self.program_counter = mmu.read_word(self.program_counter + 1);
-                increment_program_counter = false;
-            },
-            0xC4 => {
-                trace!("{:#04X}: CALL NZ,a16.", opcode);
-
-                if self.read_flag(ZERO_BIT) == 0 {
-                    trace!("Calling {:#06X}", mmu.read_word(self.program_counter + 1));
-
-                    // We wrote two bytes, so decrement accordingly (Stack grows downwards)
-                    self.stack_pointer -= 2;
-
-                    // Write address of next instruction to the stack
-                    mmu.write_word(self.stack_pointer, self.program_counter + 3);
-
-                    // Set program_counter to address of function
-                    self.program_counter = mmu.read_word(self.program_counter + 1);
-                    increment_program_counter = false;
-                    use_machine_cycles_branched = true;
-                }
-            },
-            0xC5 => {
-                trace!("{:#04X}: PUSH BC. SP:{:#06X} <- BC:{:#06X}", opcode, self.stack_pointer - 2, self.read_register_bc());
-
-                self.stack_pointer -= 2;
-                mmu.write_word(self.stack_pointer, self.read_register_bc());
-            },
-            0xC6 => {
-                trace!("{:#04X}: ADD A,d8. A = {:#04X} + d8:{:#04X}", opcode, self.read_register_a(), mmu.read_byte(self.program_counter + 1));
-
-                self.add_u8_to_a(mmu.read_byte(self.program_counter + 1));
-            },
-            0xC7 => {
-                trace!("{:#04X}: RST 0x0000", opcode);
-
-                mmu.write_word(self.stack_pointer, self.program_counter + 1);
-                self.stack_pointer -= 2;
-
-                self.program_counter = 0x0000;
-                increment_program_counter = false;
-            },
-            0xC8 => {
-                trace!("{:#04X}: RET Z.", opcode);
-
-                if self.read_flag(ZERO_BIT) == 1 {
-                    trace!("Returning to {:#06X}", mmu.read_word(self.stack_pointer));
-                    self.program_counter = mmu.read_word(self.stack_pointer);
-                    self.stack_pointer += 2;
-                    increment_program_counter = false;
-                }
-            },
-            0xC9 => {
-                trace!("{:#04X}: RET. Returning to {:#06X}", opcode, mmu.read_word(self.stack_pointer));
-                self.program_counter = mmu.read_word(self.stack_pointer);
-                self.stack_pointer += 2;
-                increment_program_counter = false;
-            },
-            0xCA => {
-                trace!("{:#04X}: JP Z,a16.", opcode);
-
-                if self.read_flag(ZERO_BIT) == 1 {
-                    trace!("Jumping to {:#06X}", mmu.read_word(self.program_counter + 1));
-                    self.program_counter = mmu.read_word(self.program_counter + 1);
-                    increment_program_counter = false;
-                    use_machine_cycles_branched = true;
-                }
-            },
-            0xCB => {
-                error!("0xCB instruction in the wrong OpCode table");
-                exit(1);
-            },
-            0xCC => {
-                trace!("{:#04X}: CALL Z,a16.", opcode);
-
-                if self.read_flag(ZERO_BIT) == 1 {
-                    trace!("Calling {:#06X}", mmu.read_word(self.program_counter + 1));
-
-                    // We wrote two bytes, so decrement accordingly (Stack grows downwards)
-                    self.stack_pointer -= 2;
-
-                    // Write address of next instruction to the stack
-                    mmu.write_word(self.stack_pointer, self.program_counter + 3);
-
-                    // Set program_counter to address of function
-                    self.program_counter = mmu.read_word(self.program_counter + 1);
-                    increment_program_counter = false;
-                    use_machine_cycles_branched = true;
-                }
-            },
-            0xCD => {
-                trace!("{:#04X}: CALL a16. Calling {:#06X}", opcode, mmu.read_word(self.program_counter + 1));
-
-                // We wrote two bytes, so decrement accordingly (Stack grows downwards)
-                self.stack_pointer -= 2;
-
-                // Write address of next instruction to the stack
-                mmu.write_word(self.stack_pointer, self.program_counter + 3);
-
-                // Set program_counter to address of function
-                self.program_counter = mmu.read_word(self.program_counter + 1);
-                increment_program_counter = false;
-            },
-            0xCE => {
-                trace!("{:#04X}: ADC A,d8. A = {:#04X} + d8:{:#04X}", opcode, self.read_register_a(), mmu.read_byte(self.program_counter + 1));
-
-                self.add_u8_and_carry_to_a(mmu.read_byte(self.program_counter + 1));
-            },
-            0xCF => {
-                trace!("{:#04X}: RST 0x0008", opcode);
-
-                mmu.write_word(self.stack_pointer, self.program_counter + 1);
-                self.stack_pointer -= 2;
-
-                self.program_counter = 0x0008;
-                increment_program_counter = false;
-            },
-            0xD0 => {
-                trace!("{:#04X}: RET NC.", opcode);
-
-                if self.read_flag(CARRY_BIT) == 0 {
-                    trace!("Returning to {:#06X}", mmu.read_word(self.stack_pointer));
-                    self.program_counter = mmu.read_word(self.stack_pointer);
-                    self.stack_pointer += 2;
-                    increment_program_counter = false;
-                }
-            },
-            0xD1 => {
-                trace!("{:#04X}: POP DE. DE:{:#06X} <- {:#06X} SP:{:#06X}", opcode, self.read_register_de(), mmu.read_word(self.stack_pointer), self.stack_pointer);
-
-                self.write_register_de(mmu.read_word(self.stack_pointer));
-                self.stack_pointer += 2;
-            },
-            0xD2 => {
-                trace!("{:#04X}: JP NC,a16.", opcode);
-
-                if self.read_flag(CARRY_BIT) == 0 {
-                    trace!("Jumping to {:#06X}", mmu.read_word(self.program_counter + 1));
-                    self.program_counter = mmu.read_word(self.program_counter + 1);
-                    increment_program_counter = false;
-                    use_machine_cycles_branched = true;
-                }
-            },
-            0xD4 => {
-                trace!("{:#04X}: CALL NC,a16.", opcode);
-
-                if self.read_flag(CARRY_BIT) == 0 {
-                    trace!("Calling {:#06X}", mmu.read_word(self.program_counter + 1));
-
-                    // We wrote two bytes, so decrement accordingly (Stack grows downwards)
-                    self.stack_pointer -= 2;
-
-                    // Write address of next instruction to the stack
-                    mmu.write_word(self.stack_pointer, self.program_counter + 3);
-
-                    // Set program_counter to address of function
-                    self.program_counter = mmu.read_word(self.program_counter + 1);
-                    increment_program_counter = false;
-                    use_machine_cycles_branched = true;
-                }
-            },
-            0xD5 => {
-                trace!("{:#04X}: PUSH DE. SP:{:#06X} <- DE:{:#06X}", opcode, self.stack_pointer - 2, self.read_register_de());
-
-                self.stack_pointer -= 2;
-                mmu.write_word(self.stack_pointer, self.read_register_de());
-            },
-            0xD6 => {
-                trace!("{:#04X}: SUB d8. A:{:#04X} - d8:{:#04X}", opcode, self.read_register_a(), mmu.read_byte(self.program_counter + 1));
-
-                self.subtract_u8_from_a(mmu.read_byte(self.program_counter + 1));
-            },
-            0xD7 => {
-                trace!("{:#04X}: RST 0x0010", opcode);
-
-                self.stack_pointer -= 2;
-                mmu.write_word(self.stack_pointer, self.program_counter + 1);
-
-                self.program_counter = 0x0010;
-                increment_program_counter = false;
-            },
-            0xD8 => {
-                trace!("{:#04X}: RET C.", opcode);
-
-                if self.read_flag(CARRY_BIT) == 1 {
-                    trace!("Returning to {:#06X}", mmu.read_word(self.stack_pointer));
-                    self.program_counter = mmu.read_word(self.stack_pointer);
-                    self.stack_pointer += 2;
-                    increment_program_counter = false;
-                }
-            },
-            0xD9 => {
-                trace!("{:#04X}: RETI. Returning to {:#06X}", opcode, mmu.read_word(self.stack_pointer));
-
-                self.program_counter = mmu.read_word(self.stack_pointer);
-                self.stack_pointer += 2;
-                increment_program_counter = false;
-
-                self.interrupt_master_enable = true;
-            },
-            0xDA => {
-                trace!("{:#04X}: JP C,a16.", opcode);
-
-                if self.read_flag(CARRY_BIT) == 1 {
-                    trace!("Jumping to {:#06X}", mmu.read_word(self.program_counter + 1));
-                    self.program_counter = mmu.read_word(self.program_counter + 1);
-                    increment_program_counter = false;
-                    use_machine_cycles_branched = true;
-                }
-            },
-            0xDC => {
-                trace!("{:#04X}: CALL C,a16.", opcode);
-
-                if self.read_flag(CARRY_BIT) == 1 {
-                    trace!("Calling {:#06X}", mmu.read_word(self.program_counter + 1));
-
-                    // We wrote two bytes, so decrement accordingly (Stack grows downwards)
-                    self.stack_pointer -= 2;
-
-                    // Write address of next instruction to the stack
-                    mmu.write_word(self.stack_pointer, self.program_counter + 3);
-
-                    // Set program_counter to address of function
-                    self.program_counter = mmu.read_word(self.program_counter + 1);
-                    increment_program_counter = false;
-                    use_machine_cycles_branched = true;
-                }
-            },
-            0xDE => {
-                trace!("{:#04X}: SBC d8. A:{:#04X} - d8:{:#04X}", opcode, self.read_register_a(), mmu.read_byte(self.program_counter + 1));
-
-                self.subtract_u8_and_carry_from_a(mmu.read_byte(self.program_counter + 1));
-            },
-            0xDF => {
-                trace!("{:#04X}: RST 0x0018", opcode);
-
-                self.stack_pointer -= 2;
-                mmu.write_word(self.stack_pointer, self.program_counter + 1);
-
-                self.program_counter = 0x0018;
-                increment_program_counter = false;
-            },
-            0xE0 => {
-                trace!("{:#04X}: LDH ($FF00+a8),A. $FF00+a8:{:#06X} <- A:{:#04X}", opcode, (0xFF00 + mmu.read_byte(self.program_counter + 1) as u16), self.read_register_a());
-
-                let next_byte = mmu.read_byte(self.program_counter + 1) as u16;
-                mmu.write_byte(0xFF00 + next_byte, self.read_register_a());
-            },
-            0xE1 => {
-                trace!("{:#04X}: POP HL. HL:{:#06X} <- {:#06X} SP:{:#06X}", opcode, self.read_register_hl(), mmu.read_word(self.stack_pointer), self.stack_pointer);
-
-                self.write_register_hl(mmu.read_word(self.stack_pointer));
-                self.stack_pointer += 2;
-            },
-            0xE2 => {
-                trace!("{:#04X}: LD ($FF00+C),A. $FF00+C:{:#06X} <- A:{:#04X}", opcode, (0xFF00 + self.read_register_c() as u16), self.read_register_a());
-
-                mmu.write_byte(0xFF00 + self.read_register_c() as u16, self.read_register_a());
-            },
-            0xE5 => {
-                trace!("{:#04X}: PUSH HL. SP:{:#06X} <- HL:{:#06X}", opcode, self.stack_pointer - 2, self.read_register_hl());
-
-                self.stack_pointer -= 2;
-                mmu.write_word(self.stack_pointer, self.read_register_hl());
-            },
-            0xE6 => {
-                trace!("{:#04X}: AND d8. A:{:#04X} -> {:#04X}", opcode, self.read_register_a(), self.read_register_a() & mmu.read_byte(self.program_counter + 1));
-
-                self.and_with_register_a(mmu.read_byte(self.program_counter + 1));
-            },
-            0xE7 => {
-                trace!("{:#04X}: RST 0x0020", opcode);
-
-                self.stack_pointer -= 2;
-                mmu.write_word(self.stack_pointer, self.program_counter + 1);
-
-                self.program_counter = 0x0020;
-                increment_program_counter = false;
-            },
-            0xE8 => {
-                trace!("{:#04X}: ADD SP,d8. SP:{:#06X} + d8:{:#04X}", opcode, self.stack_pointer, mmu.read_byte(self.program_counter + 1));
-
-                let result;
-                let next_byte_signed: i8 = mmu.read_byte(self.program_counter + 1) as i8;
-                if next_byte_signed < 0 {
-                    result = self.stack_pointer.wrapping_sub((next_byte_signed * -1) as u16);
-                } else {
-                    result = self.stack_pointer.wrapping_add(next_byte_signed as u16);
-                }
-
-                self.unset_flag_bit(ZERO_BIT);
-                self.unset_flag_bit(SUBTRACTION_BIT);
-
-                if ((self.stack_pointer ^ next_byte_signed as u16 ^ (result & 0xFFFF)) & 0x100) == 0x100 {
-                    self.set_flag_bit(CARRY_BIT);
-                } else {
-                    self.unset_flag_bit(CARRY_BIT);
-                }
-
-                if ((self.stack_pointer ^ next_byte_signed as u16 ^ (result & 0xFFFF)) & 0x10) == 0x10 {
-                    self.set_flag_bit(HALF_CARRY_BIT);
-                } else {
-                    self.unset_flag_bit(HALF_CARRY_BIT);
-                }
-
-                self.stack_pointer = result;
-            },
-            0xE9 => {
-                trace!("{:#04X}: JP HL. Jumping to {:#06X}", opcode, self.read_register_hl());
-
-                self.program_counter = self.read_register_hl();
-                increment_program_counter = false;
-            },
-            0xEA => {
-                trace!("{:#04X}: LD (a16),A. a16:{:#06X} <- A:{:#04X}", opcode, mmu.read_word(self.program_counter + 1), self.read_register_a());
-
-                let next_word = mmu.read_word(self.program_counter + 1);
-                mmu.write_byte(next_word, self.read_register_a());
-            },
-            0xEE => {
-                trace!("{:#04X}: XOR d8. d8:{:#04X} -> {:#04X}", opcode, self.read_register_a(), self.read_register_a() ^ mmu.read_byte(self.program_counter + 1));
-
-                self.xor_with_register_a(mmu.read_byte(self.program_counter + 1));
-            },
-            0xEF => {
-                trace!("{:#04X}: RST 0x0028", opcode);
-
-                self.stack_pointer -= 2;
-                mmu.write_word(self.stack_pointer, self.program_counter + 1);
-
-                self.program_counter = 0x0028;
-                increment_program_counter = false;
-            },
-            0xF0 => {
-                trace!("{:#04X}: LDH A,($FF00+a8). A:{:#04X} <- Value of {:#06X}", opcode, self.read_register_a(), (0xFF00 + mmu.read_byte(self.program_counter + 1) as u16));
-
-                let next_byte = mmu.read_byte(self.program_counter + 1);
-                self.write_register_a(mmu.read_byte(0xFF00 + next_byte as u16));
-            },
-            0xF1 => {
-                trace!("{:#04X}: POP AF. AF:{:#06X} <- {:#06X} SP:{:#06X}", opcode, self.read_register_af(), mmu.read_word(self.stack_pointer), self.stack_pointer);
-
-                self.write_register_af(mmu.read_word(self.stack_pointer));
-                let value = self.read_register_f() & 0xF0;
-                self.write_register_f(value);
-                self.stack_pointer += 2;
-            },
-            0xF2 => {
-                trace!("{:#04X}: LD A,($FF00+C). A:{:#04X} <- $FF00+C:{:#06X}", opcode, self.read_register_a(), (0xFF00 + self.read_register_c() as u16));
-
-                let byte = mmu.read_byte(0xFF00 + self.read_register_c() as u16);
-                self.write_register_a(byte);
-            },
-            0xF3 => {
-                trace!("{:#04X}: Disable Interrupts.", opcode);
-
-                self.interrupt_master_enable = false;
-            },
-            0xF5 => {
-                trace!("{:#04X}: PUSH AF. SP:{:#06X} <- AF:{:#06X}", opcode, self.stack_pointer - 2, self.read_register_af());
-
-                self.stack_pointer -= 2;
-                mmu.write_word(self.stack_pointer, self.read_register_af());
-            },
-            0xF6 => {
-                trace!("{:#04X}: OR d8. d8:{:#04X} -> {:#04X}", opcode, self.read_register_a(), self.read_register_a() | mmu.read_byte(self.program_counter + 1));
-
-                self.or_with_register_a(mmu.read_byte(self.program_counter + 1));
-            },
-            0xF7 => {
-                trace!("{:#04X}: RST 0x0030", opcode);
-
-                self.stack_pointer -= 2;
-                mmu.write_word(self.stack_pointer, self.program_counter + 1);
-
-                self.program_counter = 0x0030;
-                increment_program_counter = false;
-            },
-            0xF8 =>{
-                trace!("{:#04X}: LD HL,SP+r8. HL:{:#06X} <- SP+r8:{:#06X}", opcode, self.read_register_hl(), self.stack_pointer);
-
-                self.unset_flag_bit(ZERO_BIT);
-                self.unset_flag_bit(SUBTRACTION_BIT);
-
-                let result: u16;
-                let next_byte_signed: i8 = mmu.read_byte(self.program_counter + 1) as i8;
-                if next_byte_signed < 0 {
-                    result = self.stack_pointer.wrapping_sub((next_byte_signed * -1) as u16);
-                } else {
-                    result = self.stack_pointer.wrapping_add(next_byte_signed as u16);
-                }
-
-                if ((self.stack_pointer ^ next_byte_signed as u16 ^ result) & 0x100) == 0x100 {
-                    self.set_flag_bit(CARRY_BIT);
-                } else {
-                    self.unset_flag_bit(CARRY_BIT);
-                }
-
-                if ((self.stack_pointer ^ next_byte_signed as u16 ^ result) & 0x10) == 0x10 {
-                    self.set_flag_bit(HALF_CARRY_BIT);
-                } else {
-                    self.unset_flag_bit(HALF_CARRY_BIT);
-                }
-
-                self.write_register_hl(result);
-            },
-            0xF9 => {
-                trace!("{:#04X}: LD SP,HL. SP:{:#06X} <- HL:{:#06X}", opcode, self.stack_pointer, self.read_register_hl());
-
-                self.stack_pointer = self.read_register_hl();
-            },
-            0xFA => {
-                trace!("{:#04X}: LD A,a16. A:{:#04X} <- a16:{:#06X}", opcode, self.read_register_a(), mmu.read_word(self.program_counter + 1));
-
-                let next_word = mmu.read_word(self.program_counter + 1);
-                self.write_register_a(mmu.read_byte(next_word));
-            },
-            0xFB => {
-                trace!("{:#04X}: Enable Interrupts.", opcode);
-
-                self.interrupt_master_enable = true;
-            },
-            0xFE => {
-                trace!("{:#04X}: CP d8. A:{:#04X} d8:{:#04X}", opcode, self.read_register_a(), mmu.read_byte(self.program_counter + 1));
-
-                self.compare_with_register_a(mmu.read_byte(self.program_counter + 1));
-            },
-            0xFF => {
-                trace!("{:#04X}: RST 0x0038", opcode);
-
-                self.stack_pointer -= 2;
-                mmu.write_word(self.stack_pointer, self.program_counter + 1);
-
-                self.program_counter = 0x0038;
-                increment_program_counter = false;
-            },
-            0xD3 | 0xDB | 0xDD | 0xE3 |
-            0xE4 | 0xEB | 0xEC | 0xED |
-            0xF4 | 0xFC | 0xFD        => {
-                error!("Tried to call unused OpCode {}", opcode);
-                exit(3);
-            }
-            _ => {
-                error!("Unknown OpCode {:#04X}", opcode);
-                exit(1);
-            }
-        }
-
-        self.update_clock_and_program_counter(opcode, use_machine_cycles_branched, increment_program_counter);
-    }
-
-    fn process_cb_opcode(&mut self, mmu: &mut MMU) {
-        let opcode = mmu.read_byte(self.program_counter + 1);
-
-        match opcode {
-            0x00 => {
-                trace!("{:#04X}: RLC B. B:{:#04X}", opcode, self.read_register_b());
-
-                let result = self.rotate_left(self.read_register_b(), true);
-                self.write_register_b(result);
-            },
-            0x01 => {
-                trace!("{:#04X}: RLC C. C:{:#04X}", opcode, self.read_register_c());
-
-                let result = self.rotate_left(self.read_register_c(), true);
-                self.write_register_c(result);
-            },
-            0x02 => {
-                trace!("{:#04X}: RLC D. D:{:#04X}", opcode, self.read_register_d());
-
-                let result = self.rotate_left(self.read_register_d(), true);
-                self.write_register_d(result);
-            },
-            0x03 => {
-                trace!("{:#04X}: RLC E. E:{:#04X}", opcode, self.read_register_e());
-
-                let result = self.rotate_left(self.read_register_e(), true);
-                self.write_register_e(result);
-            },
-            0x04 => {
-                trace!("{:#04X}: RLC H. H:{:#04X}", opcode, self.read_register_h());
-
-                let result = self.rotate_left(self.read_register_h(), true);
-                self.write_register_h(result);
-            },
-            0x05 => {
-                trace!("{:#04X}: RLC L. L:{:#04X}", opcode, self.read_register_l());
-
-                let result = self.rotate_left(self.read_register_l(), true);
-                self.write_register_l(result);
-            },
-            0x06 => {
-                trace!("{:#04X}: RLC (HL). (HL):{:#04X}", opcode, mmu.read_byte(self.read_register_hl()));
-
-                let result = self.rotate_left(mmu.read_byte(self.read_register_hl()), true);
-                mmu.write_byte(self.read_register_hl(), result);
-            },
-            0x07 => {
-                trace!("{:#04X}: RLC A. A:{:#04X}", opcode, self.read_register_a());
-
-                let result = self.rotate_left(self.read_register_a(), true);
-                self.write_register_a(result);
-            },
-            0x08 => {
-                trace!("{:#04X}: RRC B. B:{:#04X}", opcode, self.read_register_b());
-
-                let result = self.rotate_right(self.read_register_b(), true);
-                self.write_register_b(result);
-            },
-            0x09 => {
-                trace!("{:#04X}: RRC C. C:{:#04X}", opcode, self.read_register_c());
-
-                let result = self.rotate_right(self.read_register_c(), true);
-                self.write_register_c(result);
-            },
-            0x0A => {
-                trace!("{:#04X}: RRC D. D:{:#04X}", opcode, self.read_register_d());
-
-                let result = self.rotate_right(self.read_register_d(), true);
-                self.write_register_d(result);
-            },
-            0x0B => {
-                trace!("{:#04X}: RRC E. E:{:#04X}", opcode, self.read_register_e());
-
-                let result = self.rotate_right(self.read_register_e(), true);
-                self.write_register_e(result);
-            },
-            0x0C => {
-                trace!("{:#04X}: RRC H. H:{:#04X}", opcode, self.read_register_h());
-
-                let result = self.rotate_right(self.read_register_h(), true);
-                self.write_register_h(result);
-            },
-            0x0D => {
-                trace!("{:#04X}: RRC L. L:{:#04X}", opcode, self.read_register_l());
-
-                let result = self.rotate_right(self.read_register_l(), true);
-                self.write_register_l(result);
-            },
-            0x0E => {
-                trace!("{:#04X}: RRC (HL). (HL):{:#04X}", opcode, mmu.read_byte(self.read_register_hl()));
-
-                let result = self.rotate_right(mmu.read_byte(self.read_register_hl()), true);
-                mmu.write_byte(self.read_register_hl(), result);
-            },
-            0x0F => {
-                trace!("{:#04X}: RRC A. A:{:#04X}", opcode, self.read_register_a());
-
-                let result = self.rotate_right(self.read_register_a(), true);
-                self.write_register_a(result);
-            },
-            0x10 => {
-                trace!("{:#04X}: RL B. B:{:#04X}", opcode, self.read_register_b());
-
-                let result = self.rotate_left_through_carry(self.read_register_b(), true);
-                self.write_register_b(result);
-            },
-            0x11 => {
-                trace!("{:#04X}: RL C. C:{:#04X}", opcode, self.read_register_c());
-
-                let result = self.rotate_left_through_carry(self.read_register_c(), true);
-                self.write_register_c(result);
-            },
-            0x12 => {
-                trace!("{:#04X}: RL D. D:{:#04X}", opcode, self.read_register_d());
-
-                let result = self.rotate_left_through_carry(self.read_register_d(), true);
-                self.write_register_d(result);
-            },
-            0x13 => {
-                trace!("{:#04X}: RL E. E:{:#04X}", opcode, self.read_register_e());
-
-                let result = self.rotate_left_through_carry(self.read_register_e(), true);
-                self.write_register_e(result);
-            },
-            0x14 => {
-                trace!("{:#04X}: RL H. H:{:#04X}", opcode, self.read_register_h());
-
-                let result = self.rotate_left_through_carry(self.read_register_h(), true);
-                self.write_register_h(result);
-            },
-            0x15 => {
-                trace!("{:#04X}: RL L. L:{:#04X}", opcode, self.read_register_l());
-
-                let result = self.rotate_left_through_carry(self.read_register_l(), true);
-                self.write_register_l(result);
-            },
-            0x16 => {
-                trace!("{:#04X}: RL (HL). (HL):{:#04X}", opcode, mmu.read_byte(self.read_register_hl()));
-
-                let result = self.rotate_left_through_carry(mmu.read_byte(self.read_register_hl()), true);
-                mmu.write_byte(self.read_register_hl(), result);
-            },
-            0x17 => {
-                trace!("{:#04X}: RL A. A:{:#04X}", opcode, self.read_register_a());
-
-                let result = self.rotate_left_through_carry(self.read_register_a(), true);
-                self.write_register_a(result);
-            },
-            0x18 => {
-                trace!("{:#04X}: RR B. B:{:#04X}", opcode, self.read_register_b());
-
-                let result = self.rotate_right_through_carry(self.read_register_b(), true);
-                self.write_register_b(result);
-            },
-            0x19 => {
-                trace!("{:#04X}: RR C. C:{:#04X}", opcode, self.read_register_c());
-
-                let result = self.rotate_right_through_carry(self.read_register_c(), true);
-                self.write_register_c(result);
-            },
-            0x1A => {
-                trace!("{:#04X}: RR D. D:{:#04X}", opcode, self.read_register_d());
-
-                let result = self.rotate_right_through_carry(self.read_register_d(), true);
-                self.write_register_d(result);
-            },
-            0x1B => {
-                trace!("{:#04X}: RR E. E:{:#04X}", opcode, self.read_register_e());
-
-                let result = self.rotate_right_through_carry(self.read_register_e(), true);
-                self.write_register_e(result);
-            },
-            0x1C => {
-                trace!("{:#04X}: RR H. H:{:#04X}", opcode, self.read_register_h());
-
-                let result = self.rotate_right_through_carry(self.read_register_h(), true);
-                self.write_register_h(result);
-            },
-            0x1D => {
-                trace!("{:#04X}: RR L. L:{:#04X}", opcode, self.read_register_l());
-
-                let result = self.rotate_right_through_carry(self.read_register_l(), true);
-                self.write_register_l(result);
-            },
-            0x1E => {
-                trace!("{:#04X}: RR (HL). (HL):{:#04X}", opcode, mmu.read_byte(self.read_register_hl()));
-
-                let result = self.rotate_right_through_carry(mmu.read_byte(self.read_register_hl()), true);
-                mmu.write_byte(self.read_register_hl(), result);
-            },
-            0x1F => {
-                trace!("{:#04X}: RR A. A:{:#04X}", opcode, self.read_register_a());
-
-                let result = self.rotate_right_through_carry(self.read_register_a(), true);
-                self.write_register_a(result);
-            },
-            0x20 => {
-                trace!("0xCB {:#04X}: SLA B. B:{:#04X}", opcode, self.read_register_b());
-
-                let result = self.shift_left(self.read_register_b());
-                self.write_register_b(result);
-            },
-            0x21 => {
-                trace!("0xCB {:#04X}: SLA C. C:{:#04X}", opcode, self.read_register_c());
-
-                let result = self.shift_left(self.read_register_c());
-                self.write_register_c(result);
-            },
-            0x22 => {
-                trace!("0xCB {:#04X}: SLA D. D:{:#04X}", opcode, self.read_register_d());
-
-                let result = self.shift_left(self.read_register_d());
-                self.write_register_d(result);
-            },
-            0x23 => {
-                trace!("0xCB {:#04X}: SLA E. E:{:#04X}", opcode, self.read_register_e());
-
-                let result = self.shift_left(self.read_register_e());
-                self.write_register_e(result);
-            },
-            0x24 => {
-                trace!("0xCB {:#04X}: SLA H. H:{:#04X}", opcode, self.read_register_h());
-
-                let result = self.shift_left(self.read_register_h());
-                self.write_register_h(result);
-            },
-            0x25 => {
-                trace!("0xCB {:#04X}: SLA L. L:{:#04X}", opcode, self.read_register_l());
-
-                let result = self.shift_left(self.read_register_l());
-                self.write_register_l(result);
-            },
-            0x26 => {
-                trace!("0xCB {:#04X}: SLA (HL). (HL):{:#04X}", opcode, mmu.read_byte(self.read_register_hl()));
-
-                let result = self.shift_left(mmu.read_byte(self.read_register_hl()));
-                mmu.write_byte(self.read_register_hl(), result);
-            },
-            0x27 => {
-                trace!("0xCB {:#04X}: SLA A. A:{:#04X}", opcode, self.read_register_a());
-
-                let result = self.shift_left(self.read_register_a());
-                self.write_register_a(result);
-            },
-            0x28 => {
-                trace!("0xCB {:#04X}: SRA B. B:{:#04X}", opcode, self.read_register_b());
-
-                let result = self.shift_right_preserve_msb(self.read_register_b());
-                self.write_register_b(result);
-            },
-            0x29 => {
-                trace!("0xCB {:#04X}: SRA C. C:{:#04X}", opcode, self.read_register_c());
-
-                let result = self.shift_right_preserve_msb(self.read_register_c());
-                self.write_register_c(result);
-            },
-            0x2A => {
-                trace!("0xCB {:#04X}: SRA D. D:{:#04X}", opcode, self.read_register_d());
-
-                let result = self.shift_right_preserve_msb(self.read_register_d());
-                self.write_register_d(result);
-            },
-            0x2B => {
-                trace!("0xCB {:#04X}: SRA E. E:{:#04X}", opcode, self.read_register_e());
-
-                let result = self.shift_right_preserve_msb(self.read_register_e());
-                self.write_register_e(result);
-            },
-            0x2C => {
-                trace!("0xCB {:#04X}: SRA H. H:{:#04X}", opcode, self.read_register_h());
-
-                let result = self.shift_right_preserve_msb(self.read_register_h());
-                self.write_register_h(result);
-            },
-            0x2D => {
-                trace!("0xCB {:#04X}: SRA L. L:{:#04X}", opcode, self.read_register_l());
-
-                let result = self.shift_right_preserve_msb(self.read_register_l());
-                self.write_register_l(result);
-            },
-            0x2E => {
-                trace!("0xCB {:#04X}: SRA (HL). (HL):{:#04X}", opcode, mmu.read_byte(self.read_register_hl()));
-
-                let result = self.shift_right_preserve_msb(mmu.read_byte(self.read_register_hl()));
-                mmu.write_byte(self.read_register_hl(), result);
-            },
-            0x2F => {
-                trace!("0xCB {:#04X}: SRA A. A:{:#04X}", opcode, self.read_register_a());
-
-                let result = self.shift_right_preserve_msb(self.read_register_a());
-                self.write_register_a(result);
-            },
-            0x30 => {
-                trace!("0xCB {:#04X}: SWAP B. B:{:#04X}", opcode, self.read_register_b());
-
-                let result = self.swap_byte(self.read_register_b());
-                self.write_register_b(result);
-            },
-            0x31 => {
-                trace!("0xCB {:#04X}: SWAP C. C:{:#04X}", opcode, self.read_register_c());
-
-                let result = self.swap_byte(self.read_register_c());
-                self.write_register_c(result);
-            },
-            0x32 => {
-                trace!("0xCB {:#04X}: SWAP D. D:{:#04X}", opcode, self.read_register_d());
-
-                let result = self.swap_byte(self.read_register_d());
-                self.write_register_d(result);
-            },
-            0x33 => {
-                trace!("0xCB {:#04X}: SWAP E. E:{:#04X}", opcode, self.read_register_e());
-
-                let result = self.swap_byte(self.read_register_e());
-                self.write_register_e(result);
-            },
-            0x34 => {
-                trace!("0xCB {:#04X}: SWAP H. H:{:#04X}", opcode, self.read_register_h());
-
-                let result = self.swap_byte(self.read_register_h());
-                self.write_register_h(result);
-            },
-            0x35 => {
-                trace!("0xCB {:#04X}: SWAP L. L:{:#04X}", opcode, self.read_register_l());
-
-                let result = self.swap_byte(self.read_register_l());
-                self.write_register_l(result);
-            },
-            0x36 => {
-                trace!("0xCB {:#04X}: SWAP (HL). (HL):{:#04X}", opcode, mmu.read_byte(self.read_register_hl()));
-
-                let result = self.swap_byte(mmu.read_byte(self.read_register_hl()));
-                mmu.write_byte(self.read_register_hl(), result);
-            },
-            0x37 => {
-                trace!("0xCB {:#04X}: SWAP A. A:{:#04X}", opcode, self.read_register_a());
-
-                let result = self.swap_byte(self.read_register_a());
-                self.write_register_a(result);
-            },
-            0x38 => {
-                trace!("0xCB {:#04X}: SRL B. B:{:#04X}", opcode, self.read_register_b());
-
-                let result = self.shift_right(self.read_register_b());
-                self.write_register_b(result);
-            },
-            0x39 => {
-                trace!("0xCB {:#04X}: SRL C. C:{:#04X}", opcode, self.read_register_c());
-
-                let result = self.shift_right(self.read_register_c());
-                self.write_register_c(result);
-            },
-            0x3A => {
-                trace!("0xCB {:#04X}: SRL D. D:{:#04X}", opcode, self.read_register_d());
-
-                let result = self.shift_right(self.read_register_d());
-                self.write_register_d(result);
-            },
-            0x3B => {
-                trace!("0xCB {:#04X}: SRL E. E:{:#04X}", opcode, self.read_register_e());
-
-                let result = self.shift_right(self.read_register_e());
-                self.write_register_e(result);
-            },
-            0x3C => {
-                trace!("0xCB {:#04X}: SRL H. H:{:#04X}", opcode, self.read_register_h());
-
-                let result = self.shift_right(self.read_register_h());
-                self.write_register_h(result);
-            },
-            0x3D => {
-                trace!("0xCB {:#04X}: SRL L. L:{:#04X}", opcode, self.read_register_l());
-
-                let result = self.shift_right(self.read_register_l());
-                self.write_register_l(result);
-            },
-            0x3E => {
-                trace!("0xCB {:#04X}: SRL (HL). (HL):{:#04X}", opcode, mmu.read_byte(self.read_register_hl()));
-
-                let result = self.shift_right(mmu.read_byte(self.read_register_hl()));
-                mmu.write_byte(self.read_register_hl(), result);
-            },
-            0x3F => {
-                trace!("0xCB {:#04X}: SRL A. A:{:#04X}", opcode, self.read_register_a());
-
-                let result = self.shift_right(self.read_register_a());
-                self.write_register_a(result);
-            },
-            0x40 => {
-                trace!("0xCB {:#04X}: BIT 0,B. B:{:#04X}", opcode, self.read_register_b());
-
-                self.test_bit(self.read_register_b(), 0);
-            },
-            0x41 => {
-                trace!("0xCB {:#04X}: BIT 0,C. C:{:#04X}", opcode, self.read_register_c());
-
-                self.test_bit(self.read_register_c(), 0);
-            },
-            0x42 => {
-                trace!("0xCB {:#04X}: BIT 0,D. D:{:#04X}", opcode, self.read_register_d());
-
-                self.test_bit(self.read_register_d(), 0);
-            },
-            0x43 => {
-                trace!("0xCB {:#04X}: BIT 0,E. E:{:#04X}", opcode, self.read_register_e());
-
-                self.test_bit(self.read_register_e(), 0);
-            },
-            0x44 => {
-                trace!("0xCB {:#04X}: BIT 0,H. H:{:#04X}", opcode, self.read_register_h());
-
-                self.test_bit(self.read_register_h(), 0);
-            },
-            0x45 => {
-                trace!("0xCB {:#04X}: BIT 0,L. L:{:#04X}", opcode, self.read_register_l());
-
-                self.test_bit(self.read_register_l(), 0);
-            },
-            0x46 => {
-                trace!("0xCB {:#04X}: BIT 0,(HL). (HL):{:#04X}", opcode, mmu.read_byte(self.read_register_hl()));
-
-                self.test_bit(mmu.read_byte(self.read_register_hl()), 0);
-            },
-            0x47 => {
-                trace!("0xCB {:#04X}: BIT 0,A. A:{:#04X}", opcode, self.read_register_a());
-
-                self.test_bit(self.read_register_a(), 0);
-            },
-            0x48 => {
-                trace!("0xCB {:#04X}: BIT 1,B. B:{:#04X}", opcode, self.read_register_b());
-
-                self.test_bit(self.read_register_b(), 1);
-            },
-            0x49 => {
-                trace!("0xCB {:#04X}: BIT 1,C. C:{:#04X}", opcode, self.read_register_c());
-
-                self.test_bit(self.read_register_c(), 1);
-            },
-            0x4A => {
-                trace!("0xCB {:#04X}: BIT 1,D. D:{:#04X}", opcode, self.read_register_d());
-
-                self.test_bit(self.read_register_d(), 1);
-            },
-            0x4B => {
-                trace!("0xCB {:#04X}: BIT 1,E. E:{:#04X}", opcode, self.read_register_e());
-
-                self.test_bit(self.read_register_e(), 1);
-            },
-            0x4C => {
-                trace!("0xCB {:#04X}: BIT 1,H. H:{:#04X}", opcode, self.read_register_h());
-
-                self.test_bit(self.read_register_h(), 1);
-            },
-            0x4D => {
-                trace!("0xCB {:#04X}: BIT 1,L. L:{:#04X}", opcode, self.read_register_l());
-
-                self.test_bit(self.read_register_l(), 1);
-            },
-            0x4E => {
-                trace!("0xCB {:#04X}: BIT 1,(HL). (HL):{:#04X}", opcode, mmu.read_byte(self.read_register_hl()));
-
-                self.test_bit(mmu.read_byte(self.read_register_hl()), 1);
-            },
-            0x4F => {
-                trace!("0xCB {:#04X}: BIT 1,A. A:{:#04X}", opcode, self.read_register_a());
-
-                self.test_bit(self.read_register_a(), 1);
-            },
-            0x50 => {
-                trace!("0xCB {:#04X}: BIT 2,B. B:{:#04X}", opcode, self.read_register_b());
-
-                self.test_bit(self.read_register_b(), 2);
-            },
-            0x51 => {
-                trace!("0xCB {:#04X}: BIT 2,C. C:{:#04X}", opcode, self.read_register_c());
-
-                self.test_bit(self.read_register_c(), 2);
-            },
-            0x52 => {
-                trace!("0xCB {:#04X}: BIT 2,D. D:{:#04X}", opcode, self.read_register_d());
-
-                self.test_bit(self.read_register_d(), 2);
-            },
-            0x53 => {
-                trace!("0xCB {:#04X}: BIT 2,E. E:{:#04X}", opcode, self.read_register_e());
-
-                self.test_bit(self.read_register_e(), 2);
-            },
-            0x54 => {
-                trace!("0xCB {:#04X}: BIT 2,H. H:{:#04X}", opcode, self.read_register_h());
-
-                self.test_bit(self.read_register_h(), 2);
-            },
-            0x55 => {
-                trace!("0xCB {:#04X}: BIT 2,L. L:{:#04X}", opcode, self.read_register_l());
-
-                self.test_bit(self.read_register_l(), 2);
-            },
-            0x56 => {
-                trace!("0xCB {:#04X}: BIT 2,(HL). (HL):{:#04X}", opcode, mmu.read_byte(self.read_register_hl()));
-
-                self.test_bit(mmu.read_byte(self.read_register_hl()), 2);
-            },
-            0x57 => {
-                trace!("0xCB {:#04X}: BIT 2,A. A:{:#04X}", opcode, self.read_register_a());
-
-                self.test_bit(self.read_register_a(), 2);
-            },
-            0x58 => {
-                trace!("0xCB {:#04X}: BIT 3,B. B:{:#04X}", opcode, self.read_register_b());
-
-                self.test_bit(self.read_register_b(), 3);
-            },
-            0x59 => {
-                trace!("0xCB {:#04X}: BIT 3,C. C:{:#04X}", opcode, self.read_register_c());
-
-                self.test_bit(self.read_register_c(), 3);
-            },
-            0x5A => {
-                trace!("0xCB {:#04X}: BIT 3,D. D:{:#04X}", opcode, self.read_register_d());
-
-                self.test_bit(self.read_register_d(), 3);
-            },
-            0x5B => {
-                trace!("0xCB {:#04X}: BIT 3,E. E:{:#04X}", opcode, self.read_register_e());
-
-                self.test_bit(self.read_register_e(), 3);
-            },
-            0x5C => {
-                trace!("0xCB {:#04X}: BIT 3,H. H:{:#04X}", opcode, self.read_register_h());
-
-                self.test_bit(self.read_register_h(), 3);
-            },
-            0x5D => {
-                trace!("0xCB {:#04X}: BIT 3,L. L:{:#04X}", opcode, self.read_register_l());
-
-                self.test_bit(self.read_register_l(), 3);
-            },
-            0x5E => {
-                trace!("0xCB {:#04X}: BIT 3,(HL). (HL):{:#04X}", opcode, mmu.read_byte(self.read_register_hl()));
-
-                self.test_bit(mmu.read_byte(self.read_register_hl()), 3);
-            },
-            0x5F => {
-                trace!("0xCB {:#04X}: BIT 3,A. A:{:#04X}", opcode, self.read_register_a());
-
-                self.test_bit(self.read_register_a(), 3);
-            },
-            0x60 => {
-                trace!("0xCB {:#04X}: BIT 4,B. B:{:#04X}", opcode, self.read_register_b());
-
-                self.test_bit(self.read_register_b(), 4);
-            },
-            0x61 => {
-                trace!("0xCB {:#04X}: BIT 4,C. C:{:#04X}", opcode, self.read_register_c());
-
-                self.test_bit(self.read_register_c(), 4);
-            },
-            0x62 => {
-                trace!("0xCB {:#04X}: BIT 4,D. D:{:#04X}", opcode, self.read_register_d());
-
-                self.test_bit(self.read_register_d(), 4);
-            },
-            0x63 => {
-                trace!("0xCB {:#04X}: BIT 4,E. E:{:#04X}", opcode, self.read_register_e());
-
-                self.test_bit(self.read_register_e(), 4);
-            },
-            0x64 => {
-                trace!("0xCB {:#04X}: BIT 4,H. H:{:#04X}", opcode, self.read_register_h());
-
-                self.test_bit(self.read_register_h(), 4);
-            },
-            0x65 => {
-                trace!("0xCB {:#04X}: BIT 4,L. L:{:#04X}", opcode, self.read_register_l());
-
-                self.test_bit(self.read_register_l(), 4);
-            },
-            0x66 => {
-                trace!("0xCB {:#04X}: BIT 4,(HL). (HL):{:#04X}", opcode, mmu.read_byte(self.read_register_hl()));
-
-                self.test_bit(mmu.read_byte(self.read_register_hl()), 4);
-            },
-            0x67 => {
-                trace!("0xCB {:#04X}: BIT 4,A. A:{:#04X}", opcode, self.read_register_a());
-
-                self.test_bit(self.read_register_a(), 4);
-            },
-            0x68 => {
-                trace!("0xCB {:#04X}: BIT 3,B. B:{:#04X}", opcode, self.read_register_b());
-
-                self.test_bit(self.read_register_b(), 5);
-            },
-            0x69 => {
-                trace!("0xCB {:#04X}: BIT 5,C. C:{:#04X}", opcode, self.read_register_c());
-
-                self.test_bit(self.read_register_c(), 5);
-            },
-            0x6A => {
-                trace!("0xCB {:#04X}: BIT 5,D. D:{:#04X}", opcode, self.read_register_d());
-
-                self.test_bit(self.read_register_d(), 5);
-            },
-            0x6B => {
-                trace!("0xCB {:#04X}: BIT 5,E. E:{:#04X}", opcode, self.read_register_e());
-
-                self.test_bit(self.read_register_e(), 5);
-            },
-            0x6C => {
-                trace!("0xCB {:#04X}: BIT 5,H. H:{:#04X}", opcode, self.read_register_h());
-
-                self.test_bit(self.read_register_h(), 5);
-            },
-            0x6D => {
-                trace!("0xCB {:#04X}: BIT 5,L. L:{:#04X}", opcode, self.read_register_l());
-
-                self.test_bit(self.read_register_l(), 5);
-            },
-            0x6E => {
-                trace!("0xCB {:#04X}: BIT 5,(HL). (HL):{:#04X}", opcode, mmu.read_byte(self.read_register_hl()));
-
-                self.test_bit(mmu.read_byte(self.read_register_hl()), 5);
-            },
-            0x6F => {
-                trace!("0xCB {:#04X}: BIT 5,A. A:{:#04X}", opcode, self.read_register_a());
-
-                self.test_bit(self.read_register_a(), 5);
-            },
-            0x70 => {
-                trace!("0xCB {:#04X}: BIT 6,B. B:{:#04X}", opcode, self.read_register_b());
-
-                self.test_bit(self.read_register_b(), 6);
-            },
-            0x71 => {
-                trace!("0xCB {:#04X}: BIT 6,C. C:{:#04X}", opcode, self.read_register_c());
-
-                self.test_bit(self.read_register_c(), 6);
-            },
-            0x72 => {
-                trace!("0xCB {:#04X}: BIT 6,D. D:{:#04X}", opcode, self.read_register_d());
-
-                self.test_bit(self.read_register_d(), 6);
-            },
-            0x73 => {
-                trace!("0xCB {:#04X}: BIT 6,E. E:{:#04X}", opcode, self.read_register_e());
-
-                self.test_bit(self.read_register_e(), 6);
-            },
-            0x74 => {
-                trace!("0xCB {:#04X}: BIT 6,H. H:{:#04X}", opcode, self.read_register_h());
-
-                self.test_bit(self.read_register_h(), 6);
-            },
-            0x75 => {
-                trace!("0xCB {:#04X}: BIT 6,L. L:{:#04X}", opcode, self.read_register_l());
-
-                self.test_bit(self.read_register_l(), 6);
-            },
-            0x76 => {
-                trace!("0xCB {:#04X}: BIT 6,(HL). (HL):{:#04X}", opcode, mmu.read_byte(self.read_register_hl()));
-
-                self.test_bit(mmu.read_byte(self.read_register_hl()), 6);
-            },
-            0x77 => {
-                trace!("0xCB {:#04X}: BIT 6,A. A:{:#04X}", opcode, self.read_register_a());
-
-                self.test_bit(self.read_register_a(), 6);
-            },
-            0x78 => {
-                trace!("0xCB {:#04X}: BIT 7,B. B:{:#04X}", opcode, self.read_register_b());
-
-                self.test_bit(self.read_register_b(), 7);
-            },
-            0x79 => {
-                trace!("0xCB {:#04X}: BIT 7,C. C:{:#04X}", opcode, self.read_register_c());
-
-                self.test_bit(self.read_register_c(), 7);
-            },
-            0x7A => {
-                trace!("0xCB {:#04X}: BIT 7,D. D:{:#04X}", opcode, self.read_register_d());
-
-                self.test_bit(self.read_register_d(), 7);
-            },
-            0x7B => {
-                trace!("0xCB {:#04X}: BIT 7,E. E:{:#04X}", opcode, self.read_register_e());
-
-                self.test_bit(self.read_register_e(), 7);
-            },
-            0x7C => {
-                trace!("0xCB {:#04X}: BIT 7,H. H:{:#04X}", opcode, self.read_register_h());
-
-                self.test_bit(self.read_register_h(), 7);
-            },
-            0x7D => {
-                trace!("0xCB {:#04X}: BIT 7,L. L:{:#04X}", opcode, self.read_register_l());
-
-                self.test_bit(self.read_register_l(), 7);
-            },
-            0x7E => {
-                trace!("0xCB {:#04X}: BIT 7,(HL). (HL):{:#04X}", opcode, mmu.read_byte(self.read_register_hl()));
-
-                self.test_bit(mmu.read_byte(self.read_register_hl()), 7);
-            },
-            0x7F => {
-                trace!("0xCB {:#04X}: BIT 7,A. A:{:#04X}", opcode, self.read_register_a());
-
-                self.test_bit(self.read_register_a(), 7);
-            },
-            0x80 => {
-                trace!("0xCB {:#04X}: RES 0,B. B:{:#04X}", opcode, self.read_register_b());
-
-                let result = self.reset_bit(self.read_register_b(), 0);
-                self.write_register_b(result);
-            },
-            0x81 => {
-                trace!("0xCB {:#04X}: RES 0,C. C:{:#04X}", opcode, self.read_register_c());
-
-                let result = self.reset_bit(self.read_register_c(), 0);
-                self.write_register_c(result);
-            },
-            0x82 => {
-                trace!("0xCB {:#04X}: RES 0,D. D:{:#04X}", opcode, self.read_register_d());
-
-                let result = self.reset_bit(self.read_register_d(), 0);
-                self.write_register_d(result);
-            },
-            0x83 => {
-                trace!("0xCB {:#04X}: RES 0,E. E:{:#04X}", opcode, self.read_register_e());
-
-                let result = self.reset_bit(self.read_register_e(), 0);
-                self.write_register_e(result);
-            },
-            0x84 => {
-                trace!("0xCB {:#04X}: RES 0,H. H:{:#04X}", opcode, self.read_register_h());
-
-                let result = self.reset_bit(self.read_register_h(), 0);
-                self.write_register_h(result);
-            },
-            0x85 => {
-                trace!("0xCB {:#04X}: RES 0,L. L:{:#04X}", opcode, self.read_register_l());
-
-                let result = self.reset_bit(self.read_register_l(), 0);
-                self.write_register_l(result);
-            },
-            0x86 => {
-                trace!("0xCB {:#04X}: RES 0,(HL). (HL):{:#04X}", opcode, mmu.read_byte(self.read_register_hl()));
-
-                let result = self.reset_bit(mmu.read_byte(self.read_register_hl()), 0);
-                mmu.write_byte(self.read_register_hl(), result);
-            },
-            0x87 => {
-                trace!("0xCB {:#04X}: RES 0,A. A:{:#04X}", opcode, self.read_register_a());
-
-                let result = self.reset_bit(self.read_register_a(), 0);
-                self.write_register_a(result);
-            },
-            0x88 => {
-                trace!("0xCB {:#04X}: RES 1,B. B:{:#04X}", opcode, self.read_register_b());
-
-                let result = self.reset_bit(self.read_register_b(), 1);
-                self.write_register_b(result);
-            },
-            0x89 => {
-                trace!("0xCB {:#04X}: RES 1,C. C:{:#04X}", opcode, self.read_register_c());
-
-                let result = self.reset_bit(self.read_register_c(), 1);
-                self.write_register_c(result);
-            },
-            0x8A => {
-                trace!("0xCB {:#04X}: RES 1,D. D:{:#04X}", opcode, self.read_register_d());
-
-                let result = self.reset_bit(self.read_register_d(), 1);
-                self.write_register_d(result);
-            },
-            0x8B => {
-                trace!("0xCB {:#04X}: RES 1,E. E:{:#04X}", opcode, self.read_register_e());
-
-                let result = self.reset_bit(self.read_register_e(), 1);
-                self.write_register_e(result);
-            },
-            0x8C => {
-                trace!("0xCB {:#04X}: RES 1,H. H:{:#04X}", opcode, self.read_register_h());
-
-                let result = self.reset_bit(self.read_register_h(), 1);
-                self.write_register_h(result);
-            },
-            0x8D => {
-                trace!("0xCB {:#04X}: RES 1,L. L:{:#04X}", opcode, self.read_register_l());
-
-                let result = self.reset_bit(self.read_register_l(), 1);
-                self.write_register_l(result);
-            },
-            0x8E => {
-                trace!("0xCB {:#04X}: RES 1,(HL). (HL):{:#04X}", opcode, mmu.read_byte(self.read_register_hl()));
-
-                let result = self.reset_bit(mmu.read_byte(self.read_register_hl()), 1);
-                mmu.write_byte(self.read_register_hl(), result);
-            },
-            0x8F => {
-                trace!("0xCB {:#04X}: RES 1,A. A:{:#04X}", opcode, self.read_register_a());
-
-                let result = self.reset_bit(self.read_register_a(), 1);
-                self.write_register_a(result);
-            },
-            0x90 => {
-                trace!("0xCB {:#04X}: RES 2,B. B:{:#04X}", opcode, self.read_register_b());
-
-                let result = self.reset_bit(self.read_register_b(), 2);
-                self.write_register_b(result);
-            },
-            0x91 => {
-                trace!("0xCB {:#04X}: RES 2,C. C:{:#04X}", opcode, self.read_register_c());
-
-                let result = self.reset_bit(self.read_register_c(), 2);
-                self.write_register_c(result);
-            },
-            0x92 => {
-                trace!("0xCB {:#04X}: RES 2,D. D:{:#04X}", opcode, self.read_register_d());
-
-                let result = self.reset_bit(self.read_register_d(), 2);
-                self.write_register_d(result);
-            },
-            0x93 => {
-                trace!("0xCB {:#04X}: RES 2,E. E:{:#04X}", opcode, self.read_register_e());
-
-                let result = self.reset_bit(self.read_register_e(), 2);
-                self.write_register_e(result);
-            },
-            0x94 => {
-                trace!("0xCB {:#04X}: RES 2,H. H:{:#04X}", opcode, self.read_register_h());
-
-                let result = self.reset_bit(self.read_register_h(), 2);
-                self.write_register_h(result);
-            },
-            0x95 => {
-                trace!("0xCB {:#04X}: RES 2,L. L:{:#04X}", opcode, self.read_register_l());
-
-                let result = self.reset_bit(self.read_register_l(), 2);
-                self.write_register_l(result);
-            },
-            0x96 => {
-                trace!("0xCB {:#04X}: RES 2,(HL). (HL):{:#04X}", opcode, mmu.read_byte(self.read_register_hl()));
-
-                let result = self.reset_bit(mmu.read_byte(self.read_register_hl()), 2);
-                mmu.write_byte(self.read_register_hl(), result);
-            },
-            0x97 => {
-                trace!("0xCB {:#04X}: RES 2,A. A:{:#04X}", opcode, self.read_register_a());
-
-                let result = self.reset_bit(self.read_register_a(), 2);
-                self.write_register_a(result);
-            },
-            0x98 => {
-                trace!("0xCB {:#04X}: RES 3,B. B:{:#04X}", opcode, self.read_register_b());
-
-                let result = self.reset_bit(self.read_register_b(), 3);
-                self.write_register_b(result);
-            },
-            0x99 => {
-                trace!("0xCB {:#04X}: RES 3,C. C:{:#04X}", opcode, self.read_register_c());
-
-                let result = self.reset_bit(self.read_register_c(), 3);
-                self.write_register_c(result);
-            },
-            0x9A => {
-                trace!("0xCB {:#04X}: RES 3,D. D:{:#04X}", opcode, self.read_register_d());
-
-                let result = self.reset_bit(self.read_register_d(), 3);
-                self.write_register_d(result);
-            },
-            0x9B => {
-                trace!("0xCB {:#04X}: RES 3,E. E:{:#04X}", opcode, self.read_register_e());
-
-                let result = self.reset_bit(self.read_register_e(), 3);
-                self.write_register_e(result);
-            },
-            0x9C => {
-                trace!("0xCB {:#04X}: RES 3,H. H:{:#04X}", opcode, self.read_register_h());
-
-                let result = self.reset_bit(self.read_register_h(), 3);
-                self.write_register_h(result);
-            },
-            0x9D => {
-                trace!("0xCB {:#04X}: RES 3,L. L:{:#04X}", opcode, self.read_register_l());
-
-                let result = self.reset_bit(self.read_register_l(), 3);
-                self.write_register_l(result);
-            },
-            0x9E => {
-                trace!("0xCB {:#04X}: RES 3,(HL). (HL):{:#04X}", opcode, mmu.read_byte(self.read_register_hl()));
-
-                let result = self.reset_bit(mmu.read_byte(self.read_register_hl()), 3);
-                mmu.write_byte(self.read_register_hl(), result);
-            },
-            0x9F => {
-                trace!("0xCB {:#04X}: RES 3,A. A:{:#04X}", opcode, self.read_register_a());
-
-                let result = self.reset_bit(self.read_register_a(), 3);
-                self.write_register_a(result);
-            },
-            0xA0 => {
-                trace!("0xCB {:#04X}: RES 4,B. B:{:#04X}", opcode, self.read_register_b());
-
-                let result = self.reset_bit(self.read_register_b(), 4);
-                self.write_register_b(result);
-            },
-            0xA1 => {
-                trace!("0xCB {:#04X}: RES 4,C. C:{:#04X}", opcode, self.read_register_c());
-
-                let result = self.reset_bit(self.read_register_c(), 4);
-                self.write_register_c(result);
-            },
-            0xA2 => {
-                trace!("0xCB {:#04X}: RES 4,D. D:{:#04X}", opcode, self.read_register_d());
-
-                let result = self.reset_bit(self.read_register_d(), 4);
-                self.write_register_d(result);
-            },
-            0xA3 => {
-                trace!("0xCB {:#04X}: RES 4,E. E:{:#04X}", opcode, self.read_register_e());
-
-                let result = self.reset_bit(self.read_register_e(), 4);
-                self.write_register_e(result);
-            },
-            0xA4 => {
-                trace!("0xCB {:#04X}: RES 4,H. H:{:#04X}", opcode, self.read_register_h());
-
-                let result = self.reset_bit(self.read_register_h(), 4);
-                self.write_register_h(result);
-            },
-            0xA5 => {
-                trace!("0xCB {:#04X}: RES 4,L. L:{:#04X}", opcode, self.read_register_l());
-
-                let result = self.reset_bit(self.read_register_l(), 4);
-                self.write_register_l(result);
-            },
-            0xA6 => {
-                trace!("0xCB {:#04X}: RES 4,(HL). (HL):{:#04X}", opcode, mmu.read_byte(self.read_register_hl()));
-
-                let result = self.reset_bit(mmu.read_byte(self.read_register_hl()), 4);
-                mmu.write_byte(self.read_register_hl(), result);
-            },
-            0xA7 => {
-                trace!("0xCB {:#04X}: RES 4,A. A:{:#04X}", opcode, self.read_register_a());
-
-                let result = self.reset_bit(self.read_register_a(), 4);
-                self.write_register_a(result);
-            },
-            0xA8 => {
-                trace!("0xCB {:#04X}: RES 5,B. B:{:#04X}", opcode, self.read_register_b());
-
-                let result = self.reset_bit(self.read_register_b(), 5);
-                self.write_register_b(result);
-            },
-            0xA9 => {
-                trace!("0xCB {:#04X}: RES 5,C. C:{:#04X}", opcode, self.read_register_c());
-
-                let result = self.reset_bit(self.read_register_c(), 5);
-                self.write_register_c(result);
-            },
-            0xAA => {
-                trace!("0xCB {:#04X}: RES 5,D. D:{:#04X}", opcode, self.read_register_d());
-
-                let result = self.reset_bit(self.read_register_d(), 5);
-                self.write_register_d(result);
-            },
-            0xAB => {
-                trace!("0xCB {:#04X}: RES 5,E. E:{:#04X}", opcode, self.read_register_e());
-
-                let result = self.reset_bit(self.read_register_e(), 5);
-                self.write_register_e(result);
-            },
-            0xAC => {
-                trace!("0xCB {:#04X}: RES 5,H. H:{:#04X}", opcode, self.read_register_h());
-
-                let result = self.reset_bit(self.read_register_h(), 5);
-                self.write_register_h(result);
-            },
-            0xAD => {
-                trace!("0xCB {:#04X}: RES 5,L. L:{:#04X}", opcode, self.read_register_l());
-
-                let result = self.reset_bit(self.read_register_l(), 5);
-                self.write_register_l(result);
-            },
-            0xAE => {
-                trace!("0xCB {:#04X}: RES 5,(HL). (HL):{:#04X}", opcode, mmu.read_byte(self.read_register_hl()));
-
-                let result = self.reset_bit(mmu.read_byte(self.read_register_hl()), 5);
-                mmu.write_byte(self.read_register_hl(), result);
-            },
-            0xAF => {
-                trace!("0xCB {:#04X}: RES 5,A. A:{:#04X}", opcode, self.read_register_a());
+    1, 3, 2, 2, 1, 1, 2, 1, 3, 2, 2, 2, 1, 1, 2, 1, // 1
+    2, 3, 2, 2, 1, 1, 2, 1, 2, 2, 2, 2, 1, 1, 2, 1, // 2
+    2, 3, 2, 2, 3, 3, 3, 1, 2, 2, 2, 2, 1, 1, 2, 1, // 3
+    1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1, // 4
+    1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1, // 5
+    1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1, // 6
+    2, 2, 2, 2, 2, 2, 1, 2, 1, 1, 1, 1, 1, 1, 2, 1, // 7
+    1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1, // 8
+    1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1, // 9
+    1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1, // A
+    1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1, // B
+    2, 3, 3, 4, 3, 4, 2, 4, 2, 4, 3, 0, 3, 6, 2, 4, // C
+    2, 3, 3, 0, 3, 4, 2, 4, 2, 4, 3, 0, 3, 0, 2, 4, // D
+    3, 3, 2, 0, 0, 4, 2, 4, 4, 1, 4, 0, 0, 0, 2, 4, // E
+    3, 3, 2, 1, 0, 4, 2, 4, 3, 2, 4, 1, 0, 0, 2, 4  // F
+];
 
-                let result = self.reset_bit(self.read_register_a(), 5);
-                self.write_register_a(result);
-            },
-            0xB0 => {
-                trace!("0xCB {:#04X}: RES 6,B. B:{:#04X}", opcode, self.read_register_b());
+pub(crate) const OPERATION_MACHINE_CYCLES_BRANCHED: [u8; 256] = [
+//  0  1  2  3  4  5  6  7  8  9  A  B  C  D  E  F
+    1, 3, 2, 2, 1, 1, 2, 1, 5, 2, 2, 2, 1, 1, 2, 1, // 0
+    1, 3, 2, 2, 1, 1, 2, 1, 3, 2, 2, 2, 1, 1, 2, 1, // 1
+    3, 3, 2, 2, 1, 1, 2, 1, 3, 2, 2, 2, 1, 1, 2, 1, // 2
+    3, 3, 2, 2, 3, 3, 3, 1, 3, 2, 2, 2, 1, 1, 2, 1, // 3
+    1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1, // 4
+    1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1, // 5
+    1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1, // 6
+    2, 2, 2, 2, 2, 2, 1, 2, 1, 1, 1, 1, 1, 1, 2, 1, // 7
+    1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1, // 8
+    1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1, // 9
+    1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1, // A
+    1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1, // B
+    5, 3, 4, 4, 6, 4, 2, 4, 5, 4, 4, 0, 6, 6, 2, 4, // C
+    5, 3, 4, 0, 6, 4, 2, 4, 5, 4, 4, 0, 6, 0, 2, 4, // D
+    3, 3, 2, 0, 0, 4, 2, 4, 4, 1, 4, 0, 0, 0, 2, 4, // E
+    3, 3, 2, 1, 0, 4, 2, 4, 3, 2, 4, 1, 0, 0, 2, 4  // F
+];
 
-                let result = self.reset_bit(self.read_register_b(), 6);
-                self.write_register_b(result);
-            },
-            0xB1 => {
-                trace!("0xCB {:#04X}: RES 6,C. C:{:#04X}", opcode, self.read_register_c());
+#[derive(Copy, Clone)]
+struct HiLoRegister {
+    pub lo: u8,
+    pub hi: u8
+}
 
-                let result = self.reset_bit(self.read_register_c(), 6);
-                self.write_register_c(result);
-            },
-            0xB2 => {
-                trace!("0xCB {:#04X}: RES 6,D. D:{:#04X}", opcode, self.read_register_d());
+union Register16 {
+    hilo: HiLoRegister,
+    word: u16
+}
 
-                let result = self.reset_bit(self.read_register_d(), 6);
-                self.write_register_d(result);
-            },
-            0xB3 => {
-                trace!("0xCB {:#04X}: RES 6,E. E:{:#04X}", opcode, self.read_register_e());
+pub struct Clock {
+    m: u8,
+    t: u8
+}
 
-                let result = self.reset_bit(self.read_register_e(), 6);
-                self.write_register_e(result);
-            },
-            0xB4 => {
-                trace!("0xCB {:#04X}: RES 6,H. H:{:#04X}", opcode, self.read_register_h());
+pub struct CPU {
+    af: Register16,
+    bc: Register16,
+    de: Register16,
+    hl: Register16,
+    pub stack_pointer: u16,
+    pub program_counter: u16,
+    pub skip_bios: bool,
+    pub clock: Clock,
+    pub interrupt_master_enable: bool,
+    is_ime_scheduled: bool, // EI was executed, IME turns on after the next instruction
+    pub is_halted: bool,    // Waiting in HALT for an enabled interrupt to be requested
+    is_halt_bug: bool,      // HALT was skipped with IME off and an interrupt pending, the next PC increment is lost
+    pub is_stopped: bool,   // In STOP's low power mode until a selected joypad line goes low
+    machine_cycles: u8      // Machine cycles the rest of the system has been ticked for during this instruction
+}
 
-                let result = self.reset_bit(self.read_register_h(), 6);
-                self.write_register_h(result);
-            },
-            0xB5 => {
-                trace!("0xCB {:#04X}: RES 6,L. L:{:#04X}", opcode, self.read_register_l());
+impl CPU {
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Self {
+        debug!("Initializing CPU");
 
-                let result = self.reset_bit(self.read_register_l(), 6);
-                self.write_register_l(result);
-            },
-            0xB6 => {
-                trace!("0xCB {:#04X}: RES 6,(HL). (HL):{:#04X}", opcode, mmu.read_byte(self.read_register_hl()));
+        CPU {
+            af: Register16{word: 0},
+            bc: Register16{word: 0},
+            de: Register16{word: 0},
+            hl: Register16{word: 0},
+            stack_pointer: 0,
+            program_counter: 0,
+            skip_bios: false,
+            clock: Clock{ m: 0, t: 0 },
+            interrupt_master_enable: true,
+            is_ime_scheduled: false,
+            is_halted: false,
+            is_halt_bug: false,
+            is_stopped: false,
+            machine_cycles: 0
+        }
+    }
 
-                let result = self.reset_bit(mmu.read_byte(self.read_register_hl()), 6);
-                mmu.write_byte(self.read_register_hl(), result);
-            },
-            0xB7 => {
-                trace!("0xCB {:#04X}: RES 6,A. A:{:#04X}", opcode, self.read_register_a());
+    pub fn get_clock_m(&self) -> u8 {
+        return self.clock.m;
+    }
 
-                let result = self.reset_bit(self.read_register_a(), 6);
-                self.write_register_a(result);
-            },
-            0xB8 => {
-                trace!("0xCB {:#04X}: RES 7,B. B:{:#04X}", opcode, self.read_register_b());
+    pub fn get_clock_t(&self) -> u8 {
+        return self.clock.t;
+    }
 
-                let result = self.reset_bit(self.read_register_b(), 7);
-                self.write_register_b(result);
-            },
-            0xB9 => {
-                trace!("0xCB {:#04X}: RES 7,C. C:{:#04X}", opcode, self.read_register_c());
+    pub fn save_state<W: Write>(&self, state: &mut StateWriter<W>) -> io::Result<()> {
+        state.write_u16(self.read_register_af())?;
+        state.write_u16(self.read_register_bc())?;
+        state.write_u16(self.read_register_de())?;
+        state.write_u16(self.read_register_hl())?;
+        state.write_u16(self.stack_pointer)?;
+        state.write_u16(self.program_counter)?;
+        state.write_u8(self.clock.m)?;
+        state.write_u8(self.clock.t)?;
+        state.write_bool(self.interrupt_master_enable)?;
+        state.write_bool(self.is_ime_scheduled)?;
+        state.write_bool(self.is_halted)?;
+        state.write_bool(self.is_halt_bug)?;
+        state.write_bool(self.is_stopped)?;
+        Ok(())
+    }
 
-                let result = self.reset_bit(self.read_register_c(), 7);
-                self.write_register_c(result);
-            },
-            0xBA => {
-                trace!("0xCB {:#04X}: RES 7,D. D:{:#04X}", opcode, self.read_register_d());
+    pub fn load_state<R: Read>(&mut self, state: &mut StateReader<R>) -> io::Result<()> {
+        self.write_register_af(state.read_u16()?);
+        self.write_register_bc(state.read_u16()?);
+        self.write_register_de(state.read_u16()?);
+        self.write_register_hl(state.read_u16()?);
+        self.stack_pointer = state.read_u16()?;
+        self.program_counter = state.read_u16()?;
+        self.clock.m = state.read_u8()?;
+        self.clock.t = state.read_u8()?;
+        self.interrupt_master_enable = state.read_bool()?;
+        self.is_ime_scheduled = state.read_bool()?;
+        self.is_halted = state.read_bool()?;
+        self.is_halt_bug = state.read_bool()?;
+        self.is_stopped = state.read_bool()?;
+        Ok(())
+    }
 
-                let result = self.reset_bit(self.read_register_d(), 7);
-                self.write_register_d(result);
-            },
-            0xBB => {
-                trace!("0xCB {:#04X}: RES 7,E. E:{:#04X}", opcode, self.read_register_e());
+    // Services the highest priority pending interrupt if IME is on, otherwise executes one instruction.
+    // Returns the opcode executed, or None if an interrupt was dispatched or the CPU is halted
+    pub fn tick(&mut self, mmu: &mut MMU) -> Result<Option<u8>, EmuError> {
+        self.machine_cycles = 0;
+
+        // Only the joypad can bring the system out of STOP
+        if self.is_stopped {
+            if !mmu.gpu.input.is_any_line_low() {
+                self.clock.m = 1;
+                self.clock.t = 4;
+                return Ok(None);
+            }
+            debug!("Joypad input, leaving STOP");
+            self.is_stopped = false;
+        }
 
-                let result = self.reset_bit(self.read_register_e(), 7);
-                self.write_register_e(result);
-            },
-            0xBC => {
-                trace!("0xCB {:#04X}: RES 7,H. H:{:#04X}", opcode, self.read_register_h());
+        // A halted CPU idles a machine cycle at a time until any enabled interrupt is requested, whether or not IME is on
+        if self.is_halted {
+            if mmu.interrupts.pending() == 0 {
+                self.advance_machine_cycle(mmu);
+                self.clock.m = 1;
+                self.clock.t = 4;
+                return Ok(None);
+            }
+            self.is_halted = false;
+        }
 
-                let result = self.reset_bit(self.read_register_h(), 7);
-                self.write_register_h(result);
-            },
-            0xBD => {
-                trace!("0xCB {:#04X}: RES 7,L. L:{:#04X}", opcode, self.read_register_l());
+        if self.interrupt_master_enable {
+            if let Some(interrupt) = mmu.interrupts.highest_pending() {
+                self.dispatch_interrupt(mmu, interrupt);
+                return Ok(None);
+            }
+        }
 
-                let result = self.reset_bit(self.read_register_l(), 7);
-                self.write_register_l(result);
-            },
-            0xBE => {
-                trace!("0xCB {:#04X}: RES 7,(HL). (HL):{:#04X}", opcode, mmu.read_byte(self.read_register_hl()));
+        // EI only takes effect once the instruction after it has run
+        let enable_interrupts = self.is_ime_scheduled;
 
-                let result = self.reset_bit(mmu.read_byte(self.read_register_hl()), 7);
-                mmu.write_byte(self.read_register_hl(), result);
-            },
-            0xBF => {
-                trace!("0xCB {:#04X}: RES 7,A. A:{:#04X}", opcode, self.read_register_a());
+        // Fetch opcode
+        let opcode = self.read_byte(mmu, self.program_counter);
 
-                let result = self.reset_bit(self.read_register_a(), 7);
-                self.write_register_a(result);
-            },
-            0xC0 => {
-                trace!("0xCB {:#04X}: SET 0,B. B:{:#04X}", opcode, self.read_register_b());
+        // HALT bug: the PC fails to move past this opcode, so it gets read again as the next byte.
+        // Backing the PC up by one makes the instruction's operands and length work out the same way
+        if self.is_halt_bug {
+            self.is_halt_bug = false;
+            self.program_counter = self.program_counter.wrapping_sub(1);
+        }
 
-                let result = self.set_bit(self.read_register_b(), 0);
-                self.write_register_b(result);
+        // Call relevant function which emulates the opcode
+        match opcode {
+            0xCB => {
+                self.process_cb_opcode(mmu);
             },
-            0xC1 => {
-                trace!("0xCB {:#04X}: SET 0,C. C:{:#04X}", opcode, self.read_register_c());
+            _ => {
+                self.process_opcode(mmu, opcode)?;
+            }
+        }
 
-                let result = self.set_bit(self.read_register_c(), 0);
-                self.write_register_c(result);
-            },
-            0xC2 => {
-                trace!("0xCB {:#04X}: SET 0,D. D:{:#04X}", opcode, self.read_register_d());
+        // Cycles where the CPU is busy without touching memory, like stepping a 16 bit register, mostly come
+        // after the accesses. PUSH, CALL and RST wait before theirs, see push_word()
+        while self.machine_cycles < self.clock.m {
+            self.advance_machine_cycle(mmu);
+        }
 
-                let result = self.set_bit(self.read_register_d(), 0);
-                self.write_register_d(result);
-            },
-            0xC3 => {
-                trace!("0xCB {:#04X}: SET 0,E. E:{:#04X}", opcode, self.read_register_e());
+        if enable_interrupts && self.is_ime_scheduled {
+            self.is_ime_scheduled = false;
+            self.interrupt_master_enable = true;
+        }
 
-                let result = self.set_bit(self.read_register_e(), 0);
-                self.write_register_e(result);
-            },
-            0xC4 => {
-                trace!("0xCB {:#04X}: SET 0,H. H:{:#04X}", opcode, self.read_register_h());
+        return Ok(Some(opcode));
+    }
 
-                let result = self.set_bit(self.read_register_h(), 0);
-                self.write_register_h(result);
-            },
-            0xC5 => {
-                trace!("0xCB {:#04X}: SET 0,L. L:{:#04X}", opcode, self.read_register_l());
+    // Whether the next tick() fetches and runs an instruction, rather than idling in HALT or STOP or servicing
+    // an interrupt. Follows the same checks tick() makes before fetching
+    pub fn will_execute_instruction(&self, mmu: &MMU) -> bool {
+        if self.is_stopped && !mmu.gpu.input.is_any_line_low() {
+            return false;
+        }
 
-                let result = self.set_bit(self.read_register_l(), 0);
-                self.write_register_l(result);
-            },
-            0xC6 => {
-                trace!("0xCB {:#04X}: SET 0,(HL). (HL):{:#04X}", opcode, mmu.read_byte(self.read_register_hl()));
+        if self.is_halted && mmu.interrupts.pending() == 0 {
+            return false;
+        }
 
-                let result = self.set_bit(mmu.read_byte(self.read_register_hl()), 0);
-                mmu.write_byte(self.read_register_hl(), result);
-            },
-            0xC7 => {
-                trace!("0xCB {:#04X}: SET 0,A. A:{:#04X}", opcode, self.read_register_a());
+        return !(self.interrupt_master_enable && mmu.interrupts.pending() != 0);
+    }
 
-                let result = self.set_bit(self.read_register_a(), 0);
-                self.write_register_a(result);
-            },
-            0xC8 => {
-                trace!("0xCB {:#04X}: SET 1,B. B:{:#04X}", opcode, self.read_register_b());
+    fn dispatch_interrupt(&mut self, mmu: &mut MMU, interrupt: Interrupt) {
+        trace!("Handling {:?} Interrupt", interrupt);
 
-                let result = self.set_bit(self.read_register_b(), 1);
-                self.write_register_b(result);
-            },
-            0xC9 => {
-                trace!("0xCB {:#04X}: SET 1,C. C:{:#04X}", opcode, self.read_register_c());
+        self.interrupt_master_enable = false;
+        mmu.interrupts.acknowledge(interrupt);
 
-                let result = self.set_bit(self.read_register_c(), 1);
-                self.write_register_c(result);
-            },
-            0xCA => {
-                trace!("0xCB {:#04X}: SET 1,D. D:{:#04X}", opcode, self.read_register_d());
+        // EI, HALT with an interrupt pending trips the HALT bug and then services the interrupt straight away,
+        // so the handler returns to the HALT
+        if self.is_halt_bug {
+            self.is_halt_bug = false;
+            self.program_counter = self.program_counter.wrapping_sub(1);
+        }
 
-                let result = self.set_bit(self.read_register_d(), 1);
-                self.write_register_d(result);
-            },
-            0xCB => {
-                trace!("0xCB {:#04X}: SET 1,E. E:{:#04X}", opcode, self.read_register_e());
+        // Two idle cycles, the push, then a cycle to jump to the vector
+        self.advance_machine_cycle(mmu);
+        self.push_word(mmu, self.program_counter);
+        self.advance_machine_cycle(mmu);
+        self.program_counter = interrupt.vector();
 
-                let result = self.set_bit(self.read_register_e(), 1);
-                self.write_register_e(result);
-            },
-            0xCC => {
-                trace!("0xCB {:#04X}: SET 1,H. H:{:#04X}", opcode, self.read_register_h());
+        self.clock.m = INTERRUPT_DISPATCH_MACHINE_CYCLES;
+        self.clock.t = INTERRUPT_DISPATCH_MACHINE_CYCLES * 4;
+    }
 
-                let result = self.set_bit(self.read_register_h(), 1);
-                self.write_register_h(result);
-            },
-            0xCD => {
-                trace!("0xCB {:#04X}: SET 1,L. L:{:#04X}", opcode, self.read_register_l());
+    fn process_opcode(&mut self, mmu: &mut MMU, opcode: u8) -> Result<(), EmuError> {
+        let mut use_machine_cycles_branched: bool = false;
+        let mut increment_program_counter: bool = true;
 
-                let result = self.set_bit(self.read_register_l(), 1);
-                self.write_register_l(result);
+        // Opcodes are laid out as xxyyyzzz, and yyy is often ppq. Operands come out of the fields the
+        // same way as in disasm::decode(), so each instruction family is only written once
+        let x = opcode >> 6;
+        let y = ((opcode >> 3) & 0x7) as usize;
+        let z = (opcode & 0x7) as usize;
+        let p = y >> 1;
+        let q = y & 1;
+
+        match (x, z) {
+            (0, 0) => {
+                match y {
+                    0 => {
+                        trace!("{:#04X}: nop.", opcode);
+                    },
+                    1 => {
+                        let address = self.read_word(mmu, self.program_counter + 1);
+                        trace!("{:#04X}: ld [{:#06X}],sp. SP:{:#06X}", opcode, address, self.stack_pointer);
+
+                        self.write_byte(mmu, address, self.stack_pointer as u8);
+                        self.write_byte(mmu, address.wrapping_add(1), (self.stack_pointer >> 8) as u8);
+                    },
+                    2 => {
+                        // Either way DIV is reset
+                        mmu.write_byte(0xFF04, 0);
+                        if mmu.is_speed_switch_armed {
+                            // CGB: STOP with KEY1 armed switches CPU speed instead of stopping
+                            mmu.is_speed_switch_armed = false;
+                            mmu.is_double_speed = !mmu.is_double_speed;
+                            debug!("Switched to {} speed", if mmu.is_double_speed { "double" } else { "normal" });
+                        } else {
+                            debug!("Entering STOP");
+                            self.is_stopped = true;
+                            mmu.gpu.clear_frame_buffer();
+                        }
+                    },
+                    _ => {
+                        // y is 3 for an unconditional JR, 4-7 for JR NZ/Z/NC/C
+                        let offset = self.read_byte(mmu, self.program_counter + 1) as i8;
+                        trace!("{:#04X}: jr {}{}. PC:{:#06X}", opcode, if y == 3 { "" } else { CONDITIONS[y - 4] }, offset, self.program_counter);
+
+                        if y == 3 || self.condition(y - 4) {
+                            self.program_counter = self.program_counter.wrapping_add(2).wrapping_add(offset as u16);
+                            increment_program_counter = false;
+                            use_machine_cycles_branched = true;
+                        }
+                    }
+                }
             },
-            0xCE => {
-                trace!("0xCB {:#04X}: SET 1,(HL). (HL):{:#04X}", opcode, mmu.read_byte(self.read_register_hl()));
+            (0, 1) => {
+                if q == 0 {
+                    let value = self.read_word(mmu, self.program_counter + 1);
+                    trace!("{:#04X}: ld {},{:#06X}.", opcode, R16[p], value);
 
-                let result = self.set_bit(mmu.read_byte(self.read_register_hl()), 1);
-                mmu.write_byte(self.read_register_hl(), result);
-            },
-            0xCF => {
-                trace!("0xCB {:#04X}: SET 1,A. A:{:#04X}", opcode, self.read_register_a());
+                    self.write_register_r16(p, value);
+                } else {
+                    trace!("{:#04X}: add hl,{}. HL:{:#06X} {}:{:#06X}", opcode, R16[p], self.read_register_hl(), R16[p], self.read_register_r16(p));
 
-                let result = self.set_bit(self.read_register_a(), 1);
-                self.write_register_a(result);
+                    self.add_u16_to_hl(self.read_register_r16(p));
+                }
             },
-            0xD0 => {
-                trace!("0xCB {:#04X}: SET 2,B. B:{:#04X}", opcode, self.read_register_b());
+            (0, 2) => {
+                if q == 0 {
+                    trace!("{:#04X}: ld {},a. A:{:#04X}", opcode, R16_MEMORY[p], self.read_register_a());
 
-                let result = self.set_bit(self.read_register_b(), 2);
-                self.write_register_b(result);
-            },
-            0xD1 => {
-                trace!("0xCB {:#04X}: SET 2,C. C:{:#04X}", opcode, self.read_register_c());
+                    let address = self.memory_address_r16(p);
+                    self.write_byte(mmu, address, self.read_register_a());
+                } else {
+                    trace!("{:#04X}: ld a,{}.", opcode, R16_MEMORY[p]);
 
-                let result = self.set_bit(self.read_register_c(), 2);
-                self.write_register_c(result);
+                    let address = self.memory_address_r16(p);
+                    let value = self.read_byte(mmu, address);
+                    self.write_register_a(value);
+                }
             },
-            0xD2 => {
-                trace!("0xCB {:#04X}: SET 2,D. D:{:#04X}", opcode, self.read_register_d());
+            (0, 3) => {
+                trace!("{:#04X}: {} {}. {}:{:#06X}", opcode, if q == 0 { "inc" } else { "dec" }, R16[p], R16[p], self.read_register_r16(p));
 
-                let result = self.set_bit(self.read_register_d(), 2);
-                self.write_register_d(result);
+                let value = self.read_register_r16(p);
+                if q == 0 {
+                    self.write_register_r16(p, value.wrapping_add(1));
+                } else {
+                    self.write_register_r16(p, value.wrapping_sub(1));
+                }
             },
-            0xD3 => {
-                trace!("0xCB {:#04X}: SET 2,E. E:{:#04X}", opcode, self.read_register_e());
-
-                let result = self.set_bit(self.read_register_e(), 2);
-                self.write_register_e(result);
+            (0, 4) => {
+                let value = self.read_register_r8(mmu, y);
+                trace!("{:#04X}: inc {}. {}:{:#04X}", opcode, R8[y], R8[y], value);
+
+                let result = self.increase_register_u8(value);
+                self.write_register_r8(mmu, y, result);
+            },
+            (0, 5) => {
+                let value = self.read_register_r8(mmu, y);
+                trace!("{:#04X}: dec {}. {}:{:#04X}", opcode, R8[y], R8[y], value);
+
+                let result = self.decrease_register_u8(value);
+                self.write_register_r8(mmu, y, result);
+            },
+            (0, 6) => {
+                let value = self.read_byte(mmu, self.program_counter + 1);
+                trace!("{:#04X}: ld {},{:#04X}.", opcode, R8[y], value);
+
+                self.write_register_r8(mmu, y, value);
+            },
+            (0, 7) => {
+                trace!("{:#04X}: {}. A:{:#04X} F:{:#04X}", opcode, ACCUMULATOR_OPS[y], self.read_register_a(), self.read_register_f());
+
+                match y {
+                    0..=3 => {
+                        // RLCA, RRCA, RLA and RRA are the first four prefixed rotates on A, except Z is always cleared
+                        let result = self.rotate_or_shift(y, self.read_register_a(), false);
+                        self.write_register_a(result);
+                    },
+                    4 => {
+                        let mut a: i16 = self.read_register_a() as i16;
+                        if self.read_flag(SUBTRACTION_BIT) == 0 {
+                            if self.read_flag(HALF_CARRY_BIT) == 1 || (a & 0xF) > 9 {
+                                a += 0x06;
+                            }
+
+                            if self.read_flag(CARRY_BIT) == 1 || (a > 0x9F) {
+                                a += 0x60;
+                            }
+                        } else {
+                            if self.read_flag(HALF_CARRY_BIT) == 1 {
+                                a = (a - 6) & 0xFF;
+                            }
+
+                            if self.read_flag(CARRY_BIT) == 1 {
+                                a -= 0x60;
+                            }
+                        }
+
+                        self.unset_flag_bit(HALF_CARRY_BIT);
+                        self.unset_flag_bit(ZERO_BIT);
+
+                        if a & 0x100 == 0x100 {
+                            self.set_flag_bit(CARRY_BIT);
+                        }
+
+                        a &= 0xFF;
+
+                        if a == 0 {
+                            self.set_flag_bit(ZERO_BIT);
+                        } else {
+                            self.unset_flag_bit(ZERO_BIT);
+                        }
+
+                        self.write_register_a(a as u8);
+                    },
+                    5 => {
+                        self.write_register_a(!self.read_register_a());
+                        self.set_flag_bit(HALF_CARRY_BIT);
+                        self.set_flag_bit(SUBTRACTION_BIT);
+                    },
+                    6 => {
+                        self.set_flag_bit(CARRY_BIT);
+                        self.unset_flag_bit(HALF_CARRY_BIT);
+                        self.unset_flag_bit(SUBTRACTION_BIT);
+                    },
+                    _ => {
+                        if self.read_flag(CARRY_BIT) == 1 {
+                            self.unset_flag_bit(CARRY_BIT);
+                        } else {
+                            self.set_flag_bit(CARRY_BIT);
+                        }
+                        self.unset_flag_bit(HALF_CARRY_BIT);
+                        self.unset_flag_bit(SUBTRACTION_BIT);
+                    }
+                }
             },
-            0xD4 => {
-                trace!("0xCB {:#04X}: SET 2,H. H:{:#04X}", opcode, self.read_register_h());
+            (1, _) => {
+                // LD (HL),(HL) is where HALT sits
+                if y == 6 && z == 6 {
+                    trace!("{:#04X}: halt.", opcode);
 
-                let result = self.set_bit(self.read_register_h(), 2);
-                self.write_register_h(result);
-            },
-            0xD5 => {
-                trace!("0xCB {:#04X}: SET 2,L. L:{:#04X}", opcode, self.read_register_l());
+                    // With IME off and an interrupt already pending the DMG doesn't halt at all, and trips the HALT bug instead
+                    if !self.interrupt_master_enable && mmu.interrupts.pending() != 0 {
+                        self.is_halt_bug = true;
+                    } else {
+                        self.is_halted = true;
+                    }
+                } else {
+                    let value = self.read_register_r8(mmu, z);
+                    trace!("{:#04X}: ld {},{}. {:#04X}", opcode, R8[y], R8[z], value);
 
-                let result = self.set_bit(self.read_register_l(), 2);
-                self.write_register_l(result);
+                    self.write_register_r8(mmu, y, value);
+                }
             },
-            0xD6 => {
-                trace!("0xCB {:#04X}: SET 2,(HL). (HL):{:#04X}", opcode, mmu.read_byte(self.read_register_hl()));
-
-                let result = self.set_bit(mmu.read_byte(self.read_register_hl()), 2);
-                mmu.write_byte(self.read_register_hl(), result);
+            (2, _) => {
+                let value = self.read_register_r8(mmu, z);
+                trace!("{:#04X}: {} a,{}. A:{:#04X} {}:{:#04X}", opcode, ALU[y], R8[z], self.read_register_a(), R8[z], value);
+
+                self.alu_operation(y, value);
+            },
+            (3, 0) => {
+                match y {
+                    0..=3 => {
+                        trace!("{:#04X}: ret {}. SP:{:#06X}", opcode, CONDITIONS[y], self.stack_pointer);
+
+                        // Checking the condition takes a cycle of its own
+                        self.advance_machine_cycle(mmu);
+                        if self.condition(y) {
+                            self.program_counter = self.pop_word(mmu);
+                            increment_program_counter = false;
+                            use_machine_cycles_branched = true;
+                        }
+                    },
+                    4 => {
+                        let offset = self.read_byte(mmu, self.program_counter + 1);
+                        trace!("{:#04X}: ldh [{:#06X}],a. A:{:#04X}", opcode, 0xFF00 + offset as u16, self.read_register_a());
+
+                        self.write_byte(mmu, 0xFF00 + offset as u16, self.read_register_a());
+                    },
+                    5 => {
+                        trace!("{:#04X}: add sp,e8. SP:{:#06X}", opcode, self.stack_pointer);
+
+                        self.stack_pointer = self.add_signed_immediate_to_stack_pointer(mmu);
+                    },
+                    6 => {
+                        let offset = self.read_byte(mmu, self.program_counter + 1);
+                        trace!("{:#04X}: ldh a,[{:#06X}].", opcode, 0xFF00 + offset as u16);
+
+                        let value = self.read_byte(mmu, 0xFF00 + offset as u16);
+                        self.write_register_a(value);
+                    },
+                    _ => {
+                        trace!("{:#04X}: ld hl,sp+e8. SP:{:#06X}", opcode, self.stack_pointer);
+
+                        let result = self.add_signed_immediate_to_stack_pointer(mmu);
+                        self.write_register_hl(result);
+                    }
+                }
             },
-            0xD7 => {
-                trace!("0xCB {:#04X}: SET 2,A. A:{:#04X}", opcode, self.read_register_a());
+            (3, 1) => {
+                if q == 0 {
+                    trace!("{:#04X}: pop {}. SP:{:#06X}", opcode, R16_STACK[p], self.stack_pointer);
 
-                let result = self.set_bit(self.read_register_a(), 2);
-                self.write_register_a(result);
+                    let value = self.pop_word(mmu);
+                    self.write_register_r16_stack(p, value);
+                } else {
+                    match p {
+                        0 | 1 => {
+                            trace!("{:#04X}: {}. SP:{:#06X}", opcode, if p == 0 { "ret" } else { "reti" }, self.stack_pointer);
+
+                            self.program_counter = self.pop_word(mmu);
+                            increment_program_counter = false;
+                            if p == 1 {
+                                self.interrupt_master_enable = true;
+                            }
+                        },
+                        2 => {
+                            trace!("{:#04X}: jp hl. HL:{:#06X}", opcode, self.read_register_hl());
+
+                            self.program_counter = self.read_register_hl();
+                            increment_program_counter = false;
+                        },
+                        _ => {
+                            trace!("{:#04X}: ld sp,hl. HL:{:#06X}", opcode, self.read_register_hl());
+
+                            self.stack_pointer = self.read_register_hl();
+                        }
+                    }
+                }
             },
-            0xD8 => {
-                trace!("0xCB {:#04X}: SET 3,B. B:{:#04X}", opcode, self.read_register_b());
-
-                let result = self.set_bit(self.read_register_b(), 3);
-                self.write_register_b(result);
+            (3, 2) => {
+                match y {
+                    0..=3 => {
+                        let address = self.read_word(mmu, self.program_counter + 1);
+                        trace!("{:#04X}: jp {},{:#06X}.", opcode, CONDITIONS[y], address);
+
+                        if self.condition(y) {
+                            self.program_counter = address;
+                            increment_program_counter = false;
+                            use_machine_cycles_branched = true;
+                        }
+                    },
+                    4 => {
+                        trace!("{:#04X}: ld [c],a. C:{:#04X} A:{:#04X}", opcode, self.read_register_c(), self.read_register_a());
+
+                        self.write_byte(mmu, 0xFF00 + self.read_register_c() as u16, self.read_register_a());
+                    },
+                    5 => {
+                        let address = self.read_word(mmu, self.program_counter + 1);
+                        trace!("{:#04X}: ld [{:#06X}],a. A:{:#04X}", opcode, address, self.read_register_a());
+
+                        self.write_byte(mmu, address, self.read_register_a());
+                    },
+                    6 => {
+                        trace!("{:#04X}: ld a,[c]. C:{:#04X}", opcode, self.read_register_c());
+
+                        let value = self.read_byte(mmu, 0xFF00 + self.read_register_c() as u16);
+                        self.write_register_a(value);
+                    },
+                    _ => {
+                        let address = self.read_word(mmu, self.program_counter + 1);
+                        trace!("{:#04X}: ld a,[{:#06X}].", opcode, address);
+
+                        let value = self.read_byte(mmu, address);
+                        self.write_register_a(value);
+                    }
+                }
             },
-            0xD9 => {
-                trace!("0xCB {:#04X}: SET 3,C. C:{:#04X}", opcode, self.read_register_c());
+            (3, 3) if y == 0 => {
+                let address = self.read_word(mmu, self.program_counter + 1);
+                trace!("{:#04X}: jp {:#06X}.", opcode, address);
 
-                let result = self.set_bit(self.read_register_c(), 3);
-                self.write_register_c(result);
+                self.program_counter = address;
+                increment_program_counter = false;
             },
-            0xDA => {
-                trace!("0xCB {:#04X}: SET 3,D. D:{:#04X}", opcode, self.read_register_d());
-
-                let result = self.set_bit(self.read_register_d(), 3);
-                self.write_register_d(result);
+            (3, 3) if y == 1 => {
+                unreachable!("0xCB instruction in the wrong OpCode table");
             },
-            0xDB => {
-                trace!("0xCB {:#04X}: SET 3,E. E:{:#04X}", opcode, self.read_register_e());
+            (3, 3) if y == 6 => {
+                trace!("{:#04X}: di.", opcode);
 
-                let result = self.set_bit(self.read_register_e(), 3);
-                self.write_register_e(result);
+                self.interrupt_master_enable = false;
+                self.is_ime_scheduled = false;
             },
-            0xDC => {
-                trace!("0xCB {:#04X}: SET 3,H. H:{:#04X}", opcode, self.read_register_h());
+            (3, 3) if y == 7 => {
+                trace!("{:#04X}: ei.", opcode);
 
-                let result = self.set_bit(self.read_register_h(), 3);
-                self.write_register_h(result);
+                self.is_ime_scheduled = true;
             },
-            0xDD => {
-                trace!("0xCB {:#04X}: SET 3,L. L:{:#04X}", opcode, self.read_register_l());
+            (3, 4) if y < 4 => {
+                let address = self.read_word(mmu, self.program_counter + 1);
+                trace!("{:#04X}: call {},{:#06X}.", opcode, CONDITIONS[y], address);
 
-                let result = self.set_bit(self.read_register_l(), 3);
-                self.write_register_l(result);
+                if self.condition(y) {
+                    // Return to the instruction after the call
+                    self.push_word(mmu, self.program_counter + 3);
+                    self.program_counter = address;
+                    increment_program_counter = false;
+                    use_machine_cycles_branched = true;
+                }
             },
-            0xDE => {
-                trace!("0xCB {:#04X}: SET 3,(HL). (HL):{:#04X}", opcode, mmu.read_byte(self.read_register_hl()));
+            (3, 5) if q == 0 => {
+                trace!("{:#04X}: push {}. {}:{:#06X}", opcode, R16_STACK[p], R16_STACK[p], self.read_register_r16_stack(p));
 
-                let result = self.set_bit(mmu.read_byte(self.read_register_hl()), 3);
-                mmu.write_byte(self.read_register_hl(), result);
+                self.push_word(mmu, self.read_register_r16_stack(p));
             },
-            0xDF => {
-                trace!("0xCB {:#04X}: SET 3,A. A:{:#04X}", opcode, self.read_register_a());
+            (3, 5) if p == 0 => {
+                let address = self.read_word(mmu, self.program_counter + 1);
+                trace!("{:#04X}: call {:#06X}.", opcode, address);
 
-                let result = self.set_bit(self.read_register_a(), 3);
-                self.write_register_a(result);
+                // Return to the instruction after the call
+                self.push_word(mmu, self.program_counter + 3);
+                self.program_counter = address;
+                increment_program_counter = false;
             },
-            0xE0 => {
-                trace!("0xCB {:#04X}: SET 4,B. B:{:#04X}", opcode, self.read_register_b());
+            (3, 6) => {
+                let value = self.read_byte(mmu, self.program_counter + 1);
+                trace!("{:#04X}: {} a,{:#04X}. A:{:#04X}", opcode, ALU[y], value, self.read_register_a());
 
-                let result = self.set_bit(self.read_register_b(), 4);
-                self.write_register_b(result);
+                self.alu_operation(y, value);
             },
-            0xE1 => {
-                trace!("0xCB {:#04X}: SET 4,C. C:{:#04X}", opcode, self.read_register_c());
+            (3, 7) => {
+                trace!("{:#04X}: rst {:#04X}.", opcode, y * 8);
 
-                let result = self.set_bit(self.read_register_c(), 4);
-                self.write_register_c(result);
+                self.push_word(mmu, self.program_counter + 1);
+                self.program_counter = (y * 8) as u16;
+                increment_program_counter = false;
             },
-            0xE2 => {
-                trace!("0xCB {:#04X}: SET 4,D. D:{:#04X}", opcode, self.read_register_d());
+            _ => {
+                // 0xD3, 0xDB, 0xDD, 0xE3, 0xE4, 0xEB, 0xEC, 0xED, 0xF4, 0xFC and 0xFD
+                error!("Tried to call unused OpCode {}", opcode);
+                return Err(EmuError::UnknownOpcode { opcode, program_counter: self.program_counter });
+            }
+        }
 
-                let result = self.set_bit(self.read_register_d(), 4);
-                self.write_register_d(result);
-            },
-            0xE3 => {
-                trace!("0xCB {:#04X}: SET 4,E. E:{:#04X}", opcode, self.read_register_e());
+        self.update_clock_and_program_counter(opcode, use_machine_cycles_branched, increment_program_counter);
+        Ok(())
+    }
 
-                let result = self.set_bit(self.read_register_e(), 4);
-                self.write_register_e(result);
-            },
-            0xE4 => {
-                trace!("0xCB {:#04X}: SET 4,H. H:{:#04X}", opcode, self.read_register_h());
+    fn process_cb_opcode(&mut self, mmu: &mut MMU) {
+        let opcode = self.read_byte(mmu, self.program_counter + 1);
 
-                let result = self.set_bit(self.read_register_h(), 4);
-                self.write_register_h(result);
-            },
-            0xE5 => {
-                trace!("0xCB {:#04X}: SET 4,L. L:{:#04X}", opcode, self.read_register_l());
+        // Prefixed opcodes are xxyyyzzz too: x picks rotate/shift, BIT, RES or SET, y is the operation or bit
+        // number and z the register
+        let x = opcode >> 6;
+        let y = ((opcode >> 3) & 0x7) as usize;
+        let z = (opcode & 0x7) as usize;
 
-                let result = self.set_bit(self.read_register_l(), 4);
-                self.write_register_l(result);
-            },
-            0xE6 => {
-                trace!("0xCB {:#04X}: SET 4,(HL). (HL):{:#04X}", opcode, mmu.read_byte(self.read_register_hl()));
+        let value = self.read_register_r8(mmu, z);
 
-                let result = self.set_bit(mmu.read_byte(self.read_register_hl()), 4);
-                mmu.write_byte(self.read_register_hl(), result);
-            },
-            0xE7 => {
-                trace!("0xCB {:#04X}: SET 4,A. A:{:#04X}", opcode, self.read_register_a());
+        match x {
+            0 => {
+                trace!("{:#04X}: {} {}. {}:{:#04X}", opcode, ROTATES[y], R8[z], R8[z], value);
 
-                let result = self.set_bit(self.read_register_a(), 4);
-                self.write_register_a(result);
+                let result = self.rotate_or_shift(y, value, true);
+                self.write_register_r8(mmu, z, result);
             },
-            0xE8 => {
-                trace!("0xCB {:#04X}: SET 5,B. B:{:#04X}", opcode, self.read_register_b());
+            1 => {
+                trace!("{:#04X}: bit {},{}. {}:{:#04X}", opcode, y, R8[z], R8[z], value);
 
-                let result = self.set_bit(self.read_register_b(), 5);
-                self.write_register_b(result);
+                self.test_bit(value, y as u8);
             },
-            0xE9 => {
-                trace!("0xCB {:#04X}: SET 5,C. C:{:#04X}", opcode, self.read_register_c());
+            2 => {
+                trace!("{:#04X}: res {},{}. {}:{:#04X}", opcode, y, R8[z], R8[z], value);
 
-                let result = self.set_bit(self.read_register_c(), 5);
-                self.write_register_c(result);
+                let result = self.reset_bit(value, y as u8);
+                self.write_register_r8(mmu, z, result);
             },
-            0xEA => {
-                trace!("0xCB {:#04X}: SET 5,D. D:{:#04X}", opcode, self.read_register_d());
+            _ => {
+                trace!("{:#04X}: set {},{}. {}:{:#04X}", opcode, y, R8[z], R8[z], value);
 
-                let result = self.set_bit(self.read_register_d(), 5);
-                self.write_register_d(result);
-            },
-            0xEB => {
-                trace!("0xCB {:#04X}: SET 5,E. E:{:#04X}", opcode, self.read_register_e());
+                let result = self.set_bit(value, y as u8);
+                self.write_register_r8(mmu, z, result);
+            }
+        }
 
-                let result = self.set_bit(self.read_register_e(), 5);
-                self.write_register_e(result);
-            },
-            0xEC => {
-                trace!("0xCB {:#04X}: SET 5,H. H:{:#04X}", opcode, self.read_register_h());
+        self.update_clock_and_program_counter_for_cb_operations(opcode);
+    }
 
-                let result = self.set_bit(self.read_register_h(), 5);
-                self.write_register_h(result);
-            },
-            0xED => {
-                trace!("0xCB {:#04X}: SET 5,L. L:{:#04X}", opcode, self.read_register_l());
+    // Operand for the 8 bit register fields in an opcode, index 6 is the byte at (HL)
+    fn read_register_r8(&mut self, mmu: &mut MMU, index: usize) -> u8 {
+        match index {
+            0 => return self.read_register_b(),
+            1 => return self.read_register_c(),
+            2 => return self.read_register_d(),
+            3 => return self.read_register_e(),
+            4 => return self.read_register_h(),
+            5 => return self.read_register_l(),
+            6 => return self.read_byte(mmu, self.read_register_hl()),
+            _ => return self.read_register_a()
+        }
+    }
 
-                let result = self.set_bit(self.read_register_l(), 5);
-                self.write_register_l(result);
-            },
-            0xEE => {
-                trace!("0xCB {:#04X}: SET 5,(HL). (HL):{:#04X}", opcode, mmu.read_byte(self.read_register_hl()));
+    fn write_register_r8(&mut self, mmu: &mut MMU, index: usize, value: u8) {
+        match index {
+            0 => self.write_register_b(value),
+            1 => self.write_register_c(value),
+            2 => self.write_register_d(value),
+            3 => self.write_register_e(value),
+            4 => self.write_register_h(value),
+            5 => self.write_register_l(value),
+            6 => self.write_byte(mmu, self.read_register_hl(), value),
+            _ => self.write_register_a(value)
+        }
+    }
 
-                let result = self.set_bit(mmu.read_byte(self.read_register_hl()), 5);
-                mmu.write_byte(self.read_register_hl(), result);
-            },
-            0xEF => {
-                trace!("0xCB {:#04X}: SET 5,A. A:{:#04X}", opcode, self.read_register_a());
+    // BC, DE, HL, SP
+    fn read_register_r16(&self, index: usize) -> u16 {
+        match index {
+            0 => return self.read_register_bc(),
+            1 => return self.read_register_de(),
+            2 => return self.read_register_hl(),
+            _ => return self.stack_pointer
+        }
+    }
 
-                let result = self.set_bit(self.read_register_a(), 5);
-                self.write_register_a(result);
-            },
-            0xF0 => {
-                trace!("0xCB {:#04X}: SET 6,B. B:{:#04X}", opcode, self.read_register_b());
+    fn write_register_r16(&mut self, index: usize, value: u16) {
+        match index {
+            0 => self.write_register_bc(value),
+            1 => self.write_register_de(value),
+            2 => self.write_register_hl(value),
+            _ => self.stack_pointer = value
+        }
+    }
 
-                let result = self.set_bit(self.read_register_b(), 6);
-                self.write_register_b(result);
-            },
-            0xF1 => {
-                trace!("0xCB {:#04X}: SET 6,C. C:{:#04X}", opcode, self.read_register_c());
+    // PUSH and POP swap SP for AF
+    fn read_register_r16_stack(&self, index: usize) -> u16 {
+        match index {
+            3 => return self.read_register_af(),
+            _ => return self.read_register_r16(index)
+        }
+    }
 
-                let result = self.set_bit(self.read_register_c(), 6);
-                self.write_register_c(result);
-            },
-            0xF2 => {
-                trace!("0xCB {:#04X}: SET 6,D. D:{:#04X}", opcode, self.read_register_d());
+    fn write_register_r16_stack(&mut self, index: usize, value: u16) {
+        match index {
+            // The low nibble of F doesn't exist, so it always reads back as 0
+            3 => self.write_register_af(value & 0xFFF0),
+            _ => self.write_register_r16(index, value)
+        }
+    }
 
-                let result = self.set_bit(self.read_register_d(), 6);
-                self.write_register_d(result);
-            },
-            0xF3 => {
-                trace!("0xCB {:#04X}: SET 6,E. E:{:#04X}", opcode, self.read_register_e());
+    // Address for LD (r16),A and LD A,(r16): (BC), (DE), (HL+), (HL-). HL is stepped once it's been used
+    fn memory_address_r16(&mut self, index: usize) -> u16 {
+        match index {
+            0 => return self.read_register_bc(),
+            1 => return self.read_register_de(),
+            _ => {
+                let address = self.read_register_hl();
+                if index == 2 {
+                    self.write_register_hl(address.wrapping_add(1));
+                } else {
+                    self.write_register_hl(address.wrapping_sub(1));
+                }
+                return address;
+            }
+        }
+    }
 
-                let result = self.set_bit(self.read_register_e(), 6);
-                self.write_register_e(result);
-            },
-            0xF4 => {
-                trace!("0xCB {:#04X}: SET 6,H. H:{:#04X}", opcode, self.read_register_h());
+    // NZ, Z, NC, C
+    fn condition(&self, index: usize) -> bool {
+        match index {
+            0 => return self.read_flag(ZERO_BIT) == 0,
+            1 => return self.read_flag(ZERO_BIT) == 1,
+            2 => return self.read_flag(CARRY_BIT) == 0,
+            _ => return self.read_flag(CARRY_BIT) == 1
+        }
+    }
 
-                let result = self.set_bit(self.read_register_h(), 6);
-                self.write_register_h(result);
-            },
-            0xF5 => {
-                trace!("0xCB {:#04X}: SET 6,L. L:{:#04X}", opcode, self.read_register_l());
+    // ADD, ADC, SUB, SBC, AND, XOR, OR, CP with A
+    fn alu_operation(&mut self, index: usize, value: u8) {
+        match index {
+            0 => self.add_u8_to_a(value),
+            1 => self.add_u8_and_carry_to_a(value),
+            2 => self.subtract_u8_from_a(value),
+            3 => self.subtract_u8_and_carry_from_a(value),
+            4 => self.and_with_register_a(value),
+            5 => self.xor_with_register_a(value),
+            6 => self.or_with_register_a(value),
+            _ => self.compare_with_register_a(value)
+        }
+    }
 
-                let result = self.set_bit(self.read_register_l(), 6);
-                self.write_register_l(result);
-            },
-            0xF6 => {
-                trace!("0xCB {:#04X}: SET 6,(HL). (HL):{:#04X}", opcode, mmu.read_byte(self.read_register_hl()));
+    // RLC, RRC, RL, RR, SLA, SRA, SWAP, SRL
+    fn rotate_or_shift(&mut self, index: usize, value: u8, is_prefixed: bool) -> u8 {
+        match index {
+            0 => return self.rotate_left(value, is_prefixed),
+            1 => return self.rotate_right(value, is_prefixed),
+            2 => return self.rotate_left_through_carry(value, is_prefixed),
+            3 => return self.rotate_right_through_carry(value, is_prefixed),
+            4 => return self.shift_left(value),
+            5 => return self.shift_right_preserve_msb(value),
+            6 => return self.swap_byte(value),
+            _ => return self.shift_right(value)
+        }
+    }
 
-                let result = self.set_bit(mmu.read_byte(self.read_register_hl()), 6);
-                mmu.write_byte(self.read_register_hl(), result);
-            },
-            0xF7 => {
-                trace!("0xCB {:#04X}: SET 6,A. A:{:#04X}", opcode, self.read_register_a());
+    // Stack grows downwards. SP is decremented during an idle cycle, then the high byte is written first
+    fn push_word(&mut self, mmu: &mut MMU, value: u16) {
+        self.advance_machine_cycle(mmu);
+        self.stack_pointer = self.stack_pointer.wrapping_sub(1);
+        self.write_byte(mmu, self.stack_pointer, (value >> 8) as u8);
+        self.stack_pointer = self.stack_pointer.wrapping_sub(1);
+        self.write_byte(mmu, self.stack_pointer, value as u8);
+    }
 
-                let result = self.set_bit(self.read_register_a(), 6);
-                self.write_register_a(result);
-            },
-            0xF8 => {
-                trace!("0xCB {:#04X}: SET 7,B. B:{:#04X}", opcode, self.read_register_b());
+    fn pop_word(&mut self, mmu: &mut MMU) -> u16 {
+        let value = self.read_word(mmu, self.stack_pointer);
+        self.stack_pointer = self.stack_pointer.wrapping_add(2);
+        return value;
+    }
 
-                let result = self.set_bit(self.read_register_b(), 7);
-                self.write_register_b(result);
-            },
-            0xF9 => {
-                trace!("0xCB {:#04X}: SET 7,C. C:{:#04X}", opcode, self.read_register_c());
+    // Every memory access takes a machine cycle, and the rest of the system is ticked ahead of it
+    fn read_byte(&mut self, mmu: &mut MMU, address: u16) -> u8 {
+        self.advance_machine_cycle(mmu);
+        return mmu.read_byte(address);
+    }
 
-                let result = self.set_bit(self.read_register_c(), 7);
-                self.write_register_c(result);
-            },
-            0xFA => {
-                trace!("0xCB {:#04X}: SET 7,D. D:{:#04X}", opcode, self.read_register_d());
+    fn write_byte(&mut self, mmu: &mut MMU, address: u16, value: u8) {
+        self.advance_machine_cycle(mmu);
+        mmu.write_byte(address, value);
+    }
 
-                let result = self.set_bit(self.read_register_d(), 7);
-                self.write_register_d(result);
-            },
-            0xFB => {
-                trace!("0xCB {:#04X}: SET 7,E. E:{:#04X}", opcode, self.read_register_e());
+    // Little endian, low byte is read first
+    fn read_word(&mut self, mmu: &mut MMU, address: u16) -> u16 {
+        let lo = self.read_byte(mmu, address) as u16;
+        let hi = self.read_byte(mmu, address.wrapping_add(1)) as u16;
+        return lo | (hi << 8);
+    }
 
-                let result = self.set_bit(self.read_register_e(), 7);
-                self.write_register_e(result);
-            },
-            0xFC => {
-                trace!("0xCB {:#04X}: SET 7,H. H:{:#04X}", opcode, self.read_register_h());
+    fn advance_machine_cycle(&mut self, mmu: &mut MMU) {
+        mmu.tick();
+        self.machine_cycles += 1;
+    }
 
-                let result = self.set_bit(self.read_register_h(), 7);
-                self.write_register_h(result);
-            },
-            0xFD => {
-                trace!("0xCB {:#04X}: SET 7,L. L:{:#04X}", opcode, self.read_register_l());
+    // SP plus the signed byte after the opcode, for ADD SP,e8 and LD HL,SP+e8. H and C come from the low byte
+    fn add_signed_immediate_to_stack_pointer(&mut self, mmu: &mut MMU) -> u16 {
+        let offset = self.read_byte(mmu, self.program_counter + 1) as i8 as u16;
+        let result = self.stack_pointer.wrapping_add(offset);
 
-                let result = self.set_bit(self.read_register_l(), 7);
-                self.write_register_l(result);
-            },
-            0xFE => {
-                trace!("0xCB {:#04X}: SET 7,(HL). (HL):{:#04X}", opcode, mmu.read_byte(self.read_register_hl()));
+        self.unset_flag_bit(ZERO_BIT);
+        self.unset_flag_bit(SUBTRACTION_BIT);
 
-                let result = self.set_bit(mmu.read_byte(self.read_register_hl()), 7);
-                mmu.write_byte(self.read_register_hl(), result);
-            },
-            0xFF => {
-                trace!("0xCB {:#04X}: SET 7,A. A:{:#04X}", opcode, self.read_register_a());
+        if ((self.stack_pointer ^ offset ^ result) & 0x100) == 0x100 {
+            self.set_flag_bit(CARRY_BIT);
+        } else {
+            self.unset_flag_bit(CARRY_BIT);
+        }
 
-                let result = self.set_bit(self.read_register_a(), 7);
-                self.write_register_a(result);
-            },
-            _ => {
-                error!("Unknown 0xCB OpCode {:#04X}", opcode);
-                exit(1);
-            }
+        if ((self.stack_pointer ^ offset ^ result) & 0x10) == 0x10 {
+            self.set_flag_bit(HALF_CARRY_BIT);
+        } else {
+            self.unset_flag_bit(HALF_CARRY_BIT);
         }
 
-        self.update_clock_and_program_counter_for_cb_operations(opcode);
+        return result;
     }
 
     fn update_clock_and_program_counter(&mut self, opcode: u8, use_machine_cycles_branched: bool, increment_program_counter: bool) {
@@ -3369,6 +1037,7 @@ impl CPU {
         return result;
     }
 
+    #[allow(clippy::needless_late_init)]
     fn rotate_right_through_carry(&mut self, value: u8, is_prefixed: bool) -> u8 {
         // Store carry flag
         let carry;
@@ -3450,6 +1119,7 @@ impl CPU {
         return result;
     }
 
+    #[allow(clippy::needless_late_init)]
     fn shift_right_preserve_msb(&mut self, value: u8) -> u8 {
         // Unset flag bits
         self.unset_flag_bit(SUBTRACTION_BIT);
@@ -3728,49 +1398,49 @@ impl CPU {
         return result;
     }
 
-    fn read_register_a(&self) -> u8 {
+    pub fn read_register_a(&self) -> u8 {
         unsafe {
             return self.af.hilo.hi;
         }
     }
 
-    fn read_register_b(&self) -> u8 {
+    pub fn read_register_b(&self) -> u8 {
         unsafe {
             return self.bc.hilo.hi;
         }
     }
 
-    fn read_register_c(&self) -> u8 {
+    pub fn read_register_c(&self) -> u8 {
         unsafe {
             return self.bc.hilo.lo;
         }
     }
 
-    fn read_register_d(&self) -> u8 {
+    pub fn read_register_d(&self) -> u8 {
         unsafe {
             return self.de.hilo.hi;
         }
     }
 
-    fn read_register_e(&self) -> u8 {
+    pub fn read_register_e(&self) -> u8 {
         unsafe {
             return self.de.hilo.lo;
         }
     }
 
-    fn read_register_f(&self) -> u8 {
+    pub fn read_register_f(&self) -> u8 {
         unsafe {
             return self.af.hilo.lo;
         }
     }
 
-    fn read_register_h(&self) -> u8 {
+    pub fn read_register_h(&self) -> u8 {
         unsafe {
             return self.hl.hilo.hi;
         }
     }
 
-    fn read_register_l(&self) -> u8 {
+    pub fn read_register_l(&self) -> u8 {
         unsafe {
             return self.hl.hilo.lo;
         }
@@ -3864,25 +1534,13 @@ impl CPU {
         return 0x80 & x;
     }
 
-    fn least_significant_bit_u16(&mut self, x: u16) -> u16 {
-        return 0x01 & x;
-    }
-
-    fn most_significant_bit_u16(&mut self, x: u16) -> u16 {
-        return 0x8000 & x;
-    }
-
     fn read_flag(&self, flag: u8) -> u8 {
         match flag {
             ZERO_BIT => return (self.read_register_f() & ZERO_BIT) >> 7,
             SUBTRACTION_BIT => return (self.read_register_f() & SUBTRACTION_BIT) >> 6,
             HALF_CARRY_BIT => return (self.read_register_f() & HALF_CARRY_BIT) >> 5,
             CARRY_BIT => return (self.read_register_f() & CARRY_BIT) >> 4,
-            _ => {
-                error!("Tried to read unknown flag {:#04X}", flag);
-            }
+            _ => unreachable!("Tried to read unknown flag {:#04X}", flag)
         }
-
-        exit(1);
     }
 }
\ No newline at end of file
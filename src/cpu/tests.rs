@@ -0,0 +1,194 @@
+use super::CPU;
+use crate::mmu::MMU;
+
+// Instructions run from WRAM, clear of the data they touch
+const CODE_START: u16 = 0xC0E0;
+
+// Memory every vector starts with: two bytes for HL/BC/a16 to point at, a return address on the stack and a byte of HRAM
+const FIXTURE: [(u16, u8); 5] = [(0xC010, 0x5A), (0xC011, 0xA5), (0xC07E, 0x34), (0xC07F, 0x12), (0xFF80, 0x3C)];
+
+// One instruction per line, a few from each family: the code, registers before (AF BC DE HL SP),
+// registers after (AF BC DE HL SP PC), machine cycles, and memory after for the ones that store
+type Vector = (&'static [u8], [u16; 5], [u16; 6], u8, &'static [(u16, u8)]);
+
+const VECTORS: [Vector; 67] = [
+    // Loads
+    (&[0x00],             [0x0000, 0x0000, 0x0000, 0x0000, 0xC080], [0x0000, 0x0000, 0x0000, 0x0000, 0xC080, 0xC0E1], 1, &[]),             // nop
+    (&[0x78],             [0x0000, 0x4200, 0x0000, 0x0000, 0xC080], [0x4200, 0x4200, 0x0000, 0x0000, 0xC080, 0xC0E1], 1, &[]),             // ld a, b
+    (&[0x06, 0x99],       [0x0000, 0x0000, 0x0000, 0x0000, 0xC080], [0x0000, 0x9900, 0x0000, 0x0000, 0xC080, 0xC0E2], 2, &[]),             // ld b, $99
+    (&[0x7E],             [0x0000, 0x0000, 0x0000, 0xC010, 0xC080], [0x5A00, 0x0000, 0x0000, 0xC010, 0xC080, 0xC0E1], 2, &[]),             // ld a, [hl]
+    (&[0x70],             [0x0000, 0x4200, 0x0000, 0xC010, 0xC080], [0x0000, 0x4200, 0x0000, 0xC010, 0xC080, 0xC0E1], 2, &[(0xC010, 0x42)]), // ld [hl], b
+    (&[0x36, 0x77],       [0x0000, 0x0000, 0x0000, 0xC010, 0xC080], [0x0000, 0x0000, 0x0000, 0xC010, 0xC080, 0xC0E2], 3, &[(0xC010, 0x77)]), // ld [hl], $77
+    (&[0x01, 0x34, 0x12], [0x0000, 0x0000, 0x0000, 0x0000, 0xC080], [0x0000, 0x1234, 0x0000, 0x0000, 0xC080, 0xC0E3], 3, &[]),             // ld bc, $1234
+    (&[0x0A],             [0x0000, 0xC011, 0x0000, 0x0000, 0xC080], [0xA500, 0xC011, 0x0000, 0x0000, 0xC080, 0xC0E1], 2, &[]),             // ld a, [bc]
+    (&[0x12],             [0x9900, 0x0000, 0xC011, 0x0000, 0xC080], [0x9900, 0x0000, 0xC011, 0x0000, 0xC080, 0xC0E1], 2, &[(0xC011, 0x99)]), // ld [de], a
+    (&[0x22],             [0x9900, 0x0000, 0x0000, 0xC010, 0xC080], [0x9900, 0x0000, 0x0000, 0xC011, 0xC080, 0xC0E1], 2, &[(0xC010, 0x99)]), // ld [hl+], a
+    (&[0x3A],             [0x0000, 0x0000, 0x0000, 0xC010, 0xC080], [0x5A00, 0x0000, 0x0000, 0xC00F, 0xC080, 0xC0E1], 2, &[]),             // ld a, [hl-]
+    (&[0x08, 0x10, 0xC0], [0x0000, 0x0000, 0x0000, 0x0000, 0xC080], [0x0000, 0x0000, 0x0000, 0x0000, 0xC080, 0xC0E3], 5, &[(0xC010, 0x80), (0xC011, 0xC0)]), // ld [$C010], sp
+    (&[0xFA, 0x11, 0xC0], [0x0000, 0x0000, 0x0000, 0x0000, 0xC080], [0xA500, 0x0000, 0x0000, 0x0000, 0xC080, 0xC0E3], 4, &[]),             // ld a, [$C011]
+    (&[0xEA, 0x12, 0xC0], [0x4200, 0x0000, 0x0000, 0x0000, 0xC080], [0x4200, 0x0000, 0x0000, 0x0000, 0xC080, 0xC0E3], 4, &[(0xC012, 0x42)]), // ld [$C012], a
+    (&[0xE0, 0x81],       [0x4200, 0x0000, 0x0000, 0x0000, 0xC080], [0x4200, 0x0000, 0x0000, 0x0000, 0xC080, 0xC0E2], 3, &[(0xFF81, 0x42)]), // ldh [$FF81], a
+    (&[0xF0, 0x80],       [0x0000, 0x0000, 0x0000, 0x0000, 0xC080], [0x3C00, 0x0000, 0x0000, 0x0000, 0xC080, 0xC0E2], 3, &[]),             // ldh a, [$FF80]
+    (&[0xE2],             [0x4200, 0x0081, 0x0000, 0x0000, 0xC080], [0x4200, 0x0081, 0x0000, 0x0000, 0xC080, 0xC0E1], 2, &[(0xFF81, 0x42)]), // ld [c], a
+    (&[0xF2],             [0x0000, 0x0080, 0x0000, 0x0000, 0xC080], [0x3C00, 0x0080, 0x0000, 0x0000, 0xC080, 0xC0E1], 2, &[]),             // ld a, [c]
+    (&[0xF9],             [0x0000, 0x0000, 0x0000, 0xC010, 0xC080], [0x0000, 0x0000, 0x0000, 0xC010, 0xC010, 0xC0E1], 2, &[]),             // ld sp, hl
+
+    // 8 bit increments and decrements, C is left alone
+    (&[0x04],             [0x0010, 0xFF00, 0x0000, 0x0000, 0xC080], [0x00B0, 0x0000, 0x0000, 0x0000, 0xC080, 0xC0E1], 1, &[]),             // inc b
+    (&[0x05],             [0x0000, 0x1000, 0x0000, 0x0000, 0xC080], [0x0060, 0x0F00, 0x0000, 0x0000, 0xC080, 0xC0E1], 1, &[]),             // dec b
+    (&[0x3D],             [0x0110, 0x0000, 0x0000, 0x0000, 0xC080], [0x00D0, 0x0000, 0x0000, 0x0000, 0xC080, 0xC0E1], 1, &[]),             // dec a
+    (&[0x34],             [0x0000, 0x0000, 0x0000, 0xC010, 0xC080], [0x0000, 0x0000, 0x0000, 0xC010, 0xC080, 0xC0E1], 3, &[(0xC010, 0x5B)]), // inc [hl]
+    (&[0x35],             [0x0000, 0x0000, 0x0000, 0xC010, 0xC080], [0x0040, 0x0000, 0x0000, 0xC010, 0xC080, 0xC0E1], 3, &[(0xC010, 0x59)]), // dec [hl]
+
+    // 16 bit arithmetic
+    (&[0x03],             [0x0000, 0xFFFF, 0x0000, 0x0000, 0xC080], [0x0000, 0x0000, 0x0000, 0x0000, 0xC080, 0xC0E1], 2, &[]),             // inc bc
+    (&[0x0B],             [0x0000, 0x0000, 0x0000, 0x0000, 0xC080], [0x0000, 0xFFFF, 0x0000, 0x0000, 0xC080, 0xC0E1], 2, &[]),             // dec bc
+    (&[0x09],             [0x0080, 0x0001, 0x0000, 0x0FFF, 0xC080], [0x00A0, 0x0001, 0x0000, 0x1000, 0xC080, 0xC0E1], 2, &[]),             // add hl, bc
+    (&[0x29],             [0x0000, 0x0000, 0x0000, 0x8000, 0xC080], [0x0010, 0x0000, 0x0000, 0x0000, 0xC080, 0xC0E1], 2, &[]),             // add hl, hl
+    (&[0xE8, 0x02],       [0x00F0, 0x0000, 0x0000, 0x0000, 0xC080], [0x0000, 0x0000, 0x0000, 0x0000, 0xC082, 0xC0E2], 4, &[]),             // add sp, 2
+    (&[0xF8, 0xFE],       [0x0000, 0x0000, 0x0000, 0x0000, 0xC080], [0x0010, 0x0000, 0x0000, 0xC07E, 0xC080, 0xC0E2], 3, &[]),             // ld hl, sp-2
+
+    // 8 bit arithmetic and logic
+    (&[0x80],             [0x3A00, 0xC600, 0x0000, 0x0000, 0xC080], [0x00B0, 0xC600, 0x0000, 0x0000, 0xC080, 0xC0E1], 1, &[]),             // add a, b
+    (&[0x86],             [0x0F00, 0x0000, 0x0000, 0xC010, 0xC080], [0x6920, 0x0000, 0x0000, 0xC010, 0xC080, 0xC0E1], 2, &[]),             // add a, [hl]
+    (&[0xCE, 0x01],       [0xFE10, 0x0000, 0x0000, 0x0000, 0xC080], [0x00B0, 0x0000, 0x0000, 0x0000, 0xC080, 0xC0E2], 2, &[]),             // adc a, $01
+    (&[0x90],             [0x3E00, 0x3E00, 0x0000, 0x0000, 0xC080], [0x00C0, 0x3E00, 0x0000, 0x0000, 0xC080, 0xC0E1], 1, &[]),             // sub b
+    (&[0xD6, 0x01],       [0x0000, 0x0000, 0x0000, 0x0000, 0xC080], [0xFF70, 0x0000, 0x0000, 0x0000, 0xC080, 0xC0E2], 2, &[]),             // sub $01
+    (&[0x98],             [0x3B10, 0x2A00, 0x0000, 0x0000, 0xC080], [0x1040, 0x2A00, 0x0000, 0x0000, 0xC080, 0xC0E1], 1, &[]),             // sbc a, b
+    (&[0xA0],             [0x5A00, 0x3F00, 0x0000, 0x0000, 0xC080], [0x1A20, 0x3F00, 0x0000, 0x0000, 0xC080, 0xC0E1], 1, &[]),             // and b
+    (&[0xAF],             [0x5A50, 0x0000, 0x0000, 0x0000, 0xC080], [0x0080, 0x0000, 0x0000, 0x0000, 0xC080, 0xC0E1], 1, &[]),             // xor a
+    (&[0xB6],             [0x0070, 0x0000, 0x0000, 0xC010, 0xC080], [0x5A00, 0x0000, 0x0000, 0xC010, 0xC080, 0xC0E1], 2, &[]),             // or [hl]
+    (&[0xFE, 0x3C],       [0x3C00, 0x0000, 0x0000, 0x0000, 0xC080], [0x3CC0, 0x0000, 0x0000, 0x0000, 0xC080, 0xC0E2], 2, &[]),             // cp $3C
+    (&[0xFE, 0x41],       [0x3C00, 0x0000, 0x0000, 0x0000, 0xC080], [0x3C50, 0x0000, 0x0000, 0x0000, 0xC080, 0xC0E2], 2, &[]),             // cp $41
+
+    // Accumulator rotates and flag operations
+    (&[0x07],             [0x85F0, 0x0000, 0x0000, 0x0000, 0xC080], [0x0B10, 0x0000, 0x0000, 0x0000, 0xC080, 0xC0E1], 1, &[]),             // rlca
+    (&[0x1F],             [0x8100, 0x0000, 0x0000, 0x0000, 0xC080], [0x4010, 0x0000, 0x0000, 0x0000, 0xC080, 0xC0E1], 1, &[]),             // rra
+    (&[0x27],             [0x7D00, 0x0000, 0x0000, 0x0000, 0xC080], [0x8300, 0x0000, 0x0000, 0x0000, 0xC080, 0xC0E1], 1, &[]),             // daa after add
+    (&[0x27],             [0x4B60, 0x0000, 0x0000, 0x0000, 0xC080], [0x4540, 0x0000, 0x0000, 0x0000, 0xC080, 0xC0E1], 1, &[]),             // daa after sub
+    (&[0x2F],             [0x3590, 0x0000, 0x0000, 0x0000, 0xC080], [0xCAF0, 0x0000, 0x0000, 0x0000, 0xC080, 0xC0E1], 1, &[]),             // cpl
+    (&[0x37],             [0x00E0, 0x0000, 0x0000, 0x0000, 0xC080], [0x0090, 0x0000, 0x0000, 0x0000, 0xC080, 0xC0E1], 1, &[]),             // scf
+    (&[0x3F],             [0x0090, 0x0000, 0x0000, 0x0000, 0xC080], [0x0080, 0x0000, 0x0000, 0x0000, 0xC080, 0xC0E1], 1, &[]),             // ccf
+
+    // Jumps, calls and the stack
+    (&[0xC3, 0x00, 0xC0], [0x0000, 0x0000, 0x0000, 0x0000, 0xC080], [0x0000, 0x0000, 0x0000, 0x0000, 0xC080, 0xC000], 4, &[]),             // jp $C000
+    (&[0xC2, 0x00, 0xC0], [0x0080, 0x0000, 0x0000, 0x0000, 0xC080], [0x0080, 0x0000, 0x0000, 0x0000, 0xC080, 0xC0E3], 3, &[]),             // jp nz, $C000
+    (&[0xE9],             [0x0000, 0x0000, 0x0000, 0xC010, 0xC080], [0x0000, 0x0000, 0x0000, 0xC010, 0xC080, 0xC010], 1, &[]),             // jp hl
+    (&[0x18, 0x05],       [0x0000, 0x0000, 0x0000, 0x0000, 0xC080], [0x0000, 0x0000, 0x0000, 0x0000, 0xC080, 0xC0E7], 3, &[]),             // jr +5
+    (&[0x28, 0x05],       [0x0000, 0x0000, 0x0000, 0x0000, 0xC080], [0x0000, 0x0000, 0x0000, 0x0000, 0xC080, 0xC0E2], 2, &[]),             // jr z, +5
+    (&[0xCD, 0x00, 0xC0], [0x0000, 0x0000, 0x0000, 0x0000, 0xC080], [0x0000, 0x0000, 0x0000, 0x0000, 0xC07E, 0xC000], 6, &[(0xC07E, 0xE3), (0xC07F, 0xC0)]), // call $C000
+    (&[0xD4, 0x00, 0xC0], [0x0010, 0x0000, 0x0000, 0x0000, 0xC080], [0x0010, 0x0000, 0x0000, 0x0000, 0xC080, 0xC0E3], 3, &[]),             // call nc, $C000
+    (&[0xC9],             [0x0000, 0x0000, 0x0000, 0x0000, 0xC07E], [0x0000, 0x0000, 0x0000, 0x0000, 0xC080, 0x1234], 4, &[]),             // ret
+    (&[0xC5],             [0x0000, 0x1234, 0x0000, 0x0000, 0xC080], [0x0000, 0x1234, 0x0000, 0x0000, 0xC07E, 0xC0E1], 4, &[(0xC07E, 0x34), (0xC07F, 0x12)]), // push bc
+    (&[0xF1],             [0x0000, 0x0000, 0x0000, 0x0000, 0xC07E], [0x1230, 0x0000, 0x0000, 0x0000, 0xC080, 0xC0E1], 3, &[]),             // pop af, the low nibble of F is always 0
+    (&[0xFF],             [0x0000, 0x0000, 0x0000, 0x0000, 0xC080], [0x0000, 0x0000, 0x0000, 0x0000, 0xC07E, 0x0038], 4, &[(0xC07E, 0xE1), (0xC07F, 0xC0)]), // rst $38
+
+    // Prefixed rotates, shifts and bit operations
+    (&[0xCB, 0x00],       [0x0000, 0x8000, 0x0000, 0x0000, 0xC080], [0x0010, 0x0100, 0x0000, 0x0000, 0xC080, 0xC0E2], 2, &[]),             // rlc b
+    (&[0xCB, 0x2F],       [0x8100, 0x0000, 0x0000, 0x0000, 0xC080], [0xC010, 0x0000, 0x0000, 0x0000, 0xC080, 0xC0E2], 2, &[]),             // sra a
+    (&[0xCB, 0x37],       [0xF170, 0x0000, 0x0000, 0x0000, 0xC080], [0x1F00, 0x0000, 0x0000, 0x0000, 0xC080, 0xC0E2], 2, &[]),             // swap a
+    (&[0xCB, 0x3E],       [0x0000, 0x0000, 0x0000, 0xC010, 0xC080], [0x0000, 0x0000, 0x0000, 0xC010, 0xC080, 0xC0E2], 4, &[(0xC010, 0x2D)]), // srl [hl]
+    (&[0xCB, 0x7C],       [0x0010, 0x0000, 0x0000, 0x7F00, 0xC080], [0x00B0, 0x0000, 0x0000, 0x7F00, 0xC080, 0xC0E2], 2, &[]),             // bit 7, h
+    (&[0xCB, 0x46],       [0x0000, 0x0000, 0x0000, 0xC010, 0xC080], [0x00A0, 0x0000, 0x0000, 0xC010, 0xC080, 0xC0E2], 3, &[]),             // bit 0, [hl]
+    (&[0xCB, 0x87],       [0xFF00, 0x0000, 0x0000, 0x0000, 0xC080], [0xFE00, 0x0000, 0x0000, 0x0000, 0xC080, 0xC0E2], 2, &[]),             // res 0, a
+    (&[0xCB, 0xFE],       [0x0000, 0x0000, 0x0000, 0xC010, 0xC080], [0x0000, 0x0000, 0x0000, 0xC010, 0xC080, 0xC0E2], 4, &[(0xC010, 0xDA)])  // set 7, [hl]
+];
+
+#[test]
+fn instruction_vectors() {
+    for (code, before, after, cycles, stores) in VECTORS.iter() {
+        let mut mmu = MMU::new();
+        for (address, value) in FIXTURE.iter() {
+            mmu.write_byte(*address, *value);
+        }
+        for (offset, value) in code.iter().enumerate() {
+            mmu.write_byte(CODE_START + offset as u16, *value);
+        }
+
+        let mut cpu = CPU::new();
+        cpu.write_register_af(before[0]);
+        cpu.write_register_bc(before[1]);
+        cpu.write_register_de(before[2]);
+        cpu.write_register_hl(before[3]);
+        cpu.stack_pointer = before[4];
+        cpu.program_counter = CODE_START;
+        cpu.tick(&mut mmu).unwrap();
+
+        let registers = [cpu.read_register_af(), cpu.read_register_bc(), cpu.read_register_de(),
+                         cpu.read_register_hl(), cpu.stack_pointer, cpu.program_counter];
+        assert_eq!(registers, *after, "{:02X?}", code);
+        assert_eq!(cpu.get_clock_m(), *cycles, "{:02X?}", code);
+        assert_eq!(cpu.machine_cycles, *cycles, "{:02X?}", code);
+        for (address, value) in stores.iter() {
+            assert_eq!(mmu.read_byte(*address), *value, "{:02X?} at {:#06X}", code, address);
+        }
+    }
+}
+
+// Runs the instruction in code at CODE_START with SP at 0xC080
+fn run_instruction(code: &[u8], flags: u8) -> (CPU, MMU) {
+    let mut mmu = MMU::new();
+    for (offset, value) in code.iter().enumerate() {
+        mmu.write_byte(CODE_START + offset as u16, *value);
+    }
+
+    let mut cpu = CPU::new();
+    cpu.write_register_af(flags as u16);
+    cpu.stack_pointer = 0xC080;
+    cpu.program_counter = CODE_START;
+    cpu.tick(&mut mmu).unwrap();
+    return (cpu, mmu);
+}
+
+#[test]
+fn rst_pushes_the_return_address_below_sp() {
+    for vector in (0..0x40).step_by(8) {
+        let (cpu, mut mmu) = run_instruction(&[0xC7 | vector as u8], 0);
+        assert_eq!(cpu.program_counter, vector, "RST {:02X}", vector);
+        assert_eq!(cpu.stack_pointer, 0xC07E, "RST {:02X}", vector);
+        assert_eq!(mmu.read_byte(0xC07E), (CODE_START + 1) as u8, "RST {:02X}", vector);
+        assert_eq!(mmu.read_byte(0xC07F), ((CODE_START + 1) >> 8) as u8, "RST {:02X}", vector);
+        assert_eq!(mmu.read_byte(0xC080), 0, "RST {:02X}", vector);
+    }
+}
+
+#[test]
+fn ret_cc_cycles() {
+    // NZ, Z, NC, C and the flags that make each one taken
+    for (opcode, taken_flags) in [(0xC0, 0x00), (0xC8, 0x80), (0xD0, 0x00), (0xD8, 0x10)].iter() {
+        let (cpu, _) = run_instruction(&[*opcode], *taken_flags);
+        assert_eq!(cpu.get_clock_m(), 5, "{:02X} taken", opcode);
+        assert_eq!(cpu.machine_cycles, 5, "{:02X} taken", opcode);
+
+        let (cpu, _) = run_instruction(&[*opcode], *taken_flags ^ 0x90);
+        assert_eq!(cpu.get_clock_m(), 2, "{:02X} not taken", opcode);
+        assert_eq!(cpu.machine_cycles, 2, "{:02X} not taken", opcode);
+    }
+}
+
+#[test]
+fn jr_offsets() {
+    for (offset, target) in [(0x00, CODE_START + 2), (0x7E, CODE_START + 128), (0x7F, CODE_START + 129),
+                             (0xFF, CODE_START + 1), (0xFE, CODE_START), (0x80, CODE_START - 126)].iter() {
+        let (cpu, _) = run_instruction(&[0x18, *offset], 0);
+        assert_eq!(cpu.program_counter, *target, "JR {:02X}", offset);
+
+        let (cpu, _) = run_instruction(&[0x20, *offset], 0);
+        assert_eq!(cpu.program_counter, *target, "JR NZ,{:02X}", offset);
+    }
+}
+
+#[test]
+fn stack_pointer_plus_offset() {
+    // Offset, result, flags. H and C come from adding the offset to the low byte of SP, which is 0x80
+    for (offset, result, flags) in [(0x80, 0xC000, 0x10), (0x7F, 0xC0FF, 0x00), (0xFF, 0xC07F, 0x10), (0x01, 0xC081, 0x00)].iter() {
+        let (cpu, _) = run_instruction(&[0xE8, *offset], 0xF0);
+        assert_eq!(cpu.stack_pointer, *result, "ADD SP,{:02X}", offset);
+        assert_eq!(cpu.read_register_f(), *flags, "ADD SP,{:02X}", offset);
+
+        let (cpu, _) = run_instruction(&[0xF8, *offset], 0xF0);
+        assert_eq!(cpu.read_register_hl(), *result, "LD HL,SP+{:02X}", offset);
+        assert_eq!(cpu.stack_pointer, 0xC080, "LD HL,SP+{:02X}", offset);
+        assert_eq!(cpu.read_register_f(), *flags, "LD HL,SP+{:02X}", offset);
+    }
+}
@@ -0,0 +1,518 @@
+#![allow(clippy::needless_return)]
+
+use std::collections::HashSet;
+use std::fmt;
+
+use crate::cpu::{OPERATION_MACHINE_CYCLES, OPERATION_MACHINE_CYCLES_BRANCHED};
+use crate::mapper::ROM_BANK_SIZE;
+
+// Operand names indexed by the opcode's bit fields, see decode()
+pub(crate) const R8: [&str; 8]       = ["b", "c", "d", "e", "h", "l", "[hl]", "a"];
+pub(crate) const R16: [&str; 4]      = ["bc", "de", "hl", "sp"];
+pub(crate) const R16_STACK: [&str; 4] = ["bc", "de", "hl", "af"];
+pub(crate) const R16_MEMORY: [&str; 4] = ["[bc]", "[de]", "[hl+]", "[hl-]"];
+pub(crate) const CONDITIONS: [&str; 4] = ["nz", "z", "nc", "c"];
+pub(crate) const ALU: [&str; 8]      = ["add", "adc", "sub", "sbc", "and", "xor", "or", "cp"];
+pub(crate) const ROTATES: [&str; 8]  = ["rlc", "rrc", "rl", "rr", "sla", "sra", "swap", "srl"];
+pub(crate) const ACCUMULATOR_OPS: [&str; 8] = ["rlca", "rrca", "rla", "rra", "daa", "cpl", "scf", "ccf"];
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Operand {
+    Register(&'static str),  // a, hl, [hl], [hl+] etc.
+    Condition(&'static str),
+    Immediate8(u8),
+    Immediate16(u16),
+    SignedImmediate(i8),     // add sp, e8
+    StackOffset(i8),         // ld hl, sp+e8
+    Address(u16),            // [a16]
+    HighAddress(u8),         // ldh [a8], 0xFF00 + a8
+    Target(u16),             // Where a jump or call goes
+    Bit(u8)
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Operand::Register(name) | Operand::Condition(name) => write!(f, "{}", name),
+            Operand::Immediate8(value) => write!(f, "${:02X}", value),
+            Operand::Immediate16(value) => write!(f, "${:04X}", value),
+            Operand::SignedImmediate(value) => write!(f, "{}", value),
+            Operand::StackOffset(value) => write!(f, "sp{:+}", value),
+            Operand::Address(address) => write!(f, "[${:04X}]", address),
+            Operand::HighAddress(offset) => write!(f, "[$FF{:02X}]", offset),
+            Operand::Target(address) => write!(f, "${:04X}", address),
+            Operand::Bit(bit) => write!(f, "{}", bit)
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Instruction {
+    pub address: u16,
+    pub opcode: u8,
+    pub is_prefixed: bool,        // 0xCB opcode, opcode is the byte after the prefix
+    pub mnemonic: &'static str,   // "db" for bytes that aren't a valid opcode
+    pub operands: Vec<Operand>,
+    pub bytes: Vec<u8>,           // Raw bytes, prefix included
+    pub length: u16,
+    pub cycles: u8,               // Machine cycles, when a conditional branch isn't taken
+    pub cycles_branched: u8       // Machine cycles when it is
+}
+
+impl Instruction {
+    // Address of a jump, call or restart, if this is one
+    pub fn target(&self) -> Option<u16> {
+        for operand in self.operands.iter() {
+            if let Operand::Target(address) = operand {
+                return Some(*address);
+            }
+        }
+        return None;
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.mnemonic)?;
+        for (index, operand) in self.operands.iter().enumerate() {
+            write!(f, "{}{}", if index == 0 { " " } else { ", " }, operand)?;
+        }
+        Ok(())
+    }
+}
+
+// Decodes the instruction at the start of bytes, which was read from address. Bytes past the end of
+// the slice read as 0, callers check length if that matters
+pub fn decode(bytes: &[u8], address: u16) -> Instruction {
+    let mut instruction = decode_fields(bytes, address);
+    instruction.bytes = (0..instruction.length as usize).map(|index| bytes.get(index).cloned().unwrap_or(0)).collect();
+    return instruction;
+}
+
+fn decode_fields(bytes: &[u8], address: u16) -> Instruction {
+    let byte = |index: usize| bytes.get(index).cloned().unwrap_or(0);
+    let n8 = Operand::Immediate8(byte(1));
+    let n16 = Operand::Immediate16(byte(1) as u16 | (byte(2) as u16) << 8);
+    let a16 = Operand::Address(byte(1) as u16 | (byte(2) as u16) << 8);
+    let jump_target = Operand::Target(byte(1) as u16 | (byte(2) as u16) << 8);
+    let relative_target = Operand::Target(address.wrapping_add(2).wrapping_add(byte(1) as i8 as u16));
+
+    let opcode = byte(0);
+    if opcode == 0xCB {
+        return decode_prefixed(byte(1), address);
+    }
+
+    // Opcodes are laid out as xxyyyzzz, and yyy is often ppq
+    let x = opcode >> 6;
+    let y = ((opcode >> 3) & 0x7) as usize;
+    let z = opcode & 0x7;
+    let p = y >> 1;
+    let q = y & 1;
+
+    let mnemonic: &'static str;
+    let mut operands = Vec::new();
+    let mut length = 1;
+
+    match (x, z) {
+        (0, 0) => {
+            match y {
+                0 => mnemonic = "nop",
+                1 => {
+                    mnemonic = "ld";
+                    operands = vec![a16, Operand::Register("sp")];
+                    length = 3;
+                },
+                2 => {
+                    mnemonic = "stop";
+                    length = 2;
+                },
+                3 => {
+                    mnemonic = "jr";
+                    operands = vec![relative_target];
+                    length = 2;
+                },
+                _ => {
+                    mnemonic = "jr";
+                    operands = vec![Operand::Condition(CONDITIONS[y - 4]), relative_target];
+                    length = 2;
+                }
+            }
+        },
+        (0, 1) => {
+            if q == 0 {
+                mnemonic = "ld";
+                operands = vec![Operand::Register(R16[p]), n16];
+                length = 3;
+            } else {
+                mnemonic = "add";
+                operands = vec![Operand::Register("hl"), Operand::Register(R16[p])];
+            }
+        },
+        (0, 2) => {
+            mnemonic = "ld";
+            if q == 0 {
+                operands = vec![Operand::Register(R16_MEMORY[p]), Operand::Register("a")];
+            } else {
+                operands = vec![Operand::Register("a"), Operand::Register(R16_MEMORY[p])];
+            }
+        },
+        (0, 3) => {
+            mnemonic = if q == 0 { "inc" } else { "dec" };
+            operands = vec![Operand::Register(R16[p])];
+        },
+        (0, 4) | (0, 5) => {
+            mnemonic = if z == 4 { "inc" } else { "dec" };
+            operands = vec![Operand::Register(R8[y])];
+        },
+        (0, 6) => {
+            mnemonic = "ld";
+            operands = vec![Operand::Register(R8[y]), n8];
+            length = 2;
+        },
+        (0, _) => mnemonic = ACCUMULATOR_OPS[y],
+        (1, _) => {
+            if y == 6 && z == 6 {
+                mnemonic = "halt";
+            } else {
+                mnemonic = "ld";
+                operands = vec![Operand::Register(R8[y]), Operand::Register(R8[z as usize])];
+            }
+        },
+        (2, _) => {
+            mnemonic = ALU[y];
+            operands = alu_operands(y, Operand::Register(R8[z as usize]));
+        },
+        (3, 0) => {
+            match y {
+                0..=3 => {
+                    mnemonic = "ret";
+                    operands = vec![Operand::Condition(CONDITIONS[y])];
+                },
+                4 | 6 => {
+                    mnemonic = "ldh";
+                    let address = Operand::HighAddress(byte(1));
+                    operands = if y == 4 { vec![address, Operand::Register("a")] } else { vec![Operand::Register("a"), address] };
+                    length = 2;
+                },
+                5 => {
+                    mnemonic = "add";
+                    operands = vec![Operand::Register("sp"), Operand::SignedImmediate(byte(1) as i8)];
+                    length = 2;
+                },
+                _ => {
+                    mnemonic = "ld";
+                    operands = vec![Operand::Register("hl"), Operand::StackOffset(byte(1) as i8)];
+                    length = 2;
+                }
+            }
+        },
+        (3, 1) => {
+            if q == 0 {
+                mnemonic = "pop";
+                operands = vec![Operand::Register(R16_STACK[p])];
+            } else {
+                match p {
+                    0 => mnemonic = "ret",
+                    1 => mnemonic = "reti",
+                    2 => {
+                        mnemonic = "jp";
+                        operands = vec![Operand::Register("hl")];
+                    },
+                    _ => {
+                        mnemonic = "ld";
+                        operands = vec![Operand::Register("sp"), Operand::Register("hl")];
+                    }
+                }
+            }
+        },
+        (3, 2) => {
+            match y {
+                0..=3 => {
+                    mnemonic = "jp";
+                    operands = vec![Operand::Condition(CONDITIONS[y]), jump_target];
+                    length = 3;
+                },
+                4 => {
+                    mnemonic = "ldh";
+                    operands = vec![Operand::Register("[c]"), Operand::Register("a")];
+                },
+                5 => {
+                    mnemonic = "ld";
+                    operands = vec![a16, Operand::Register("a")];
+                    length = 3;
+                },
+                6 => {
+                    mnemonic = "ldh";
+                    operands = vec![Operand::Register("a"), Operand::Register("[c]")];
+                },
+                _ => {
+                    mnemonic = "ld";
+                    operands = vec![Operand::Register("a"), a16];
+                    length = 3;
+                }
+            }
+        },
+        (3, 3) => {
+            match y {
+                0 => {
+                    mnemonic = "jp";
+                    operands = vec![jump_target];
+                    length = 3;
+                },
+                6 => mnemonic = "di",
+                7 => mnemonic = "ei",
+                _ => return invalid(opcode, address)
+            }
+        },
+        (3, 4) => {
+            if y > 3 {
+                return invalid(opcode, address);
+            }
+            mnemonic = "call";
+            operands = vec![Operand::Condition(CONDITIONS[y]), jump_target];
+            length = 3;
+        },
+        (3, 5) => {
+            if q == 0 {
+                mnemonic = "push";
+                operands = vec![Operand::Register(R16_STACK[p])];
+            } else if p == 0 {
+                mnemonic = "call";
+                operands = vec![jump_target];
+                length = 3;
+            } else {
+                return invalid(opcode, address);
+            }
+        },
+        (3, 6) => {
+            mnemonic = ALU[y];
+            operands = alu_operands(y, n8);
+            length = 2;
+        },
+        _ => {
+            mnemonic = "rst";
+            operands = vec![Operand::Target((y * 8) as u16)];
+        }
+    }
+
+    return Instruction {
+        address,
+        opcode,
+        is_prefixed: false,
+        mnemonic,
+        operands,
+        bytes: Vec::new(),
+        length,
+        cycles: OPERATION_MACHINE_CYCLES[opcode as usize],
+        cycles_branched: OPERATION_MACHINE_CYCLES_BRANCHED[opcode as usize]
+    };
+}
+
+// 0xCB xxyyyzzz: x picks rotate/shift (with y as the operation), BIT, RES or SET (with y as the bit)
+fn decode_prefixed(opcode: u8, address: u16) -> Instruction {
+    let x = opcode >> 6;
+    let y = (opcode >> 3) & 0x7;
+    let z = (opcode & 0x7) as usize;
+    let register = Operand::Register(R8[z]);
+
+    let mnemonic;
+    let operands;
+    match x {
+        0 => {
+            mnemonic = ROTATES[y as usize];
+            operands = vec![register];
+        },
+        _ => {
+            mnemonic = ["", "bit", "res", "set"][x as usize];
+            operands = vec![Operand::Bit(y), register];
+        }
+    }
+
+    // Register operands take 2 cycles, (HL) needs the extra memory accesses
+    let cycles = match (x, z) {
+        (1, 6) => 3,
+        (_, 6) => 4,
+        _ => 2
+    };
+
+    return Instruction {
+        address,
+        opcode,
+        is_prefixed: true,
+        mnemonic,
+        operands,
+        bytes: Vec::new(),
+        length: 2,
+        cycles,
+        cycles_branched: cycles
+    };
+}
+
+fn alu_operands(operation: usize, operand: Operand) -> Vec<Operand> {
+    // add, adc and sbc spell out the accumulator, the others leave it implied
+    match operation {
+        0 | 1 | 3 => return vec![Operand::Register("a"), operand],
+        _ => return vec![operand]
+    }
+}
+
+fn invalid(opcode: u8, address: u16) -> Instruction {
+    return Instruction {
+        address,
+        opcode,
+        is_prefixed: false,
+        mnemonic: "db",
+        operands: vec![Operand::Immediate8(opcode)],
+        bytes: vec![opcode],
+        length: 1,
+        cycles: 0,
+        cycles_branched: 0
+    };
+}
+
+// Decodes count instructions of a ROM bank starting at from, an address in the bank's usual window:
+// 0x0000 - 0x3FFF for bank 0, 0x4000 - 0x7FFF for the rest. Stops early at the end of the bank
+pub fn disassemble_bank(rom: &[u8], bank: usize, from: u16, count: usize) -> Result<Vec<Instruction>, String> {
+    let bank_start = bank * ROM_BANK_SIZE;
+    if bank_start >= rom.len() {
+        return Err(format!("bank {} is past the end of the ROM, it has {} banks", bank, rom.len().div_ceil(ROM_BANK_SIZE)));
+    }
+
+    let window_start: u16 = if bank == 0 { 0x0000 } else { 0x4000 };
+    let window_end = window_start as usize + ROM_BANK_SIZE;
+    if (from as usize) < window_start as usize || from as usize >= window_end {
+        return Err(format!("address ${:04X} isn't in bank {}, which is mapped at ${:04X} - ${:04X}", from, bank, window_start, window_end - 1));
+    }
+
+    let bank_data = &rom[bank_start..(bank_start + ROM_BANK_SIZE).min(rom.len())];
+    let mut instructions = Vec::with_capacity(count);
+    let mut offset = (from - window_start) as usize;
+    while instructions.len() < count && offset < bank_data.len() {
+        let address = window_start + offset as u16;
+        let mut instruction = decode(&bank_data[offset..], address);
+        // Cut short by the end of the bank, show what's left as data
+        if offset + instruction.length as usize > bank_data.len() {
+            instruction = invalid(bank_data[offset], address);
+        }
+        offset += instruction.length as usize;
+        instructions.push(instruction);
+    }
+
+    return Ok(instructions);
+}
+
+// RGBDS source for a run of instructions. Jumps and calls into the listing get labels, the address and
+// raw bytes of each instruction are in a trailing comment
+pub fn format_listing(instructions: &[Instruction]) -> Vec<String> {
+    let starts: HashSet<u16> = instructions.iter().map(|instruction| instruction.address).collect();
+    let labels: HashSet<u16> = instructions.iter()
+        .filter_map(|instruction| instruction.target())
+        .filter(|target| starts.contains(target))
+        .collect();
+
+    let mut lines = Vec::new();
+    for instruction in instructions.iter() {
+        if labels.contains(&instruction.address) {
+            lines.push(format!("{}:", label(instruction.address)));
+        }
+
+        let mut text = String::from(instruction.mnemonic);
+        for (index, operand) in instruction.operands.iter().enumerate() {
+            text.push_str(if index == 0 { " " } else { ", " });
+            match operand {
+                Operand::Target(target) if labels.contains(target) => text.push_str(&label(*target)),
+                _ => text.push_str(&operand.to_string())
+            }
+        }
+
+        let bytes: Vec<String> = instruction.bytes.iter().map(|byte| format!("{:02X}", byte)).collect();
+        lines.push(format!("    {:<24}; ${:04X}: {}", text, instruction.address, bytes.join(" ")));
+    }
+
+    return lines;
+}
+
+fn label(address: u16) -> String {
+    return format!("L_{:04X}", address);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::cpu::OPERATION_BYTES;
+
+    #[test]
+    fn lengths_match_the_cpu() {
+        for opcode in 0..=255u8 {
+            let instruction = decode(&[opcode, 0, 0], 0);
+            match (opcode, OPERATION_BYTES[opcode as usize]) {
+                (0xCB, _) => assert_eq!(instruction.length, 2),
+                // The CPU has no length for opcodes that don't exist, they come out as a single byte of data
+                (_, 0) => {
+                    assert_eq!(instruction.mnemonic, "db", "{:02X}", opcode);
+                    assert_eq!(instruction.length, 1, "{:02X}", opcode);
+                },
+                (_, length) => assert_eq!(instruction.length, length, "{:02X} {}", opcode, instruction)
+            }
+        }
+    }
+
+    #[test]
+    fn prefixed_opcodes() {
+        for (opcode, text, cycles) in [(0x00, "rlc b", 2), (0x11, "rl c", 2), (0x37, "swap a", 2), (0x3E, "srl [hl]", 4),
+                                       (0x46, "bit 0, [hl]", 3), (0x7F, "bit 7, a", 2), (0x86, "res 0, [hl]", 4),
+                                       (0xBA, "res 7, d", 2), (0xC3, "set 0, e", 2), (0xFE, "set 7, [hl]", 4)].iter() {
+            let instruction = decode(&[0xCB, *opcode], 0);
+            assert_eq!(instruction.to_string(), *text, "CB {:02X}", opcode);
+            assert!(instruction.is_prefixed);
+            assert_eq!(instruction.opcode, *opcode);
+            assert_eq!(instruction.cycles, *cycles, "CB {:02X}", opcode);
+            assert_eq!(instruction.bytes, vec![0xCB, *opcode]);
+        }
+    }
+
+    #[test]
+    fn relative_jump_targets() {
+        for (offset, target) in [(0x00, 0x0202), (0x7F, 0x0281), (0x80, 0x0182), (0xFE, 0x0200)].iter() {
+            assert_eq!(decode(&[0x18, *offset], 0x0200).target(), Some(*target), "JR {:02X}", offset);
+            assert_eq!(decode(&[0x38, *offset], 0x0200).target(), Some(*target), "JR C,{:02X}", offset);
+        }
+        assert_eq!(decode(&[0x18, 0xFC], 0x0000).target(), Some(0xFFFE));
+    }
+
+    #[test]
+    fn instruction_cut_off_by_the_end_of_the_bank_is_data() {
+        let mut rom = vec![0; 2 * ROM_BANK_SIZE];
+        rom[2 * ROM_BANK_SIZE - 2] = 0xC3; // jp $xx00, one byte short
+        let instructions = disassemble_bank(&rom, 1, 0x7FFE, 4).unwrap();
+        assert_eq!(instructions.len(), 2);
+        assert_eq!(instructions[0].to_string(), "db $C3");
+        assert_eq!(instructions[0].bytes, vec![0xC3]);
+        assert_eq!(instructions[1].to_string(), "nop");
+        assert_eq!(instructions[1].address, 0x7FFF);
+    }
+
+    #[test]
+    fn only_targets_in_the_listing_get_labels() {
+        let mut rom = vec![0; 2 * ROM_BANK_SIZE];
+        rom[0x100..0x10B].copy_from_slice(&[
+            0x18, 0x01,       // jr $0103
+            0x00,             // nop
+            0xC3, 0x00, 0x01, // jp $0100
+            0xCD, 0x00, 0x40, // call $4000, outside the listing
+            0x20, 0xFB        // jr nz, $0106
+        ]);
+        let instructions = disassemble_bank(&rom, 0, 0x0100, 5).unwrap();
+        assert_eq!(format_listing(&instructions), vec![
+            "L_0100:",
+            "    jr L_0103               ; $0100: 18 01",
+            "    nop                     ; $0102: 00",
+            "L_0103:",
+            "    jp L_0100               ; $0103: C3 00 01",
+            "L_0106:",
+            "    call $4000              ; $0106: CD 00 40",
+            "    jr nz, L_0106           ; $0109: 20 FB"
+        ]);
+    }
+}
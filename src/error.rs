@@ -0,0 +1,65 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+#[derive(Debug)]
+pub enum EmuError {
+    UnknownOpcode { opcode: u8, program_counter: u16 },
+    UnsupportedCartridgeType(u8),
+    BadRomSize(u8),
+    BadRamSize(u8),
+    BadBiosSize(usize),
+    TruncatedRom { expected: usize, actual: usize },
+    BadSaveState(String),
+    BadArchive(String),
+    Io(io::Error)
+}
+
+impl fmt::Display for EmuError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EmuError::UnknownOpcode { opcode, program_counter } => {
+                write!(f, "Unknown OpCode {:#04X} at {:#06X}", opcode, program_counter)
+            },
+            EmuError::UnsupportedCartridgeType(cartridge_type) => {
+                write!(f, "Unsupported cartridge type {:#04X}", cartridge_type)
+            },
+            EmuError::BadRomSize(rom_size) => {
+                write!(f, "Unknown ROM size {:#04X} in cartridge header", rom_size)
+            },
+            EmuError::BadRamSize(ram_size) => {
+                write!(f, "Unknown RAM size {:#04X} in cartridge header", ram_size)
+            },
+            EmuError::BadBiosSize(size) => {
+                write!(f, "BIOS is {} bytes, it can be at most 256", size)
+            },
+            EmuError::TruncatedRom { expected, actual } => {
+                write!(f, "ROM is truncated, expected {} bytes but got {}", expected, actual)
+            },
+            EmuError::BadSaveState(reason) => {
+                write!(f, "Invalid save state: {}", reason)
+            },
+            EmuError::BadArchive(reason) => {
+                write!(f, "Could not read ROM archive: {}", reason)
+            },
+            EmuError::Io(error) => {
+                write!(f, "I/O error: {}", error)
+            }
+        }
+    }
+}
+
+impl Error for EmuError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            EmuError::Io(error) => Some(error),
+            _ => None
+        }
+    }
+}
+
+impl From<io::Error> for EmuError {
+    fn from(error: io::Error) -> Self {
+        EmuError::Io(error)
+    }
+}
@@ -0,0 +1,48 @@
+#![allow(clippy::needless_return)]
+
+// Interfaces between the emulator core and whatever is presenting it (SDL window, headless runner, recorder...).
+// The GameBoy calls into these at fixed points, see GameBoy::step.
+
+// Receives every finished frame, called once per VBlank while the LCD is on
+pub trait VideoSink {
+    // SCREEN_WIDTH * SCREEN_HEIGHT palette resolved shades (0-3), row by row
+    fn present_frame(&mut self, frame_buffer: &[u8]);
+}
+
+// Receives audio in batches, called once per frame
+pub trait AudioSink {
+    fn sample_rate(&self) -> u32;
+
+    // Interleaved stereo samples (left, right, left, right...)
+    fn queue_samples(&mut self, samples: &[i16]);
+}
+
+// Supplies the joypad state, polled once per frame at VBlank
+pub trait InputSource {
+    // Bitmask of the currently pressed buttons, see the BUTTON_* constants in input.rs
+    fn poll(&mut self) -> u8;
+}
+
+pub struct NullVideoSink;
+
+impl VideoSink for NullVideoSink {
+    fn present_frame(&mut self, _frame_buffer: &[u8]) {}
+}
+
+pub struct NullAudioSink;
+
+impl AudioSink for NullAudioSink {
+    fn sample_rate(&self) -> u32 {
+        return 44100;
+    }
+
+    fn queue_samples(&mut self, _samples: &[i16]) {}
+}
+
+pub struct NullInputSource;
+
+impl InputSource for NullInputSource {
+    fn poll(&mut self) -> u8 {
+        return 0;
+    }
+}
@@ -1,23 +1,61 @@
-use std::process::exit;
+#![allow(clippy::needless_return)]
 
-use sdl2::event::Event;
-use sdl2::keyboard::Keycode;
-
-use crate::cartridge::Cartridge;
-use crate::cpu::{CPU, JOYPAD_INTERRUPT_BIT, LCD_INTERRUPT_BIT, SERIAL_INTERRUPT_BIT, TIMER_INTERRUPT_BIT, VBLANK_INTERRUPT_BIT};
+use crate::cpu::CPU;
+use crate::error::EmuError;
+use crate::frontend::{AudioSink, InputSource, NullAudioSink, NullInputSource, NullVideoSink, VideoSink};
 use crate::mmu::MMU;
+use crate::savestate::{self, StateReader, StateWriter};
+use crate::tracer::Tracer;
+
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+pub const CLOCK_SPEED: u32      = 4194304; // T-cycles per second
+pub const CYCLES_PER_FRAME: u32 = 70224;   // T-cycles per frame (154 lines * 456)
 
-const HALT_INSTRUCTION: u8 = 0x76;
+// What happened during a call to one of the stepping functions
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct StepSummary {
+    pub cycles: u32,          // T-cycles consumed, at the normal speed clock
+    pub entered_vblank: bool  // The GPU entered VBlank, a full frame is in the frame buffer
+}
 
 pub struct GameBoy {
     pub cpu: CPU,
     pub mmu: MMU,
-    pub cartridge: Cartridge,
-    pub is_paused: bool
+    pub is_paused: bool,
+    pub video_sink: Box<dyn VideoSink>,
+    pub audio_sink: Box<dyn AudioSink>,
+    pub input_source: Box<dyn InputSource>,
+    pub save_path: Option<PathBuf>, // Where battery backed cartridge RAM is kept, None if the cartridge has no battery
+    pub rumble_callback: Option<Box<dyn FnMut(bool)>>, // Called when an MBC5 rumble cartridge turns its motor on or off
+    pub tracer: Option<Tracer>, // Logs every instruction executed
+    was_rumbling: bool,
+    stopped_cycles: u32, // Cycles spent in STOP since a frame was last presented
+    audio_buffer: Vec<i16>
 }
 
 impl GameBoy {
-    pub fn run(&mut self) {
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Self {
+        GameBoy {
+            cpu: CPU::new(),
+            mmu: MMU::new(),
+            is_paused: false,
+            video_sink: Box::new(NullVideoSink),
+            audio_sink: Box::new(NullAudioSink),
+            input_source: Box::new(NullInputSource),
+            save_path: None,
+            rumble_callback: None,
+            tracer: None,
+            was_rumbling: false,
+            stopped_cycles: 0,
+            audio_buffer: Vec::new()
+        }
+    }
+
+    pub fn start(&mut self) {
         debug!("GameBoy running");
 
         if !self.cpu.skip_bios {
@@ -26,199 +64,218 @@ impl GameBoy {
             self.emulate_bios_setup();
             self.cpu.program_counter = 0x100;
         }
+    }
+
+    // Runs instructions until at least the given number of T-cycles have passed
+    pub fn run_cycles(&mut self, cycles: u32) -> Result<StepSummary, EmuError> {
+        let mut summary = StepSummary::default();
+
+        while summary.cycles < cycles {
+            let step = self.step_instruction()?;
+            summary.cycles += step.cycles;
+            summary.entered_vblank |= step.entered_vblank;
+        }
 
-        loop {
-            if !self.is_paused {
-                //TODO - Move this...
-                for event in self.mmu.gpu.event_pump.poll_iter() {
-                    match event {
-                        Event::Quit    {..} => exit(0),
-                        Event::KeyDown { keycode: Some(Keycode::Escape), ..} => exit(0),
-
-                        Event::KeyDown { keycode: Some(Keycode::Right), ..} => {  self.mmu.gpu.input.keys[1] &= 0xE},
-                        Event::KeyDown { keycode: Some(Keycode::Left), ..} => {   self.mmu.gpu.input.keys[1] &= 0xD},
-                        Event::KeyDown { keycode: Some(Keycode::Up), ..} => {     self.mmu.gpu.input.keys[1] &= 0xB},
-                        Event::KeyDown { keycode: Some(Keycode::Down), ..} => {   self.mmu.gpu.input.keys[1] &= 0x7},
-                        Event::KeyDown { keycode: Some(Keycode::Z), ..} => {      self.mmu.gpu.input.keys[0] &= 0xE},
-                        Event::KeyDown { keycode: Some(Keycode::X), ..} => {      self.mmu.gpu.input.keys[0] &= 0xD},
-                        Event::KeyDown { keycode: Some(Keycode::Space), ..} => {  self.mmu.gpu.input.keys[0] &= 0xB},
-                        Event::KeyDown { keycode: Some(Keycode::KpEnter), ..} => {self.mmu.gpu.input.keys[0] &= 0x7},
-
-                        Event::KeyUp   { keycode: Some(Keycode::Right), ..} => {  self.mmu.gpu.input.keys[1] |= 0x1},
-                        Event::KeyUp   { keycode: Some(Keycode::Left), ..} => {   self.mmu.gpu.input.keys[1] |= 0x2},
-                        Event::KeyUp   { keycode: Some(Keycode::Up), ..} => {     self.mmu.gpu.input.keys[1] |= 0x4},
-                        Event::KeyUp   { keycode: Some(Keycode::Down), ..} => {   self.mmu.gpu.input.keys[1] |= 0x8},
-                        Event::KeyUp   { keycode: Some(Keycode::Z), ..} => {      self.mmu.gpu.input.keys[0] |= 0x1},
-                        Event::KeyUp   { keycode: Some(Keycode::X), ..} => {      self.mmu.gpu.input.keys[0] |= 0x2},
-                        Event::KeyUp   { keycode: Some(Keycode::Space), ..} => {  self.mmu.gpu.input.keys[0] |= 0x4},
-                        Event::KeyUp   { keycode: Some(Keycode::KpEnter), ..} => {self.mmu.gpu.input.keys[0] |= 0x8},
-                        _ => {}
-                    }
-                }
-
-                // Execute CPU Cycle
-                let opcode = self.cpu.tick(&mut self.mmu);
-
-                // Timer Tick
-                self.mmu.timer.step(self.cpu.get_clock_t());
-
-                // Update display
-                let entered_vblank = self.mmu.gpu.tick(self.cpu.get_clock_t());
-                if entered_vblank {
-                    //TODO - trace
-                    trace!("Requesting VBlank Interrupt");
-                    let int_flags = self.mmu.read_byte(0xFF0F);
-                    self.mmu.write_byte(0xFF0F, int_flags | 0x1);
-                }
-
-                // Handle Interrupts
-                if opcode == HALT_INSTRUCTION || self.cpu.interrupt_master_enable {
-                    // Check if any interrupts are enabled, check if any interrupts have been fired (0xFF0F)
-                    let interrupt_enable_register = self.mmu.interrupt_enable_register;
-                    let interrupt_flags_register = self.mmu.read_byte(0xFF0F);
-
-                    if interrupt_enable_register > 0 && interrupt_flags_register > 0 {
-                        let mut interrupt_handled_this_tick = false;
-
-                        // Vertical Blank Interrupt
-                        if interrupt_enable_register & VBLANK_INTERRUPT_BIT == 1 && interrupt_flags_register & VBLANK_INTERRUPT_BIT == 1 {
-                            //TODO - trace
-                            debug!("Handling VBlank Interrupt");
-
-                            interrupt_handled_this_tick = true;
-
-                            if opcode == HALT_INSTRUCTION {
-                                self.cpu.program_counter += 1;
-                            }
-
-                            if self.cpu.interrupt_master_enable {
-                                self.cpu.interrupt_master_enable = false;
-                                self.mmu.write_byte(0xFF0F, interrupt_flags_register & (255 - VBLANK_INTERRUPT_BIT));
-                                self.cpu.stack_pointer -= 2;
-                                self.mmu.write_word(self.cpu.stack_pointer, self.cpu.program_counter);
-                                self.cpu.program_counter = 0x40;
-                            }
-                        }
-
-                        // LCD Interrupt
-                        if !interrupt_handled_this_tick &&
-                        interrupt_enable_register & LCD_INTERRUPT_BIT == 2 && interrupt_flags_register & LCD_INTERRUPT_BIT == 2 {
-                            debug!("Handling LCD Interrupt");
-
-                            interrupt_handled_this_tick = true;
-
-                            if opcode == HALT_INSTRUCTION {
-                                self.cpu.program_counter += 1;
-                            }
-
-                            if self.cpu.interrupt_master_enable {
-                                self.cpu.interrupt_master_enable = false;
-                                self.mmu.write_byte(0xFF0F, interrupt_flags_register & (255 - LCD_INTERRUPT_BIT));
-                                self.cpu.stack_pointer -= 2;
-                                self.mmu.write_word(self.cpu.stack_pointer, self.cpu.program_counter);
-                                self.cpu.program_counter = 0x48;
-                            }
-                        }
-
-                        // Timer Interrupt
-                        if !interrupt_handled_this_tick &&
-                        interrupt_enable_register & TIMER_INTERRUPT_BIT == 4 && interrupt_flags_register & TIMER_INTERRUPT_BIT == 4 {
-                            debug!("Handling Timer Interrupt");
-
-                            interrupt_handled_this_tick = true;
-
-                            if opcode == HALT_INSTRUCTION {
-                                self.cpu.program_counter += 1;
-                            }
-
-                            if self.cpu.interrupt_master_enable {
-                                self.cpu.interrupt_master_enable = false;
-                                self.mmu.write_byte(0xFF0F, interrupt_flags_register & (255 - TIMER_INTERRUPT_BIT));
-                                // TODO - is overflowing ok?
-                                // self.cpu.stack_pointer = self.cpu.stack_pointer.wrapping_sub(2);
-                                self.cpu.stack_pointer -= 2;
-                                self.mmu.write_word(self.cpu.stack_pointer, self.cpu.program_counter);
-                                self.cpu.program_counter = 0x50;
-                            }
-                        }
-
-                        // Serial Interrupt
-                        if !interrupt_handled_this_tick &&
-                        interrupt_enable_register & SERIAL_INTERRUPT_BIT == 8 && interrupt_flags_register & SERIAL_INTERRUPT_BIT == 8 {
-                            debug!("Handling Serial Interrupt");
-
-                            interrupt_handled_this_tick = true;
-
-                            if opcode == HALT_INSTRUCTION {
-                                self.cpu.program_counter += 1;
-                            }
-
-                            if self.cpu.interrupt_master_enable {
-                                self.cpu.interrupt_master_enable = false;
-                                self.mmu.write_byte(0xFF0F, interrupt_flags_register & (255 - SERIAL_INTERRUPT_BIT));
-                                self.cpu.stack_pointer -= 2;
-                                self.mmu.write_word(self.cpu.stack_pointer, self.cpu.program_counter);
-                                self.cpu.program_counter = 0x58;
-                            }
-                        }
-
-                        // Joypad Interrupt
-                        if !interrupt_handled_this_tick &&
-                        interrupt_enable_register & JOYPAD_INTERRUPT_BIT == 16 && interrupt_flags_register & JOYPAD_INTERRUPT_BIT == 16 {
-                            debug!("Handling Joypad Interrupt");
-
-                            if opcode == HALT_INSTRUCTION {
-                                self.cpu.program_counter += 1;
-                            }
-
-                            if self.cpu.interrupt_master_enable {
-                                self.cpu.interrupt_master_enable = false;
-                                self.mmu.write_byte(0xFF0F, interrupt_flags_register & (255 - JOYPAD_INTERRUPT_BIT));
-                                self.cpu.stack_pointer -= 2;
-                                self.mmu.write_word(self.cpu.stack_pointer, self.cpu.program_counter);
-                                self.cpu.program_counter = 0x60;
-                            }
-                        }
-                    }
-                }
+        return Ok(summary);
+    }
+
+    // Runs instructions until the GPU moves on to the next line
+    pub fn run_scanline(&mut self) -> Result<StepSummary, EmuError> {
+        let mut summary = StepSummary::default();
+        let line = self.mmu.gpu.read_register(0xFF44);
+
+        while self.mmu.gpu.read_register(0xFF44) == line {
+            let step = self.step_instruction()?;
+            summary.cycles += step.cycles;
+            summary.entered_vblank |= step.entered_vblank;
+        }
+
+        return Ok(summary);
+    }
+
+    // Runs instructions until the GPU enters VBlank. Gives up after a frame's worth of cycles,
+    // so this still returns if the game never reaches VBlank
+    pub fn run_frame(&mut self) -> Result<StepSummary, EmuError> {
+        let mut summary = StepSummary::default();
+
+        while !summary.entered_vblank && summary.cycles < CYCLES_PER_FRAME {
+            let step = self.step_instruction()?;
+            summary.cycles += step.cycles;
+            summary.entered_vblank |= step.entered_vblank;
+        }
+
+        return Ok(summary);
+    }
+
+    // Runs a single instruction along with the timer, GPU and interrupt handling for the cycles it took
+    pub fn step_instruction(&mut self) -> Result<StepSummary, EmuError> {
+        if let Some(tracer) = self.tracer.as_mut() {
+            if self.cpu.will_execute_instruction(&self.mmu) {
+                tracer.log(&self.cpu, &mut self.mmu)?;
             }
+        }
+
+        // Execute CPU Cycle. The CPU runs the timer, serial port and GPU alongside each machine cycle
+        self.cpu.tick(&mut self.mmu)?;
+
+        let mut cycles = self.cpu.get_clock_t();
+
+        let entered_vblank = self.mmu.has_entered_vblank;
+        self.mmu.has_entered_vblank = false;
+
+        let is_rumbling = self.mmu.cartridge.mapper.is_rumbling();
+        if is_rumbling != self.was_rumbling {
+            self.was_rumbling = is_rumbling;
+            if let Some(rumble_callback) = self.rumble_callback.as_mut() {
+                rumble_callback(self.was_rumbling);
+            }
+        }
+
+        // The LCD, timer and everything else are stopped along with the CPU. Keep presenting the blank
+        // screen and polling the joypad at the normal frame rate so a button press can wake the system
+        if self.cpu.is_stopped {
+            self.stopped_cycles += cycles as u32;
+            let entered_vblank = self.stopped_cycles >= CYCLES_PER_FRAME;
+            if entered_vblank {
+                self.stopped_cycles -= CYCLES_PER_FRAME;
+                self.end_frame();
+            }
+            return Ok(StepSummary {
+                cycles: cycles as u32,
+                entered_vblank
+            });
+        }
 
-            // TODO - proper processing speed
-            // microseconds not millis
-            // thread::sleep(time::Duration::from_millis(10));
+        // In double speed mode the CPU, timer and serial port see twice as many cycles as the LCD
+        if self.mmu.is_double_speed {
+            cycles /= 2;
         }
+
+        if entered_vblank {
+            self.end_frame();
+        }
+
+        return Ok(StepSummary {
+            cycles: cycles as u32,
+            entered_vblank
+        });
+    }
+
+    // Hands the finished frame and audio to the sinks and polls the joypad for the next frame
+    fn end_frame(&mut self) {
+        if self.mmu.gpu.get_display_status() == 1 {
+            self.video_sink.present_frame(self.mmu.gpu.frame_buffer());
+        }
+
+        // TODO - APU. Until there is one, queue a frame's worth of silence so audio sinks stay in step with video
+        let sample_count = (self.audio_sink.sample_rate() as u64 * CYCLES_PER_FRAME as u64 / CLOCK_SPEED as u64) as usize;
+        self.audio_buffer.clear();
+        self.audio_buffer.resize(sample_count * 2, 0);
+        self.audio_sink.queue_samples(&self.audio_buffer);
+
+        let pressed = self.input_source.poll();
+        self.mmu.gpu.input.set_pressed(pressed, &mut self.mmu.interrupts);
+
+        self.mmu.cartridge.mapper.tick_rtc();
     }
 
-    pub fn load_rom(&mut self, rom_path: &str) {
+    pub fn load_rom(&mut self, rom_path: &str, rom_entry: Option<&str>) -> Result<(), EmuError> {
         // Load ROM from disk
-        let result = self.mmu.load_rom(rom_path);
-        if result.is_err() {
-            error!("Failed to load ROM");
-            error!("Error: {:?}", result.err());
-            exit(1);
+        self.mmu.load_rom(rom_path, rom_entry)?;
+
+        self.mmu.cartridge.print_cartridge();
+
+        // Battery backed RAM lives next to the ROM (or the archive it came from) as <rom>.sav
+        self.save_path = None;
+        if self.mmu.cartridge.has_battery() && !self.mmu.cartridge.mapper.save_data().is_empty() {
+            let save_path = Path::new(rom_path).with_extension("sav");
+            match fs::read(&save_path) {
+                Ok(data) => {
+                    debug!("Loaded save data from {}", save_path.display());
+                    self.mmu.cartridge.mapper.load_save_data(&data);
+                },
+                Err(ref error) if error.kind() == io::ErrorKind::NotFound => {
+                    debug!("No save data at {}, starting with empty cartridge RAM", save_path.display());
+                },
+                Err(error) => return Err(EmuError::Io(error))
+            }
+            self.save_path = Some(save_path);
         }
 
-        // Setup Cartridge
-        debug!("Setting up Cartridge Data");
-        //TODO
-//        let mut title: Vec<u8> = vec![0; 16];
-//        for i in 0..16 {
-//            // Read Title
-//            title[i] = self.mmu.read_byte((0x134 + i) as u16);
-//        }
-//        self.cartridge.title = String::from_utf8(title).unwrap();
-        self.cartridge.gameboy_type = self.mmu.read_byte(0x143);
-        self.cartridge.is_super_gameboy = self.mmu.read_byte(0x146);
-        self.cartridge.cartridge_type = self.mmu.read_byte(0x147);
-        self.cartridge.rom_size = self.mmu.read_byte(0x148);
-        self.cartridge.ram_size = self.mmu.read_byte(0x149);
-        self.cartridge.region = self.mmu.read_byte(0x14A);
-        self.cartridge.licensee = self.mmu.read_byte(0x14B);
-        self.cartridge.version = self.mmu.read_byte(0x14C);
-        self.cartridge.checksum = self.mmu.read_word(0x14E);
-        self.cartridge.print_cartridge();
-
-        self.mmu.set_cartridge_type(self.cartridge.cartridge_type);
+        Ok(())
+    }
+
+    // Writes battery backed RAM to the save file if it has changed since the last flush. The data is
+    // written to a temporary file first and renamed over the old save, so a crash can't leave it half written
+    pub fn flush_save(&mut self) -> Result<(), EmuError> {
+        let save_path = match self.save_path.as_ref() {
+            Some(save_path) => save_path,
+            None => return Ok(())
+        };
+
+        if !self.mmu.is_external_ram_dirty {
+            return Ok(());
+        }
+
+        let temp_path = save_path.with_extension("sav.tmp");
+        let mut file = File::create(&temp_path)?;
+        file.write_all(&self.mmu.cartridge.mapper.save_data())?;
+        file.sync_all()?;
+        fs::rename(&temp_path, save_path)?;
+
+        self.mmu.is_external_ram_dirty = false;
+        debug!("Flushed save data to {}", save_path.display());
+        Ok(())
+    }
+
+    // Writes a snapshot of the whole machine. The ROM itself isn't included, the state can only be
+    // loaded back with the same game
+    pub fn save_state<W: Write>(&self, writer: &mut W) -> Result<(), EmuError> {
+        let mut state = StateWriter::new(writer);
+        state.write_bytes(&savestate::MAGIC)?;
+        state.write_u8(savestate::VERSION)?;
+        state.write_u16(self.mmu.cartridge.checksum)?;
+
+        self.cpu.save_state(&mut state)?;
+        self.mmu.save_state(&mut state)?;
+        Ok(())
+    }
+
+    // Restores a snapshot written by save_state. If the snapshot turns out to be bad part way
+    // through, the machine is put back the way it was before returning the error
+    pub fn load_state<R: Read>(&mut self, reader: &mut R) -> Result<(), EmuError> {
+        let mut state = StateReader::new(reader);
+
+        let mut magic = [0; 4];
+        state.read_bytes(&mut magic).map_err(save_state_error)?;
+        if magic != savestate::MAGIC {
+            return Err(EmuError::BadSaveState(String::from("not a RustBoy save state")));
+        }
+
+        let version = state.read_u8().map_err(save_state_error)?;
+        if version != savestate::VERSION {
+            return Err(EmuError::BadSaveState(format!("unsupported version {}, expected {}", version, savestate::VERSION)));
+        }
+
+        let checksum = state.read_u16().map_err(save_state_error)?;
+        if checksum != self.mmu.cartridge.checksum {
+            return Err(EmuError::BadSaveState(format!("made with a different ROM (checksum {:#06X}, loaded ROM is {:#06X})", checksum, self.mmu.cartridge.checksum)));
+        }
+
+        let mut backup = Vec::new();
+        self.cpu.save_state(&mut StateWriter::new(&mut backup))?;
+        self.mmu.save_state(&mut StateWriter::new(&mut backup))?;
+
+        let result = self.cpu.load_state(&mut state).and_then(|_| self.mmu.load_state(&mut state));
+        if let Err(error) = result {
+            let mut backup_reader = &backup[..];
+            let mut backup_state = StateReader::new(&mut backup_reader);
+            self.cpu.load_state(&mut backup_state)?;
+            self.mmu.load_state(&mut backup_state)?;
+            return Err(save_state_error(error));
+        }
+
+        Ok(())
     }
 
     pub fn skip_bios(&mut self, skip: bool) {
@@ -269,4 +326,113 @@ impl GameBoy {
         self.mmu.write_byte(0xFF4B, 0x00);
         self.mmu.write_byte(0xFFFF, 0x00);
     }
-}
\ No newline at end of file
+}
+
+fn save_state_error(error: io::Error) -> EmuError {
+    if error.kind() == io::ErrorKind::UnexpectedEof {
+        return EmuError::BadSaveState(String::from("file is truncated"));
+    }
+    if error.kind() == io::ErrorKind::InvalidData {
+        return EmuError::BadSaveState(error.to_string());
+    }
+    return EmuError::Io(error);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::interrupts::TIMER_INTERRUPT_BIT;
+    use crate::mapper::RomOnly;
+
+    // Offset of the mapper state length, just after the GPU, timer, serial and interrupt state
+    fn mapper_state_offset(gameboy: &GameBoy) -> usize {
+        let mut buffer = Vec::new();
+        let mut state = StateWriter::new(&mut buffer);
+        state.write_bytes(&savestate::MAGIC).unwrap();
+        state.write_u8(savestate::VERSION).unwrap();
+        state.write_u16(gameboy.mmu.cartridge.checksum).unwrap();
+        gameboy.cpu.save_state(&mut state).unwrap();
+        gameboy.mmu.gpu.save_state(&mut state).unwrap();
+        gameboy.mmu.timer.save_state(&mut state).unwrap();
+        gameboy.mmu.serial.save_state(&mut state).unwrap();
+        gameboy.mmu.interrupts.save_state(&mut state).unwrap();
+        return buffer.len();
+    }
+
+    fn assert_bad_save_state(result: Result<(), EmuError>) {
+        match result {
+            Err(EmuError::BadSaveState(_)) => {},
+            other => panic!("expected BadSaveState, got {:?}", other)
+        }
+    }
+
+    #[test]
+    fn timer_interrupt_cycles_after_a_tima_write() {
+        let mut rom = vec![0; 0x8000];
+        rom[0x100..0x108].copy_from_slice(&[
+            0x3E, 0x05, // LD A,$05
+            0xE0, 0x07, // LDH ($07),A - TIMA every 16 T-cycles
+            0x3E, 0xFF, // LD A,$FF
+            0xE0, 0x05  // LDH ($05),A - TIMA overflows on its next increment
+        ]);
+        let mut gameboy = GameBoy::new();
+        gameboy.mmu.cartridge.mapper = Box::new(RomOnly::new(rom));
+        gameboy.skip_bios(true);
+        gameboy.start();
+
+        for _ in 0..4 {
+            gameboy.step_instruction().unwrap();
+        }
+        assert_eq!(gameboy.mmu.timer.tima, 0xFF);
+
+        // The TIMA write lands 4 T-cycles into a period. 12 more to the overflow, then TMA is
+        // loaded and the interrupt requested on the next M-cycle. The rest of the ROM is NOPs
+        let mut cycles = 0;
+        while gameboy.mmu.interrupts.flags & TIMER_INTERRUPT_BIT == 0 {
+            cycles += gameboy.step_instruction().unwrap().cycles;
+            assert!(cycles < 1024, "the timer interrupt was never requested");
+        }
+        assert_eq!(cycles, 16);
+    }
+
+    #[test]
+    fn load_state_round_trips() {
+        let mut gameboy = GameBoy::new();
+        gameboy.cpu.program_counter = 0x1234;
+        let mut saved = Vec::new();
+        gameboy.save_state(&mut saved).unwrap();
+
+        gameboy.cpu.program_counter = 0;
+        gameboy.load_state(&mut &saved[..]).unwrap();
+        assert_eq!(gameboy.cpu.program_counter, 0x1234);
+    }
+
+    #[test]
+    fn load_state_rejects_a_huge_mapper_state() {
+        let mut gameboy = GameBoy::new();
+        let mut saved = Vec::new();
+        gameboy.save_state(&mut saved).unwrap();
+
+        let offset = mapper_state_offset(&gameboy);
+        saved[offset..offset + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert_bad_save_state(gameboy.load_state(&mut &saved[..]));
+    }
+
+    #[test]
+    fn load_state_rolls_back_a_corrupt_state() {
+        let mut gameboy = GameBoy::new();
+        gameboy.cpu.program_counter = 0x1234;
+        let mut saved = Vec::new();
+        gameboy.save_state(&mut saved).unwrap();
+
+        // Corrupt the first frame buffer pixel, just after the CPU state and the 3 bytes of input state
+        let mut cpu_state = Vec::new();
+        gameboy.cpu.save_state(&mut StateWriter::new(&mut cpu_state)).unwrap();
+        saved[7 + cpu_state.len() + 3] = 0xFF;
+
+        gameboy.cpu.program_counter = 0x4321;
+        assert_bad_save_state(gameboy.load_state(&mut &saved[..]));
+        assert_eq!(gameboy.cpu.program_counter, 0x4321);
+    }
+}
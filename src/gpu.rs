@@ -1,12 +1,21 @@
-use std::process::exit;
+#![allow(clippy::needless_return)]
 
-use sdl2::{EventPump, Sdl};
-use sdl2::pixels::Color;
-use sdl2::rect::Point;
-use sdl2::render::{BlendMode, Canvas};
-use sdl2::video::Window;
+use std::io::{self, Read, Write};
 
 use crate::input::Input;
+use crate::interrupts::{Interrupt, InterruptController};
+use crate::savestate::{StateReader, StateWriter};
+
+// Screen Dimensions
+pub const SCREEN_WIDTH: usize  = 160;
+pub const SCREEN_HEIGHT: usize = 144;
+
+// VRAM Debug View Dimensions
+pub const VRAM_DEBUG_WIDTH: usize  = 256;
+pub const VRAM_DEBUG_HEIGHT: usize = 256;
+
+// RGB values for each of the 4 shades of green, lightest to darkest
+pub const SHADE_COLORS: [[u8; 3]; 4] = [[155, 188, 15], [139, 172, 15], [48, 98, 48], [15, 56, 15]];
 
 // GPU States
 pub const STATE_HBLANK: u8    = 0;
@@ -48,11 +57,9 @@ impl Sprite {
 }
 
 pub struct GPU {
-    sdl_context: Sdl,
-    pub canvas: Canvas<Window>,
-    pub vram_debug_canvas: Canvas<Window>,
-    pub event_pump: EventPump,
     pub input: Input,
+    pub is_ly_stubbed: bool, // LY always reads 0x90 like gameboy-doctor expects, set while tracing. Not part of save states
+    frame_buffer: [u8; SCREEN_WIDTH * SCREEN_HEIGHT], // Palette resolved shade (0-3) of every pixel on screen
     vram: [u8; 8192],
     oam:  [u8;  160],
     object_data: [Sprite; 40],
@@ -65,38 +72,25 @@ pub struct GPU {
     scroll_x: u8,
     render_line: u8,
     ly_compare: u8,   // TODO - implement and use this
-    dma_transfer: u8, // TODO - implement and use this
+    dma_transfer: u8, // Source page of the last OAM DMA, the MMU does the copy
     palette: [u8; 4],
     sprite_palette_0: [u8; 4],
     sprite_palette_1: [u8; 4],
     window_y: u8,
     window_x: u8,
     gpu_registers: [u8; 52],
-    palette_reference: [Color; 4],
-    lock_vram: bool,
-    pub debug: bool
+    lock_vram: bool
 }
 
 impl GPU {
+    #[allow(clippy::new_without_default)]
     pub fn new() -> Self {
         debug!("Initializing GPU");
 
-        let sdl_context = sdl2::init().unwrap();
-        let mut canvas = sdl_context.video().unwrap()
-            .window("RustBoy", 160, 144).position(800, 100).build().unwrap()
-            .into_canvas().accelerated().build().unwrap();
-        canvas.set_blend_mode(BlendMode::Blend);
-        let vram_debug_canvas = sdl_context.video().unwrap()
-            .window("GPU", 256, 256).position(800, 300).hidden().build().unwrap()
-            .into_canvas().accelerated().build().unwrap();
-        let event_pump = sdl_context.event_pump().unwrap();
-
         GPU {
-            sdl_context,
-            canvas,
-            vram_debug_canvas,
-            event_pump,
             input: Input::new(),
+            is_ly_stubbed: false,
+            frame_buffer: [0; SCREEN_WIDTH * SCREEN_HEIGHT],
             vram: [0; 8192],
             oam: [0; 160],
             object_data: [Sprite::new(); 40],
@@ -116,9 +110,7 @@ impl GPU {
             window_y: 0,
             window_x: 0,
             gpu_registers: [0; 52],
-            palette_reference: [Color::RGB(155,188,15), Color::RGB(139,172,15), Color::RGB(48,98,48), Color::RGB(15,56,15)],
-            lock_vram: false,
-            debug: false
+            lock_vram: false
         }
     }
 
@@ -137,6 +129,9 @@ impl GPU {
                 return self.scroll_x;
             },
             0xFF44 => {
+                if self.is_ly_stubbed {
+                    return 0x90;
+                }
                 return self.render_line;
             },
             0xFF45 => {
@@ -226,7 +221,7 @@ impl GPU {
                 return;
             },
             _ => {
-                self.gpu_registers[(address - 0xFF4C) as usize];
+                self.gpu_registers[(address - 0xFF4C) as usize] = value;
                 return;
             }
         }
@@ -266,34 +261,9 @@ impl GPU {
                     };
 
                     self.tileset[tile_index as usize][row_index as usize][pixel_index as usize] = pixel_value;
-
-                    if self.debug {
-                        // If VRAM Debugging - draw update
-                        self.vram_debug_canvas.set_draw_color(self.palette_reference[pixel_value as usize]);
-                        let result = self.vram_debug_canvas.draw_point(Point::new((((tile_index % 32) * 8) + pixel_index as u16) as i32, (((tile_index / 32) * 8) + row_index) as i32));
-                        if result.is_err() {
-                            error!("Error: {:?}", result.err());
-                            exit(1);
-                        }
-                    }
-                }
-            } else {
-                if self.debug {
-                    // If VRAM Debugging - draw update
-                    self.vram_debug_canvas.set_draw_color(Color::RGB(255 - value, 255 - value, 255 - value));
-                    let result = self.vram_debug_canvas.draw_line(Point::new(((index % 32) * 8) as i32, (index / 32) as i32), Point::new((((index % 32) * 8) + 8) as i32, (index / 32) as i32));
-                    if result.is_err() {
-                        error!("Error: {:?}", result.err());
-                        exit(1);
-                    }
                 }
             }
         }
-
-        if self.debug {
-            // If VRAM Debugging - update the canvas
-            self.vram_debug_canvas.present();
-        }
     }
 
     pub fn read_oam(&self, address: u8) -> u8 {
@@ -304,9 +274,11 @@ impl GPU {
         self.oam[address as usize] = value;
     }
 
+    #[allow(clippy::needless_late_init)]
     fn render_scanline(&mut self) {
         // Scanline data, for use by sprite renderer
         let mut scan_row: [u8; 160] = [0; 160];
+        let line_start = self.render_line as usize * SCREEN_WIDTH;
 
         // Render background if enabled
         if self.get_background_status() == 1 {
@@ -328,16 +300,11 @@ impl GPU {
                     //  warn!("Using Tileset 0");
                 }
 
-                let color = self.palette_reference[self.palette[self.tileset[t_index as usize][y as usize][(x % 8) as usize] as usize] as usize];
+                let shade = self.palette[self.tileset[t_index as usize][y as usize][(x % 8) as usize] as usize];
 
                 scan_row[x as usize] = self.tileset[t_index as usize][y as usize][(x % 8) as usize];
 
-                self.canvas.set_draw_color(color);
-                let result = self.canvas.draw_point(Point::new(x as i32, self.render_line as i32));
-                if result.is_err() {
-                    error!("Error: {:?}", result.err());
-                    exit(1);
-                }
+                self.frame_buffer[line_start + x as usize] = shade;
             }
         }
 
@@ -370,20 +337,16 @@ impl GPU {
                             tile = self.tileset[object.tile as usize][(self.render_line - object.y as u8) as usize][x_index as usize];
                         }
 
-                        if object.x + x >= 0 && object.x + x < 160 && tile != 0 && object.bg_priority || scan_row[(object.x + x) as usize] == 0 {
-                            let color;
-                            let tile_palette = sprite_palette[tile as usize];
-                            if tile_palette == 0 {
-                                color = Color::RGBA(0,0,0,0);
-                            } else {
-                                color = self.palette_reference[tile_palette as usize];
-                            }
-                            self.canvas.set_draw_color(color);
+                        let screen_x = object.x + x;
+                        if screen_x < 0 || screen_x >= SCREEN_WIDTH as i16 {
+                            continue;
+                        }
 
-                            let result = self.canvas.draw_point(Point::new(object.x as i32 + x as i32, self.render_line as i32));
-                            if result.is_err() {
-                                error!("Error: {:?}", result.err());
-                                exit(1);
+                        if tile != 0 && object.bg_priority || scan_row[screen_x as usize] == 0 {
+                            // Colour 0 is transparent for sprites
+                            let tile_palette = sprite_palette[tile as usize];
+                            if tile_palette != 0 {
+                                self.frame_buffer[line_start + screen_x as usize] = tile_palette;
                             }
                         }
                     }
@@ -392,6 +355,46 @@ impl GPU {
         }
     }
 
+    pub fn frame_buffer(&self) -> &[u8] {
+        return &self.frame_buffer;
+    }
+
+    pub fn clear_frame_buffer(&mut self) {
+        self.frame_buffer = [0; SCREEN_WIDTH * SCREEN_HEIGHT];
+    }
+
+    // Renders the tileset (32 tiles per row) followed by the raw tile map bytes as an RGB24 image
+    pub fn render_vram_debug(&self) -> Vec<u8> {
+        let mut buffer = vec![0u8; VRAM_DEBUG_WIDTH * VRAM_DEBUG_HEIGHT * 3];
+
+        for tile_index in 0..384 {
+            for row_index in 0..8 {
+                for pixel_index in 0..8 {
+                    let x = ((tile_index % 32) * 8) + pixel_index;
+                    let y = ((tile_index / 32) * 8) + row_index;
+                    let shade = self.tileset[tile_index][row_index][pixel_index];
+
+                    let offset = (y * VRAM_DEBUG_WIDTH + x) * 3;
+                    buffer[offset..offset + 3].copy_from_slice(&SHADE_COLORS[shade as usize]);
+                }
+            }
+        }
+
+        for index in 0x1800..0x2000 {
+            let x = (index % 32) * 8;
+            let y = index / 32;
+            let grey = 255 - self.vram[index];
+
+            for pixel_index in 0..8 {
+                let offset = (y * VRAM_DEBUG_WIDTH + x + pixel_index) * 3;
+                buffer[offset..offset + 3].copy_from_slice(&[grey, grey, grey]);
+            }
+        }
+
+        return buffer;
+    }
+
+    #[allow(clippy::needless_bool_assign)]
     pub fn build_object_data(&mut self, address: u16, value: u8) {
         let object = address >> 2;
         if object < 40 {
@@ -439,8 +442,10 @@ impl GPU {
         }
     }
 
-    pub fn tick(&mut self, clock_t: u8) -> bool {
+    pub fn tick(&mut self, clock_t: u8, interrupts: &mut InterruptController) -> bool {
         let mut entered_vblank = false;
+        let previous_state = self.state;
+        let previous_line = self.render_line;
 
         self.state_clock += clock_t as u16;
 
@@ -473,11 +478,6 @@ impl GPU {
 
                     if self.render_line == 143 {
                         self.state = STATE_VBLANK;
-
-                        if self.get_display_status() == 1 {
-                            self.canvas.present();
-                        }
-
                         entered_vblank = true;
                     } else {
                         self.state = STATE_OAM_READ;
@@ -503,6 +503,27 @@ impl GPU {
             }
         }
 
+        if entered_vblank {
+            interrupts.request(Interrupt::VBlank);
+        }
+
+        // STAT interrupt sources, enabled by bits 3-6 of the STAT register
+        let mut stat_interrupt = false;
+        if self.state != previous_state {
+            match self.state {
+                STATE_HBLANK => stat_interrupt = self.lcd_status & 0x08 != 0,
+                STATE_VBLANK => stat_interrupt = self.lcd_status & 0x10 != 0,
+                STATE_OAM_READ => stat_interrupt = self.lcd_status & 0x20 != 0,
+                _ => {}
+            }
+        }
+        if self.render_line != previous_line && self.render_line == self.ly_compare && self.lcd_status & 0x40 != 0 {
+            stat_interrupt = true;
+        }
+        if stat_interrupt {
+            interrupts.request(Interrupt::LcdStat);
+        }
+
         return entered_vblank;
     }
 
@@ -510,10 +531,12 @@ impl GPU {
         return (self.lcd_control & 0x80) >> 7;
     }
 
+    #[allow(dead_code)]
     fn get_window_tilemap(&self) -> u8 {
         return (self.lcd_control & 0x40) >> 6;
     }
 
+    #[allow(dead_code)]
     fn get_window_status(&self) -> u8 {
         return (self.lcd_control & 0x20) >> 5;
     }
@@ -526,6 +549,7 @@ impl GPU {
         return (self.lcd_control & 0x08) >> 3;
     }
 
+    #[allow(dead_code)]
     fn get_sprite_size(&self) -> u8 {
         return (self.lcd_control & 0x04) >> 2;
     }
@@ -537,4 +561,192 @@ impl GPU {
     fn get_background_status(&self) -> u8 {
         return self.lcd_control & 0x01;
     }
-}
\ No newline at end of file
+
+    pub fn save_state<W: Write>(&self, state: &mut StateWriter<W>) -> io::Result<()> {
+        self.input.save_state(state)?;
+        state.write_bytes(&self.frame_buffer)?;
+        state.write_bytes(&self.vram)?;
+        state.write_bytes(&self.oam)?;
+        for sprite in self.object_data.iter() {
+            state.write_i16(sprite.x)?;
+            state.write_i16(sprite.y)?;
+            state.write_u8(sprite.tile)?;
+            state.write_bool(sprite.palette)?;
+            state.write_bool(sprite.xflip)?;
+            state.write_bool(sprite.yflip)?;
+            state.write_bool(sprite.bg_priority)?;
+        }
+        // The tileset has the background palette baked in, so it can't be rebuilt from VRAM alone
+        for tile in self.tileset.iter() {
+            for row in tile.iter() {
+                state.write_bytes(row)?;
+            }
+        }
+        state.write_u8(self.state)?;
+        state.write_u16(self.state_clock)?;
+        state.write_u8(self.lcd_control)?;
+        state.write_u8(self.lcd_status)?;
+        state.write_u8(self.scroll_y)?;
+        state.write_u8(self.scroll_x)?;
+        state.write_u8(self.render_line)?;
+        state.write_u8(self.ly_compare)?;
+        state.write_u8(self.dma_transfer)?;
+        state.write_bytes(&self.palette)?;
+        state.write_bytes(&self.sprite_palette_0)?;
+        state.write_bytes(&self.sprite_palette_1)?;
+        state.write_u8(self.window_y)?;
+        state.write_u8(self.window_x)?;
+        state.write_bytes(&self.gpu_registers)?;
+        state.write_bool(self.lock_vram)?;
+        Ok(())
+    }
+
+    pub fn load_state<R: Read>(&mut self, state: &mut StateReader<R>) -> io::Result<()> {
+        self.input.load_state(state)?;
+        state.read_bytes(&mut self.frame_buffer)?;
+        state.read_bytes(&mut self.vram)?;
+        state.read_bytes(&mut self.oam)?;
+        for sprite in self.object_data.iter_mut() {
+            sprite.x = state.read_i16()?;
+            sprite.y = state.read_i16()?;
+            sprite.tile = state.read_u8()?;
+            sprite.palette = state.read_bool()?;
+            sprite.xflip = state.read_bool()?;
+            sprite.yflip = state.read_bool()?;
+            sprite.bg_priority = state.read_bool()?;
+        }
+        for tile in self.tileset.iter_mut() {
+            for row in tile.iter_mut() {
+                state.read_bytes(row)?;
+            }
+        }
+        self.state = state.read_u8()?;
+        self.state_clock = state.read_u16()?;
+        self.lcd_control = state.read_u8()?;
+        self.lcd_status = state.read_u8()?;
+        self.scroll_y = state.read_u8()?;
+        self.scroll_x = state.read_u8()?;
+        self.render_line = state.read_u8()?;
+        self.ly_compare = state.read_u8()?;
+        self.dma_transfer = state.read_u8()?;
+        state.read_bytes(&mut self.palette)?;
+        state.read_bytes(&mut self.sprite_palette_0)?;
+        state.read_bytes(&mut self.sprite_palette_1)?;
+        self.window_y = state.read_u8()?;
+        self.window_x = state.read_u8()?;
+        state.read_bytes(&mut self.gpu_registers)?;
+        self.lock_vram = state.read_bool()?;
+        return self.check_state();
+    }
+
+    // A corrupt state would otherwise panic later on, indexing SHADE_COLORS, the palettes or the frame buffer
+    fn check_state(&self) -> io::Result<()> {
+        let pixels = self.tileset.iter().flat_map(|tile| tile.iter().flatten());
+        let palettes = self.palette.iter().chain(self.sprite_palette_0.iter()).chain(self.sprite_palette_1.iter());
+        if self.frame_buffer.iter().chain(pixels).chain(palettes).any(|shade| *shade > 3) {
+            return Err(bad_state(String::from("GPU shade out of range")));
+        }
+
+        if self.state > STATE_VRAM_READ {
+            return Err(bad_state(format!("unknown GPU mode {}", self.state)));
+        }
+
+        // Lines 143 to 153 are only reached in VBlank, see tick
+        if self.render_line > 153 || (self.state != STATE_VBLANK && self.render_line >= 143) {
+            return Err(bad_state(format!("GPU line {} in mode {}", self.render_line, self.state)));
+        }
+
+        if self.state_clock >= 456 {
+            return Err(bad_state(format!("GPU mode clock {} is longer than a line", self.state_clock)));
+        }
+
+        // The range build_object_data can produce from an OAM byte
+        for sprite in self.object_data.iter() {
+            if sprite.y < -16 || sprite.y > 239 || sprite.x < -8 || sprite.x > 247 {
+                return Err(bad_state(format!("sprite position {}, {} out of range", sprite.x, sprite.y)));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn bad_state(reason: String) -> io::Error {
+    return io::Error::new(io::ErrorKind::InvalidData, reason);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reload(gpu: &GPU) -> io::Result<()> {
+        let mut buffer = Vec::new();
+        gpu.save_state(&mut StateWriter::new(&mut buffer))?;
+        return GPU::new().load_state(&mut StateReader::new(&mut &buffer[..]));
+    }
+
+    fn assert_rejected(gpu: &GPU) {
+        let error = reload(gpu).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn stubbed_ly_reads_0x90() {
+        let mut gpu = GPU::new();
+        assert_eq!(gpu.read_register(0xFF44), 0);
+        gpu.is_ly_stubbed = true;
+        assert_eq!(gpu.read_register(0xFF44), 0x90);
+    }
+
+    #[test]
+    fn load_state_accepts_a_valid_state() {
+        let mut gpu = GPU::new();
+        gpu.state = STATE_VBLANK;
+        gpu.render_line = 153;
+        gpu.frame_buffer[0] = 3;
+        reload(&gpu).unwrap();
+    }
+
+    #[test]
+    fn load_state_rejects_bad_shades() {
+        let mut gpu = GPU::new();
+        gpu.frame_buffer[100] = 4;
+        assert_rejected(&gpu);
+
+        let mut gpu = GPU::new();
+        gpu.tileset[383][7][7] = 0xFF;
+        assert_rejected(&gpu);
+
+        let mut gpu = GPU::new();
+        gpu.palette[2] = 4;
+        assert_rejected(&gpu);
+
+        let mut gpu = GPU::new();
+        gpu.sprite_palette_1[3] = 0x80;
+        assert_rejected(&gpu);
+    }
+
+    #[test]
+    fn load_state_rejects_bad_mode_and_line() {
+        let mut gpu = GPU::new();
+        gpu.state = 4;
+        assert_rejected(&gpu);
+
+        let mut gpu = GPU::new();
+        gpu.state = STATE_VBLANK;
+        gpu.render_line = 154;
+        assert_rejected(&gpu);
+
+        let mut gpu = GPU::new();
+        gpu.state = STATE_VRAM_READ;
+        gpu.render_line = 150;
+        assert_rejected(&gpu);
+
+        let mut gpu = GPU::new();
+        gpu.state_clock = 0xFFFF;
+        assert_rejected(&gpu);
+
+        let mut gpu = GPU::new();
+        gpu.object_data[0].y = i16::MAX;
+        assert_rejected(&gpu);
+    }
+}
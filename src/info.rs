@@ -0,0 +1,140 @@
+#![allow(clippy::needless_return)]
+
+use rustboy::EmuError;
+use rustboy::archive::read_rom_file;
+use rustboy::cartridge::Cartridge;
+
+// `RustBoy info <rom>`: prints what the header says about a ROM without running it
+pub fn print_info(rom_path: &str, rom_entry: Option<&str>, json: bool) -> Result<(), EmuError> {
+    let data = read_rom_file(rom_path, rom_entry)?;
+    let cartridge = Cartridge::from_bytes(&data)?;
+
+    let features = cartridge_features(&cartridge);
+    let crc = crc32(&data);
+
+    if json {
+        print_json(rom_path, &cartridge, &features, data.len(), crc);
+    } else {
+        print_text(rom_path, &cartridge, &features, data.len(), crc);
+    }
+    return Ok(());
+}
+
+fn cartridge_features(cartridge: &Cartridge) -> Vec<&'static str> {
+    let mut features = Vec::new();
+    if cartridge.has_battery() {
+        features.push("battery");
+    }
+    if cartridge.has_rtc() {
+        features.push("rtc");
+    }
+    if cartridge.has_rumble() {
+        features.push("rumble");
+    }
+    if cartridge.gameboy_type == 0xC0 {
+        features.push("cgb-only");
+    }
+    if cartridge.supports_sgb() {
+        features.push("sgb");
+    }
+    return features;
+}
+
+fn print_text(rom_path: &str, cartridge: &Cartridge, features: &[&str], file_size: usize, crc: u32) {
+    println!("File:            {}", rom_path);
+    println!("File size:       {} bytes", file_size);
+    println!("CRC32:           {:08x}", crc);
+    println!("Title:           {}", cartridge.title);
+    if !cartridge.manufacturer_code.is_empty() {
+        println!("Manufacturer:    {}", cartridge.manufacturer_code);
+    }
+    println!("Licensee:        {}", cartridge.licensee_name());
+    println!("Cartridge type:  {} ({:#04X})", cartridge.cartridge_type_name(), cartridge.cartridge_type);
+    println!("ROM size:        {} KiB", cartridge.rom_size_bytes / 1024);
+    println!("RAM size:        {} KiB", cartridge.ram_size_bytes / 1024);
+    println!("Hardware:        {}", cartridge.cgb_support_name());
+    println!("Destination:     {}", cartridge.destination_name());
+    println!("Version:         {}", cartridge.version);
+    println!("Features:        {}", if features.is_empty() { String::from("none") } else { features.join(", ") });
+    println!("Header checksum: {:#04X} ({})", cartridge.header_checksum, if cartridge.is_header_checksum_valid { "OK" } else { "mismatch" });
+    println!("Global checksum: {:#06X} ({})", cartridge.checksum, if cartridge.is_checksum_valid { "OK" } else { "mismatch" });
+}
+
+fn print_json(rom_path: &str, cartridge: &Cartridge, features: &[&str], file_size: usize, crc: u32) {
+    let features: Vec<String> = features.iter().map(|feature| json_string(feature)).collect();
+
+    println!("{{");
+    println!("  \"file\": {},", json_string(rom_path));
+    println!("  \"file_size\": {},", file_size);
+    println!("  \"crc32\": \"{:08x}\",", crc);
+    println!("  \"title\": {},", json_string(&cartridge.title));
+    println!("  \"manufacturer_code\": {},", json_string(&cartridge.manufacturer_code));
+    println!("  \"licensee\": {},", json_string(cartridge.licensee_name()));
+    println!("  \"cartridge_type\": {},", cartridge.cartridge_type);
+    println!("  \"cartridge_type_name\": {},", json_string(cartridge.cartridge_type_name()));
+    println!("  \"rom_size\": {},", cartridge.rom_size_bytes);
+    println!("  \"ram_size\": {},", cartridge.ram_size_bytes);
+    println!("  \"hardware\": {},", json_string(cartridge.cgb_support_name()));
+    println!("  \"destination\": {},", json_string(cartridge.destination_name()));
+    println!("  \"version\": {},", cartridge.version);
+    println!("  \"features\": [{}],", features.join(", "));
+    println!("  \"header_checksum\": {},", cartridge.header_checksum);
+    println!("  \"header_checksum_valid\": {},", cartridge.is_header_checksum_valid);
+    println!("  \"global_checksum\": {},", cartridge.checksum);
+    println!("  \"global_checksum_valid\": {}", cartridge.is_checksum_valid);
+    println!("}}");
+}
+
+// Quotes and escapes a string for JSON output
+fn json_string(value: &str) -> String {
+    let mut output = String::with_capacity(value.len() + 2);
+    output.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => output.push_str("\\\""),
+            '\\' => output.push_str("\\\\"),
+            '\n' => output.push_str("\\n"),
+            '\r' => output.push_str("\\r"),
+            '\t' => output.push_str("\\t"),
+            c if (c as u32) < 0x20 => output.push_str(&format!("\\u{:04x}", c as u32)),
+            c => output.push(c)
+        }
+    }
+    output.push('"');
+    return output;
+}
+
+// CRC-32 (IEEE), the same hash No-Intro and most ROM databases list
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFFFFFFu32;
+    for byte in data.iter() {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            if crc & 1 != 0 {
+                crc = (crc >> 1) ^ 0xEDB88320;
+            } else {
+                crc >>= 1;
+            }
+        }
+    }
+    return !crc;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn features_for_type(cartridge_type: u8) -> Vec<&'static str> {
+        let mut rom = vec![0; 0x8000];
+        rom[0x147] = cartridge_type;
+        rom[0x149] = 0x02;
+        return cartridge_features(&Cartridge::from_bytes(&rom).unwrap());
+    }
+
+    #[test]
+    fn mbc5_ram_has_no_battery() {
+        assert!(features_for_type(0x1A).is_empty());
+        assert_eq!(features_for_type(0x1B), vec!["battery"]);
+        assert_eq!(features_for_type(0x1E), vec!["battery", "rumble"]);
+    }
+}
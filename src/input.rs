@@ -1,9 +1,27 @@
+#![allow(clippy::needless_return)]
+
+use std::io::{self, Read, Write};
+
+use crate::interrupts::{Interrupt, InterruptController};
+use crate::savestate::{StateReader, StateWriter};
+
+// Buttons, as reported by an InputSource
+pub const BUTTON_A: u8      = 0x01;
+pub const BUTTON_B: u8      = 0x02;
+pub const BUTTON_SELECT: u8 = 0x04;
+pub const BUTTON_START: u8  = 0x08;
+pub const BUTTON_RIGHT: u8  = 0x10;
+pub const BUTTON_LEFT: u8   = 0x20;
+pub const BUTTON_UP: u8     = 0x40;
+pub const BUTTON_DOWN: u8   = 0x80;
+
 pub struct Input {
     pub column: u8,
     pub keys: [u8; 2],
 }
 
 impl Input {
+    #[allow(clippy::new_without_default)]
     pub fn new() -> Self {
         debug!("Initializing Input");
 
@@ -24,4 +42,40 @@ impl Input {
     pub fn write(&mut self, value: u8) {
         self.column = value & 0x30;
     }
-}
\ No newline at end of file
+
+    // True if a button on a selected line is held. This is what brings the system out of STOP
+    pub fn is_any_line_low(&self) -> bool {
+        let mut lines = 0x0F;
+        if self.column & 0x20 == 0 {
+            lines &= self.keys[0];
+        }
+        if self.column & 0x10 == 0 {
+            lines &= self.keys[1];
+        }
+        return lines != 0x0F;
+    }
+
+    // Takes a bitmask of pressed BUTTON_* values. The hardware is active low, so a pressed button reads as 0.
+    // Pressing a button that wasn't already held requests the joypad interrupt
+    pub fn set_pressed(&mut self, pressed: u8, interrupts: &mut InterruptController) {
+        let was_pressed = !(self.keys[0] | self.keys[1] << 4);
+        if pressed & !was_pressed != 0 {
+            interrupts.request(Interrupt::Joypad);
+        }
+
+        self.keys[0] = !pressed & 0x0F;
+        self.keys[1] = !(pressed >> 4) & 0x0F;
+    }
+
+    pub fn save_state<W: Write>(&self, state: &mut StateWriter<W>) -> io::Result<()> {
+        state.write_u8(self.column)?;
+        state.write_bytes(&self.keys)?;
+        Ok(())
+    }
+
+    pub fn load_state<R: Read>(&mut self, state: &mut StateReader<R>) -> io::Result<()> {
+        self.column = state.read_u8()?;
+        state.read_bytes(&mut self.keys)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,103 @@
+#![allow(clippy::needless_return)]
+
+use std::io::{self, Read, Write};
+
+use crate::savestate::{StateReader, StateWriter};
+
+// Interrupt bits in IE (0xFFFF) and IF (0xFF0F)
+pub const VBLANK_INTERRUPT_BIT: u8 = 0x01;
+pub const LCD_INTERRUPT_BIT: u8    = 0x02;
+pub const TIMER_INTERRUPT_BIT: u8  = 0x04;
+pub const SERIAL_INTERRUPT_BIT: u8 = 0x08;
+pub const JOYPAD_INTERRUPT_BIT: u8 = 0x10;
+
+// Listed in priority order, VBlank is serviced first when several are pending
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Interrupt {
+    VBlank,
+    LcdStat,
+    Timer,
+    Serial,
+    Joypad
+}
+
+const INTERRUPTS: [Interrupt; 5] = [Interrupt::VBlank, Interrupt::LcdStat, Interrupt::Timer, Interrupt::Serial, Interrupt::Joypad];
+
+impl Interrupt {
+    pub fn bit(self) -> u8 {
+        match self {
+            Interrupt::VBlank => return VBLANK_INTERRUPT_BIT,
+            Interrupt::LcdStat => return LCD_INTERRUPT_BIT,
+            Interrupt::Timer => return TIMER_INTERRUPT_BIT,
+            Interrupt::Serial => return SERIAL_INTERRUPT_BIT,
+            Interrupt::Joypad => return JOYPAD_INTERRUPT_BIT
+        }
+    }
+
+    // Address the CPU jumps to when it services the interrupt
+    pub fn vector(self) -> u16 {
+        match self {
+            Interrupt::VBlank => return 0x40,
+            Interrupt::LcdStat => return 0x48,
+            Interrupt::Timer => return 0x50,
+            Interrupt::Serial => return 0x58,
+            Interrupt::Joypad => return 0x60
+        }
+    }
+}
+
+// Owns IE and IF. Hardware requests interrupts here and the CPU services them
+pub struct InterruptController {
+    pub enable: u8, // IE, 0xFFFF
+    pub flags: u8   // IF, 0xFF0F
+}
+
+impl InterruptController {
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Self {
+        InterruptController {
+            enable: 0,
+            flags: 0
+        }
+    }
+
+    pub fn request(&mut self, interrupt: Interrupt) {
+        trace!("Requesting {:?} Interrupt", interrupt);
+        self.flags |= interrupt.bit();
+    }
+
+    // Clears the request once the CPU has jumped to the handler
+    pub fn acknowledge(&mut self, interrupt: Interrupt) {
+        self.flags &= !interrupt.bit();
+    }
+
+    // Interrupts that are both requested and enabled. These wake the CPU from HALT even when IME is off
+    pub fn pending(&self) -> u8 {
+        return self.enable & self.flags & 0x1F;
+    }
+
+    pub fn highest_pending(&self) -> Option<Interrupt> {
+        let pending = self.pending();
+        return INTERRUPTS.iter().cloned().find(|interrupt| pending & interrupt.bit() != 0);
+    }
+
+    pub fn read_flags(&self) -> u8 {
+        return self.flags | 0xE0; // or'd with E0 because Bits 5-7 are unmapped, unmapped bits always return as 1
+    }
+
+    pub fn write_flags(&mut self, value: u8) {
+        self.flags = value & 0x1F;
+    }
+
+    pub fn save_state<W: Write>(&self, state: &mut StateWriter<W>) -> io::Result<()> {
+        state.write_u8(self.enable)?;
+        state.write_u8(self.flags)?;
+        Ok(())
+    }
+
+    pub fn load_state<R: Read>(&mut self, state: &mut StateReader<R>) -> io::Result<()> {
+        self.enable = state.read_u8()?;
+        self.flags = state.read_u8()?;
+        Ok(())
+    }
+}
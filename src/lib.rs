@@ -0,0 +1,28 @@
+#[macro_use] extern crate log;
+
+pub mod archive;
+pub mod cartridge;
+pub mod cpu;
+pub mod disasm;
+pub mod error;
+pub mod frontend;
+pub mod gameboy;
+pub mod gpu;
+pub mod input;
+pub mod interrupts;
+pub mod mapper;
+pub mod mmu;
+pub mod pacing;
+pub mod rewind;
+pub mod rtc;
+pub mod savestate;
+pub mod serial;
+pub mod timer;
+pub mod tracer;
+
+pub use crate::cpu::CPU;
+pub use crate::error::EmuError;
+pub use crate::gameboy::GameBoy;
+pub use crate::gpu::GPU;
+pub use crate::mmu::MMU;
+pub use crate::timer::Timer;
@@ -1,22 +1,31 @@
+#![allow(clippy::needless_return)]
+
 #[macro_use] extern crate clap;
 #[macro_use] extern crate log;
 extern crate pretty_env_logger;
 
 use clap::App;
 
-use crate::cartridge::Cartridge;
-use crate::cpu::CPU;
-use crate::gameboy::GameBoy;
-use crate::mmu::MMU;
+use rustboy::GameBoy;
+use rustboy::archive::read_rom_file;
+use rustboy::disasm;
+use rustboy::pacing::Speed;
+use rustboy::tracer::Tracer;
 use std::process::exit;
 
-mod cartridge;
-mod cpu;
-mod gameboy;
-mod gpu;
-mod input;
-mod mmu;
-mod timer;
+mod info;
+#[cfg(feature = "sdl")]
+mod sdl;
+
+// Frontend settings from the command line
+#[cfg_attr(not(feature = "sdl"), allow(dead_code))]
+struct RunOptions<'a> {
+    debug: bool,
+    speed: Speed,
+    rom_path: &'a str,
+    rewind_budget: usize,  // Bytes
+    rewind_interval: u32   // Frames
+}
 
 fn main() {
     // Command Line Arg Parser
@@ -26,27 +35,60 @@ fn main() {
     // Logger Init
     pretty_env_logger::init();
 
+    if let Some(info_matches) = matches.subcommand_matches("info") {
+        let rom_path = info_matches.value_of("ROM").unwrap_or("");
+        let result = info::print_info(rom_path, info_matches.value_of("rom-entry"), info_matches.is_present("json"));
+        if result.is_err() {
+            error!("Failed to read ROM info: {}", result.err().unwrap());
+            exit(1);
+        }
+        return;
+    }
+
+    if let Some(disasm_matches) = matches.subcommand_matches("disasm") {
+        disassemble(disasm_matches);
+        return;
+    }
+
     // Init GameBoy
-    let mut gameboy = GameBoy {
-        cpu: CPU::new(),
-        mmu: MMU::new(),
-        cartridge: Cartridge::new(),
-        is_paused: false
-    };
+    let mut gameboy = GameBoy::new();
 
     // Parse args
     let bios_path = matches.value_of("bios").unwrap_or("");
     let rom_path= matches.value_of("rom").unwrap_or("");
-    let debug = matches.value_of("debug").unwrap_or("false");
-    if debug.eq_ignore_ascii_case("true") {
-        gameboy.mmu.gpu.debug = true;
-        gameboy.mmu.gpu.vram_debug_canvas.window_mut().show();
-    }
+    let debug = matches.value_of("debug").unwrap_or("false").eq_ignore_ascii_case("true");
     let paused = matches.value_of("pause").unwrap_or("false");
     if paused.eq_ignore_ascii_case("true") {
         gameboy.is_paused = true;
     }
 
+    let speed_arg = matches.value_of("speed").unwrap_or("1");
+    let speed = match Speed::parse(speed_arg) {
+        Some(speed) => speed,
+        None => {
+            error!("Invalid speed '{}'. Use a multiplier between 0.25 and 8, or 'unlimited'.", speed_arg);
+            exit(1);
+        }
+    };
+
+    let rewind_budget_arg = matches.value_of("rewind-budget").unwrap_or("32");
+    let rewind_budget = match rewind_budget_arg.parse::<usize>() {
+        Ok(megabytes) => megabytes * 1024 * 1024,
+        Err(_) => {
+            error!("Invalid rewind budget '{}'. Use a whole number of megabytes.", rewind_budget_arg);
+            exit(1);
+        }
+    };
+
+    let rewind_interval_arg = matches.value_of("rewind-interval").unwrap_or("2");
+    let rewind_interval = match rewind_interval_arg.parse::<u32>() {
+        Ok(frames) if frames > 0 => frames,
+        _ => {
+            error!("Invalid rewind interval '{}'. Use a whole number of frames greater than 0.", rewind_interval_arg);
+            exit(1);
+        }
+    };
+
     if bios_path.is_empty() && rom_path.is_empty() {
         error!("No ROM or BIOS was specified. You must provide at least one (Usually ROM or both). Use the --help flag for more information.");
         exit(1);
@@ -69,8 +111,207 @@ fn main() {
 
     // Load ROM if provided
     if !rom_path.is_empty() {
-        gameboy.load_rom(rom_path);
+        let result = gameboy.load_rom(rom_path, matches.value_of("rom-entry"));
+        if result.is_err() {
+            error!("Failed to load ROM");
+            error!("Error: {}", result.err().unwrap());
+            exit(1);
+        }
+    }
+
+    if let Some(trace_path) = matches.value_of("trace") {
+        gameboy.tracer = Some(create_tracer(trace_path, &matches));
+        gameboy.mmu.gpu.is_ly_stubbed = true;
+    }
+
+    let options = RunOptions {
+        debug,
+        speed,
+        rom_path,
+        rewind_budget,
+        rewind_interval
+    };
+    run(&mut gameboy, &options);
+}
+
+// Sets up --trace along with its start/stop triggers and line limit
+fn create_tracer(trace_path: &str, matches: &clap::ArgMatches) -> Tracer {
+    let mut tracer = match Tracer::create(trace_path) {
+        Ok(tracer) => tracer,
+        Err(error) => {
+            error!("Failed to create trace file '{}': {}", trace_path, error);
+            exit(1);
+        }
+    };
+
+    if let Some(start_arg) = matches.value_of("trace-start") {
+        tracer.start_pc = Some(parse_address(start_arg));
+    }
+
+    if let Some(stop_arg) = matches.value_of("trace-stop") {
+        tracer.stop_pc = Some(parse_address(stop_arg));
+    }
+
+    if let Some(limit_arg) = matches.value_of("trace-limit") {
+        match limit_arg.parse::<u64>() {
+            Ok(lines) if lines > 0 => tracer.max_lines = Some(lines),
+            _ => {
+                error!("Invalid trace limit '{}'. Use a whole number of lines greater than 0.", limit_arg);
+                exit(1);
+            }
+        }
+    }
+
+    return tracer;
+}
+
+// Hex address from the command line, with or without a $ or 0x prefix
+fn parse_address(address_arg: &str) -> u16 {
+    match u16::from_str_radix(address_arg.trim_start_matches('$').trim_start_matches("0x"), 16) {
+        Ok(address) => return address,
+        Err(_) => {
+            error!("Invalid address '{}'. Use a hex address like 0150 or $4000.", address_arg);
+            exit(1);
+        }
+    }
+}
+
+// `RustBoy disasm <rom>`: prints an RGBDS listing of part of a ROM bank
+fn disassemble(matches: &clap::ArgMatches) {
+    let bank_arg = matches.value_of("bank").unwrap_or("0");
+    let bank = match bank_arg.parse::<usize>() {
+        Ok(bank) => bank,
+        Err(_) => {
+            error!("Invalid bank '{}'. Use a whole number.", bank_arg);
+            exit(1);
+        }
+    };
+
+    let from = match matches.value_of("from") {
+        Some(from_arg) => parse_address(from_arg),
+        None if bank == 0 => 0x0100,
+        None => 0x4000
+    };
+
+    let count_arg = matches.value_of("count").unwrap_or("32");
+    let count = match count_arg.parse::<usize>() {
+        Ok(count) => count,
+        Err(_) => {
+            error!("Invalid count '{}'. Use a whole number of instructions.", count_arg);
+            exit(1);
+        }
+    };
+
+    let rom_path = matches.value_of("ROM").unwrap_or("");
+    let rom = match read_rom_file(rom_path, matches.value_of("rom-entry")) {
+        Ok(rom) => rom,
+        Err(error) => {
+            error!("Failed to read ROM: {}", error);
+            exit(1);
+        }
+    };
+
+    match disasm::disassemble_bank(&rom, bank, from, count) {
+        Ok(instructions) => {
+            for line in disasm::format_listing(&instructions) {
+                println!("{}", line);
+            }
+        },
+        Err(reason) => {
+            error!("Can't disassemble: {}", reason);
+            exit(1);
+        }
+    }
+}
+
+#[cfg(feature = "sdl")]
+fn run(gameboy: &mut GameBoy, options: &RunOptions) {
+    use rustboy::gameboy::CYCLES_PER_FRAME;
+    use rustboy::pacing::FramePacer;
+    use rustboy::rewind::RewindBuffer;
+    use std::time::{Duration, Instant};
+
+    // How often battery backed RAM is written out while playing
+    const SAVE_FLUSH_INTERVAL: Duration = Duration::from_secs(5);
+
+    let mut frontend = sdl::SdlFrontend::new(gameboy, options.debug, options.rom_path);
+    let mut pacer = FramePacer::new(options.speed);
+    let mut rewind = RewindBuffer::new(options.rewind_interval, options.rewind_budget);
+
+    let mut last_save_flush = Instant::now();
+
+    gameboy.start();
+
+    while !frontend.quit {
+        if last_save_flush.elapsed() >= SAVE_FLUSH_INTERVAL {
+            flush_save(gameboy);
+            last_save_flush = Instant::now();
+        }
+
+        if gameboy.is_paused {
+            frontend.wait_for_events(gameboy);
+            pacer.reset();
+            continue;
+        }
+
+        frontend.handle_events(gameboy);
+        if frontend.quit {
+            break;
+        }
+
+        // Play back snapshots newest first while the rewind key is held, each one held for the capture interval.
+        // Once the buffer runs dry the emulator holds on the oldest snapshot until the key is released
+        if frontend.rewinding {
+            if rewind.rewind(gameboy) {
+                gameboy.video_sink.present_frame(gameboy.mmu.gpu.frame_buffer());
+                frontend.present_vram_debug(gameboy);
+            }
+            pacer.wait(CYCLES_PER_FRAME);
+            continue;
+        }
+
+        let result = gameboy.run_frame();
+        if result.is_err() {
+            error!("Emulation stopped: {}", result.err().unwrap());
+            flush_save(gameboy);
+            flush_trace(gameboy);
+            exit(1);
+        }
+
+        rewind.capture(gameboy);
+        frontend.present_vram_debug(gameboy);
+
+        if frontend.fast_forward {
+            pacer.reset();
+        } else {
+            pacer.wait(result.unwrap().cycles);
+        }
     }
 
-    gameboy.run();
+    flush_save(gameboy);
+    flush_trace(gameboy);
+}
+
+#[cfg(feature = "sdl")]
+fn flush_trace(gameboy: &mut GameBoy) {
+    if let Some(tracer) = gameboy.tracer.as_mut() {
+        let result = tracer.flush();
+        if result.is_err() {
+            error!("Failed to write trace: {}", result.err().unwrap());
+        }
+    }
+}
+
+#[cfg(feature = "sdl")]
+fn flush_save(gameboy: &mut GameBoy) {
+    let result = gameboy.flush_save();
+    if result.is_err() {
+        error!("Failed to write save data: {}", result.err().unwrap());
+    }
+}
+
+#[cfg(not(feature = "sdl"))]
+fn run(_gameboy: &mut GameBoy, _options: &RunOptions) {
+    error!("RustBoy was built without the `sdl` feature, there is no frontend to run the emulator with.");
+    exit(1);
 }
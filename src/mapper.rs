@@ -0,0 +1,136 @@
+#![allow(clippy::needless_return)]
+
+use std::io;
+
+use crate::cartridge::{ROM_ONLY, MBC1, MBC1_RAM, MBC1_RAM_BATT, MBC2, MBC2_BATT, MBC3_TIMER_BATT, MBC3_TIMER_RAM_BATT, MBC3, MBC3_RAM,
+                       MBC3_RAM_BATT, MBC5, MBC5_RAM, MBC5_RAM_BATT, MBC5_RUMBLE, MBC5_RUMBLE_SRAM, MBC5_RUMB_SRAM_BATT};
+use crate::error::EmuError;
+use crate::savestate::{StateReader, StateWriter};
+
+mod mbc1;
+mod mbc2;
+mod mbc3;
+mod mbc5;
+mod rom_only;
+
+pub use self::mbc1::Mbc1;
+pub use self::mbc2::Mbc2;
+pub use self::mbc3::Mbc3;
+pub use self::mbc5::Mbc5;
+pub use self::rom_only::RomOnly;
+
+pub const ROM_BANK_SIZE: usize = 0x4000;
+pub const RAM_BANK_SIZE: usize = 0x2000;
+
+// The memory bank controller on a cartridge. It owns the cartridge's ROM and RAM and decides which
+// banks of them the CPU sees at 0x0000 - 0x7FFF and 0xA000 - 0xBFFF
+pub trait Mapper {
+    // 0x0000 - 0x7FFF
+    fn read_rom(&self, address: u16) -> u8;
+
+    // Writes to 0x0000 - 0x7FFF, which go to the MBC's registers as ROM can't be written
+    fn write_control(&mut self, address: u16, value: u8);
+
+    // 0xA000 - 0xBFFF. Writes return whether the byte was stored, so the MMU knows if there's anything new to save
+    fn read_ram(&self, address: u16) -> u8;
+    fn write_ram(&mut self, address: u16, value: u8) -> bool;
+
+    // Contents of a .sav file for this cartridge. Empty if there is nothing worth saving
+    fn save_data(&mut self) -> Vec<u8>;
+    fn load_save_data(&mut self, data: &[u8]);
+
+    // Brings a real time clock up to date with the host's clock, for cartridges that have one
+    fn tick_rtc(&mut self) {}
+
+    fn is_rumbling(&self) -> bool {
+        return false;
+    }
+
+    fn save_state(&self, state: &mut StateWriter<Vec<u8>>) -> io::Result<()>;
+    fn load_state(&mut self, state: &mut StateReader<&[u8]>) -> io::Result<()>;
+}
+
+// Picks the mapper for the cartridge type in the header
+pub fn new_mapper(cartridge_type: u8, rom: Vec<u8>, ram_size: usize) -> Result<Box<dyn Mapper>, EmuError> {
+    match cartridge_type {
+        ROM_ONLY => return Ok(Box::new(RomOnly::new(rom))),
+        MBC1 | MBC1_RAM | MBC1_RAM_BATT => return Ok(Box::new(Mbc1::new(rom, ram_size))),
+        MBC2 | MBC2_BATT => return Ok(Box::new(Mbc2::new(rom))),
+        MBC3 | MBC3_RAM | MBC3_RAM_BATT => return Ok(Box::new(Mbc3::new(rom, ram_size, false))),
+        MBC3_TIMER_BATT | MBC3_TIMER_RAM_BATT => return Ok(Box::new(Mbc3::new(rom, ram_size, true))),
+        MBC5 | MBC5_RAM | MBC5_RAM_BATT => return Ok(Box::new(Mbc5::new(rom, ram_size, false))),
+        MBC5_RUMBLE | MBC5_RUMBLE_SRAM | MBC5_RUMB_SRAM_BATT => return Ok(Box::new(Mbc5::new(rom, ram_size, true))),
+        _ => return Err(EmuError::UnsupportedCartridgeType(cartridge_type))
+    }
+}
+
+// Reads from a 16k ROM bank. Bank numbers past the end of the ROM wrap around, as the unused
+// address lines aren't connected on smaller cartridges
+fn read_rom_bank(rom: &[u8], bank: usize, address: u16) -> u8 {
+    let bank_count = (rom.len() / ROM_BANK_SIZE).max(1);
+    let index = (bank % bank_count) * ROM_BANK_SIZE + (address as usize & (ROM_BANK_SIZE - 1));
+    match rom.get(index) {
+        Some(value) => return *value,
+        None => return 0xFF
+    }
+}
+
+// ROM whose banks each start with their own bank number, 16 bits little endian
+#[cfg(test)]
+pub(crate) fn numbered_rom(bank_count: usize) -> Vec<u8> {
+    let mut rom = vec![0; bank_count * ROM_BANK_SIZE];
+    for bank in 0..bank_count {
+        rom[bank * ROM_BANK_SIZE] = bank as u8;
+        rom[bank * ROM_BANK_SIZE + 1] = (bank >> 8) as u8;
+    }
+    return rom;
+}
+
+// Bank of a numbered_rom that a mapper has in the 16k window at 0x0000 or 0x4000
+#[cfg(test)]
+pub(crate) fn visible_bank(mapper: &dyn Mapper, window: u16) -> usize {
+    return mapper.read_rom(window) as usize | (mapper.read_rom(window + 1) as usize) << 8;
+}
+
+// Index into external RAM for an address in 0xA000 - 0xBFFF. RAM smaller than a bank (2k) is mirrored across it
+fn ram_index(ram: &[u8], bank: usize, address: u16) -> Option<usize> {
+    if ram.is_empty() {
+        return None;
+    }
+
+    let mut index = bank * RAM_BANK_SIZE + (address as usize & (RAM_BANK_SIZE - 1));
+    if ram.len() < RAM_BANK_SIZE {
+        index %= ram.len();
+    }
+
+    if index < ram.len() {
+        return Some(index);
+    }
+    return None;
+}
+
+fn save_ram(state: &mut StateWriter<Vec<u8>>, ram: &[u8]) -> io::Result<()> {
+    state.write_u32(ram.len() as u32)?;
+    state.write_bytes(ram)?;
+    Ok(())
+}
+
+fn load_ram(state: &mut StateReader<&[u8]>, ram: &mut [u8]) -> io::Result<()> {
+    let ram_size = state.read_u32()? as usize;
+    if ram_size != ram.len() {
+        return Err(io::Error::new(io::ErrorKind::InvalidData,
+            format!("state has {} bytes of external RAM, the cartridge has {}", ram_size, ram.len())));
+    }
+    state.read_bytes(ram)?;
+    Ok(())
+}
+
+// Copies a .sav file into external RAM
+fn load_ram_data(ram: &mut [u8], data: &[u8]) {
+    if data.len() < ram.len() {
+        warn!("Save data is {} bytes but the cartridge has {} bytes of RAM", data.len(), ram.len());
+    }
+
+    let length = data.len().min(ram.len());
+    ram[..length].copy_from_slice(&data[..length]);
+}
@@ -0,0 +1,201 @@
+#![allow(clippy::needless_return)]
+
+use std::io;
+
+use crate::mapper::{load_ram, load_ram_data, ram_index, read_rom_bank, save_ram, Mapper};
+use crate::savestate::{StateReader, StateWriter};
+
+pub struct Mbc1 {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    ram_enabled: bool,
+    rom_bank_register: u8,       // 5 bit ROM bank register, 0x2000 - 0x3FFF
+    secondary_bank_register: u8, // 2 bit RAM bank / upper ROM bank register, 0x4000 - 0x5FFF
+    memory_mode: u8              // 0x6000 - 0x7FFF, 1 = advanced banking
+}
+
+impl Mbc1 {
+    pub fn new(rom: Vec<u8>, ram_size: usize) -> Self {
+        Mbc1 {
+            rom,
+            ram: vec![0; ram_size],
+            ram_enabled: false,
+            rom_bank_register: 1,
+            secondary_bank_register: 0,
+            memory_mode: 0
+        }
+    }
+
+    // 0x4000 - 0x7FFF always gets the secondary register as bits 5-6 of the ROM bank. In mode 1 (advanced)
+    // the secondary register also picks the bank at 0x0000 - 0x3FFF (0x00/0x20/0x40/0x60) and the RAM bank,
+    // in mode 0 those are fixed at bank 0
+    fn rom_bank_0(&self) -> usize {
+        if self.memory_mode == 1 {
+            return (self.secondary_bank_register as usize) << 5;
+        }
+        return 0;
+    }
+
+    fn rom_bank(&self) -> usize {
+        return ((self.secondary_bank_register as usize) << 5) | self.rom_bank_register as usize;
+    }
+
+    fn ram_bank(&self) -> usize {
+        // 8k or smaller RAM only has the one bank
+        if self.memory_mode == 1 && self.ram.len() > 0x2000 {
+            return self.secondary_bank_register as usize;
+        }
+        return 0;
+    }
+}
+
+impl Mapper for Mbc1 {
+    fn read_rom(&self, address: u16) -> u8 {
+        if address < 0x4000 {
+            return read_rom_bank(&self.rom, self.rom_bank_0(), address);
+        }
+        return read_rom_bank(&self.rom, self.rom_bank(), address);
+    }
+
+    #[allow(clippy::needless_bool_assign)]
+    fn write_control(&mut self, address: u16, value: u8) {
+        match address {
+            0x0000..=0x1FFF => {
+                //TODO - trace
+                debug!("Updating external RAM - {:#04X}. 0x0A = enable, all else disable", value);
+
+                // Only the lower nibble is checked
+                if value & 0x0F == 0x0A {
+                    self.ram_enabled = true;
+                } else {
+                    self.ram_enabled = false;
+                }
+            },
+            0x2000..=0x3FFF => {
+                //TODO - trace
+                debug!("Updating ROM Bank {}", value);
+
+                // Only 5 bits are wired up, and 0 reads as 1 even if the upper bits are set
+                self.rom_bank_register = value & 0x1F;
+                if self.rom_bank_register == 0 {
+                    self.rom_bank_register = 1;
+                }
+            },
+            0x4000..=0x5FFF => {
+                trace!("Updating secondary bank register. {}", value & 3);
+                self.secondary_bank_register = value & 3;
+            },
+            _ => {
+                //TODO - trace
+                debug!("Memory Mode set to {}", value & 0x1);
+                self.memory_mode = value & 0x1;
+            }
+        }
+
+        trace!("MBC1 banks - ROM 0: {} ROM: {} RAM: {}", self.rom_bank_0(), self.rom_bank(), self.ram_bank());
+    }
+
+    fn read_ram(&self, address: u16) -> u8 {
+        if !self.ram_enabled {
+            return 0xFF;
+        }
+
+        match ram_index(&self.ram, self.ram_bank(), address) {
+            Some(index) => return self.ram[index],
+            None => return 0xFF
+        }
+    }
+
+    fn write_ram(&mut self, address: u16, value: u8) -> bool {
+        if !self.ram_enabled {
+            return false;
+        }
+
+        if let Some(index) = ram_index(&self.ram, self.ram_bank(), address) {
+            self.ram[index] = value;
+            return true;
+        }
+        return false;
+    }
+
+    fn save_data(&mut self) -> Vec<u8> {
+        return self.ram.clone();
+    }
+
+    fn load_save_data(&mut self, data: &[u8]) {
+        load_ram_data(&mut self.ram, data);
+    }
+
+    fn save_state(&self, state: &mut StateWriter<Vec<u8>>) -> io::Result<()> {
+        save_ram(state, &self.ram)?;
+        state.write_bool(self.ram_enabled)?;
+        state.write_u8(self.rom_bank_register)?;
+        state.write_u8(self.secondary_bank_register)?;
+        state.write_u8(self.memory_mode)?;
+        Ok(())
+    }
+
+    fn load_state(&mut self, state: &mut StateReader<&[u8]>) -> io::Result<()> {
+        load_ram(state, &mut self.ram)?;
+        self.ram_enabled = state.read_bool()?;
+        self.rom_bank_register = state.read_u8()?;
+        self.secondary_bank_register = state.read_u8()?;
+        self.memory_mode = state.read_u8()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::mapper::{numbered_rom, visible_bank};
+
+    #[test]
+    fn banks_0x20_0x40_and_0x60_map_to_the_next_bank() {
+        let mut mbc1 = Mbc1::new(numbered_rom(128), 0);
+        assert_eq!(visible_bank(&mbc1, 0x4000), 1);
+
+        // Only the low 5 bits count, so 0x20 is bank 0 and reads as 1
+        mbc1.write_control(0x2000, 0x20);
+        assert_eq!(visible_bank(&mbc1, 0x4000), 1);
+
+        for secondary in 1..4 {
+            mbc1.write_control(0x4000, secondary);
+            assert_eq!(visible_bank(&mbc1, 0x4000), (secondary as usize) << 5 | 1);
+        }
+
+        mbc1.write_control(0x2000, 0x1F);
+        assert_eq!(visible_bank(&mbc1, 0x4000), 0x7F);
+        assert_eq!(visible_bank(&mbc1, 0x0000), 0);
+    }
+
+    #[test]
+    fn advanced_mode_banks_the_first_window_on_large_roms() {
+        // 1 MiB, the secondary register is bits 5-6 of the bank and wraps past 0x3F
+        let mut mbc1 = Mbc1::new(numbered_rom(64), 0x8000);
+        mbc1.write_control(0x2000, 0x02);
+        mbc1.write_control(0x4000, 0x01);
+        mbc1.write_control(0x6000, 0x01);
+        assert_eq!(visible_bank(&mbc1, 0x0000), 0x20);
+        assert_eq!(visible_bank(&mbc1, 0x4000), 0x22);
+
+        mbc1.write_control(0x4000, 0x02);
+        assert_eq!(visible_bank(&mbc1, 0x0000), 0x00);
+        assert_eq!(visible_bank(&mbc1, 0x4000), 0x02);
+
+        // The secondary register picks the RAM bank too
+        mbc1.write_control(0x0000, 0x0A);
+        assert!(mbc1.write_ram(0xA000, 0x42));
+        mbc1.write_control(0x4000, 0x00);
+        assert_eq!(mbc1.read_ram(0xA000), 0x00);
+        mbc1.write_control(0x4000, 0x02);
+        assert_eq!(mbc1.read_ram(0xA000), 0x42);
+
+        // Back in simple mode the first window and RAM go back to bank 0
+        mbc1.write_control(0x6000, 0x00);
+        assert_eq!(visible_bank(&mbc1, 0x0000), 0x00);
+        assert_eq!(visible_bank(&mbc1, 0x4000), 0x02);
+        assert_eq!(mbc1.read_ram(0xA000), 0x00);
+    }
+}
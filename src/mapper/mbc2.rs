@@ -0,0 +1,148 @@
+#![allow(clippy::needless_return)]
+
+use std::io;
+
+use crate::mapper::{load_ram, load_ram_data, read_rom_bank, save_ram, Mapper};
+use crate::savestate::{StateReader, StateWriter};
+
+// MBC2 has 16 ROM banks and 512 x 4 bit RAM built into the chip
+pub struct Mbc2 {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    ram_enabled: bool,
+    rom_bank: u8
+}
+
+impl Mbc2 {
+    pub fn new(rom: Vec<u8>) -> Self {
+        Mbc2 {
+            rom,
+            ram: vec![0; 512],
+            ram_enabled: false,
+            rom_bank: 1
+        }
+    }
+}
+
+impl Mapper for Mbc2 {
+    fn read_rom(&self, address: u16) -> u8 {
+        if address < 0x4000 {
+            return read_rom_bank(&self.rom, 0, address);
+        }
+        return read_rom_bank(&self.rom, self.rom_bank as usize, address);
+    }
+
+    // There is a single register range at 0x0000 - 0x3FFF, address bit 8 selects what is written.
+    // Bit 8 clear enables/disables RAM, bit 8 set selects one of 16 ROM banks
+    #[allow(clippy::needless_bool_assign)]
+    fn write_control(&mut self, address: u16, value: u8) {
+        if address >= 0x4000 {
+            trace!("Tried to write {:#04X} to {:#06X}, MBC2 has no register there.", value, address);
+            return;
+        }
+
+        if address & 0x100 == 0 {
+            //TODO - trace
+            debug!("Updating external RAM - {:#04X}. 0x0A = enable, all else disable", value);
+
+            if value & 0x0F == 0x0A {
+                self.ram_enabled = true;
+            } else {
+                self.ram_enabled = false;
+            }
+        } else {
+            //TODO - trace
+            debug!("Updating ROM Bank {}", value);
+
+            self.rom_bank = value & 0x0F;
+            if self.rom_bank == 0 {
+                self.rom_bank = 1;
+            }
+        }
+    }
+
+    // The 512 nibbles are mirrored across the whole range. Only the lower nibble exists,
+    // the upper bits read as 1s
+    fn read_ram(&self, address: u16) -> u8 {
+        if !self.ram_enabled {
+            return 0xFF;
+        }
+        return self.ram[(address & 0x1FF) as usize] | 0xF0;
+    }
+
+    fn write_ram(&mut self, address: u16, value: u8) -> bool {
+        if !self.ram_enabled {
+            return false;
+        }
+
+        self.ram[(address & 0x1FF) as usize] = value & 0x0F;
+        return true;
+    }
+
+    fn save_data(&mut self) -> Vec<u8> {
+        return self.ram.clone();
+    }
+
+    fn load_save_data(&mut self, data: &[u8]) {
+        load_ram_data(&mut self.ram, data);
+    }
+
+    fn save_state(&self, state: &mut StateWriter<Vec<u8>>) -> io::Result<()> {
+        save_ram(state, &self.ram)?;
+        state.write_bool(self.ram_enabled)?;
+        state.write_u8(self.rom_bank)?;
+        Ok(())
+    }
+
+    fn load_state(&mut self, state: &mut StateReader<&[u8]>) -> io::Result<()> {
+        load_ram(state, &mut self.ram)?;
+        self.ram_enabled = state.read_bool()?;
+        self.rom_bank = state.read_u8()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::mapper::{numbered_rom, visible_bank};
+
+    #[test]
+    fn address_bit_8_picks_the_register() {
+        let mut mbc2 = Mbc2::new(numbered_rom(16));
+
+        // Bit 8 set: ROM bank, wherever it is in 0x0000 - 0x3FFF
+        mbc2.write_control(0x0100, 0x05);
+        assert_eq!(visible_bank(&mbc2, 0x4000), 5);
+        mbc2.write_control(0x3FFF, 0x1C);
+        assert_eq!(visible_bank(&mbc2, 0x4000), 0x0C);
+        mbc2.write_control(0x2100, 0x00);
+        assert_eq!(visible_bank(&mbc2, 0x4000), 1);
+
+        // Bit 8 clear: RAM enable, which leaves the ROM bank alone
+        mbc2.write_control(0x3EFF, 0x0A);
+        assert!(mbc2.write_ram(0xA000, 0x03));
+        assert_eq!(mbc2.read_ram(0xA000), 0xF3);
+        assert_eq!(visible_bank(&mbc2, 0x4000), 1);
+
+        mbc2.write_control(0x0000, 0x00);
+        assert!(!mbc2.write_ram(0xA000, 0x04));
+        assert_eq!(mbc2.read_ram(0xA000), 0xFF);
+    }
+
+    #[test]
+    fn ram_is_512_nibbles_echoed_across_the_window() {
+        let mut mbc2 = Mbc2::new(numbered_rom(16));
+        mbc2.write_control(0x0000, 0x0A);
+
+        mbc2.write_ram(0xA010, 0xA5);
+        assert_eq!(mbc2.read_ram(0xA010), 0xF5);
+        for echo in [0xA210, 0xA410, 0xB010, 0xBE10].iter() {
+            assert_eq!(mbc2.read_ram(*echo), 0xF5, "{:#06X}", echo);
+        }
+
+        mbc2.write_ram(0xBFFF, 0x0E);
+        assert_eq!(mbc2.read_ram(0xA1FF), 0xFE);
+    }
+}
@@ -0,0 +1,244 @@
+#![allow(clippy::needless_return)]
+
+use std::io;
+
+use crate::mapper::{load_ram, load_ram_data, ram_index, read_rom_bank, save_ram, Mapper};
+use crate::rtc::{Rtc, RTC_DAY_HIGH, RTC_SAVE_SIZE, RTC_SECONDS};
+use crate::savestate::{StateReader, StateWriter};
+
+// MBC3 has 128 ROM banks, 4 RAM banks and on some cartridges a real time clock
+pub struct Mbc3 {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    rtc: Option<Rtc>,
+    ram_enabled: bool,  // Also enables the RTC registers
+    rom_bank: u8,
+    ram_bank: u8,
+    rtc_register: u8    // RTC register mapped at 0xA000 - 0xBFFF instead of RAM, 0 for none
+}
+
+impl Mbc3 {
+    pub fn new(rom: Vec<u8>, ram_size: usize, has_rtc: bool) -> Self {
+        let mut rtc = None;
+        if has_rtc {
+            rtc = Some(Rtc::new());
+        }
+
+        Mbc3 {
+            rom,
+            ram: vec![0; ram_size],
+            rtc,
+            ram_enabled: false,
+            rom_bank: 1,
+            ram_bank: 0,
+            rtc_register: 0
+        }
+    }
+}
+
+impl Mapper for Mbc3 {
+    fn read_rom(&self, address: u16) -> u8 {
+        if address < 0x4000 {
+            return read_rom_bank(&self.rom, 0, address);
+        }
+        return read_rom_bank(&self.rom, self.rom_bank as usize, address);
+    }
+
+    #[allow(clippy::needless_bool_assign)]
+    fn write_control(&mut self, address: u16, value: u8) {
+        match address {
+            0x0000..=0x1FFF => {
+                //TODO - trace
+                debug!("Updating external RAM - {:#04X}. 0x0A = enable, all else disable", value);
+
+                if value & 0x0F == 0x0A {
+                    self.ram_enabled = true;
+                } else {
+                    self.ram_enabled = false;
+                }
+            },
+            0x2000..=0x3FFF => {
+                //TODO - trace
+                debug!("Updating ROM Bank {}", value);
+
+                // 7 bit bank number, 0 maps to 1
+                self.rom_bank = value & 0x7F;
+                if self.rom_bank == 0 {
+                    self.rom_bank = 1;
+                }
+            },
+            0x4000..=0x5FFF => {
+                // 0x00 - 0x03 map a RAM bank, 0x08 - 0x0C map an RTC register
+                match value {
+                    0x00..=0x03 => {
+                        trace!("Updating active RAM bank. {}", value);
+                        self.ram_bank = value;
+                        self.rtc_register = 0;
+                    },
+                    RTC_SECONDS..=RTC_DAY_HIGH if self.rtc.is_some() => {
+                        trace!("Mapping RTC register {:#04X}", value);
+                        self.rtc_register = value;
+                    },
+                    _ => debug!("Tried to map unknown RAM bank/RTC register {:#04X}", value)
+                }
+            },
+            _ => {
+                // Latches the clock rather than setting a mode
+                match self.rtc.as_mut() {
+                    Some(rtc) => rtc.write_latch(value),
+                    None => trace!("Tried to latch the clock on an MBC3 without an RTC.")
+                }
+            }
+        }
+    }
+
+    fn read_ram(&self, address: u16) -> u8 {
+        if !self.ram_enabled {
+            return 0xFF;
+        }
+
+        if self.rtc_register != 0 {
+            match self.rtc.as_ref() {
+                Some(rtc) => return rtc.read(self.rtc_register),
+                None => return 0xFF
+            }
+        }
+
+        match ram_index(&self.ram, self.ram_bank as usize, address) {
+            Some(index) => return self.ram[index],
+            None => return 0xFF
+        }
+    }
+
+    fn write_ram(&mut self, address: u16, value: u8) -> bool {
+        if !self.ram_enabled {
+            return false;
+        }
+
+        // The clock is saved along with the RAM, so setting it counts as a store
+        if self.rtc_register != 0 {
+            if let Some(rtc) = self.rtc.as_mut() {
+                rtc.write(self.rtc_register, value);
+                return true;
+            }
+            return false;
+        }
+
+        if let Some(index) = ram_index(&self.ram, self.ram_bank as usize, address) {
+            self.ram[index] = value;
+            return true;
+        }
+        return false;
+    }
+
+    // External RAM, followed by the clock for cartridges with an RTC
+    fn save_data(&mut self) -> Vec<u8> {
+        let mut data = self.ram.clone();
+        if let Some(rtc) = self.rtc.as_mut() {
+            data.extend_from_slice(&rtc.save_data());
+        }
+        return data;
+    }
+
+    fn load_save_data(&mut self, data: &[u8]) {
+        load_ram_data(&mut self.ram, data);
+
+        if let Some(rtc) = self.rtc.as_mut() {
+            if data.len() > self.ram.len() {
+                rtc.load_data(&data[self.ram.len()..]);
+            } else {
+                warn!("Save data has no clock, expected {} bytes of RTC data after RAM", RTC_SAVE_SIZE);
+            }
+        }
+    }
+
+    fn tick_rtc(&mut self) {
+        if let Some(rtc) = self.rtc.as_mut() {
+            rtc.update();
+        }
+    }
+
+    fn save_state(&self, state: &mut StateWriter<Vec<u8>>) -> io::Result<()> {
+        save_ram(state, &self.ram)?;
+        state.write_bool(self.ram_enabled)?;
+        state.write_u8(self.rom_bank)?;
+        state.write_u8(self.ram_bank)?;
+        state.write_u8(self.rtc_register)?;
+        if let Some(rtc) = self.rtc.as_ref() {
+            rtc.save_state(state)?;
+        }
+        Ok(())
+    }
+
+    fn load_state(&mut self, state: &mut StateReader<&[u8]>) -> io::Result<()> {
+        load_ram(state, &mut self.ram)?;
+        self.ram_enabled = state.read_bool()?;
+        self.rom_bank = state.read_u8()?;
+        self.ram_bank = state.read_u8()?;
+        self.rtc_register = state.read_u8()?;
+        if let Some(rtc) = self.rtc.as_mut() {
+            rtc.load_state(state)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::mapper::{numbered_rom, visible_bank};
+    use crate::rtc::RTC_MINUTES;
+
+    #[test]
+    fn rom_and_ram_banks() {
+        let mut mbc3 = Mbc3::new(numbered_rom(128), 0x8000, false);
+        mbc3.write_control(0x2000, 0x00);
+        assert_eq!(visible_bank(&mbc3, 0x4000), 1);
+        mbc3.write_control(0x2000, 0xFF);
+        assert_eq!(visible_bank(&mbc3, 0x4000), 0x7F);
+        assert_eq!(visible_bank(&mbc3, 0x0000), 0);
+
+        mbc3.write_control(0x0000, 0x0A);
+        for bank in 0..4 {
+            mbc3.write_control(0x4000, bank);
+            mbc3.write_ram(0xA000, bank + 0x10);
+        }
+        mbc3.write_control(0x4000, 0x02);
+        assert_eq!(mbc3.read_ram(0xA000), 0x12);
+
+        // No clock on this cartridge, so RTC registers don't unmap the RAM
+        mbc3.write_control(0x4000, RTC_MINUTES);
+        assert_eq!(mbc3.read_ram(0xA000), 0x12);
+    }
+
+    #[test]
+    fn clock_registers_replace_ram_while_selected() {
+        let mut mbc3 = Mbc3::new(numbered_rom(4), 0x2000, true);
+        mbc3.write_control(0x0000, 0x0A);
+        mbc3.write_ram(0xA000, 0x42);
+
+        // Halt the clock so the host's time doesn't move it during the test
+        mbc3.write_control(0x4000, RTC_DAY_HIGH);
+        assert!(mbc3.write_ram(0xA000, 0x40));
+        mbc3.write_control(0x4000, RTC_MINUTES);
+        assert!(mbc3.write_ram(0xA000, 5));
+        assert_eq!(mbc3.read_ram(0xA000), 5);
+
+        // Latching through 0x6000 - 0x7FFF leaves a halted clock where it was
+        mbc3.write_control(0x6000, 0x00);
+        mbc3.write_control(0x6000, 0x01);
+        assert_eq!(mbc3.read_ram(0xA000), 5);
+        mbc3.write_control(0x4000, RTC_DAY_HIGH);
+        assert_eq!(mbc3.read_ram(0xA000), 0x40);
+
+        mbc3.write_control(0x4000, 0x00);
+        assert_eq!(mbc3.read_ram(0xA000), 0x42);
+
+        // RAM disable covers the clock too
+        mbc3.write_control(0x4000, RTC_MINUTES);
+        mbc3.write_control(0x0000, 0x00);
+        assert!(!mbc3.write_ram(0xA000, 6));
+        assert_eq!(mbc3.read_ram(0xA000), 0xFF);
+    }
+}
@@ -0,0 +1,181 @@
+#![allow(clippy::needless_return)]
+
+use std::io;
+
+use crate::mapper::{load_ram, load_ram_data, ram_index, read_rom_bank, save_ram, Mapper};
+use crate::savestate::{StateReader, StateWriter};
+
+// MBC5 has up to 512 ROM banks, 16 RAM banks and on some cartridges a rumble motor
+pub struct Mbc5 {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    has_rumble: bool,
+    ram_enabled: bool,
+    rom_bank: u16,      // 9 bit bank number
+    ram_bank: u8,
+    is_rumbling: bool
+}
+
+impl Mbc5 {
+    pub fn new(rom: Vec<u8>, ram_size: usize, has_rumble: bool) -> Self {
+        Mbc5 {
+            rom,
+            ram: vec![0; ram_size],
+            has_rumble,
+            ram_enabled: false,
+            rom_bank: 1,
+            ram_bank: 0,
+            is_rumbling: false
+        }
+    }
+}
+
+impl Mapper for Mbc5 {
+    fn read_rom(&self, address: u16) -> u8 {
+        if address < 0x4000 {
+            return read_rom_bank(&self.rom, 0, address);
+        }
+        return read_rom_bank(&self.rom, self.rom_bank as usize, address);
+    }
+
+    #[allow(clippy::needless_bool_assign)]
+    fn write_control(&mut self, address: u16, value: u8) {
+        match address {
+            0x0000..=0x1FFF => {
+                //TODO - trace
+                debug!("Updating external RAM - {:#04X}. 0x0A = enable, all else disable", value);
+
+                if value & 0x0F == 0x0A {
+                    self.ram_enabled = true;
+                } else {
+                    self.ram_enabled = false;
+                }
+            },
+            // The low 8 bits of the ROM bank are at 0x2000 - 0x2FFF and bit 8 at 0x3000 - 0x3FFF.
+            // Unlike the other MBCs, bank 0 can be mapped at 0x4000 - 0x7FFF
+            0x2000..=0x2FFF => {
+                self.rom_bank = (self.rom_bank & 0x100) | value as u16;
+                //TODO - trace
+                debug!("Updating ROM Bank {}", self.rom_bank);
+            },
+            0x3000..=0x3FFF => {
+                self.rom_bank = (self.rom_bank & 0xFF) | (((value & 0x01) as u16) << 8);
+                //TODO - trace
+                debug!("Updating ROM Bank {}", self.rom_bank);
+            },
+            0x4000..=0x5FFF => {
+                if self.has_rumble {
+                    // Bit 3 drives the rumble motor instead of selecting a RAM bank
+                    self.ram_bank = value & 0x07;
+                    self.is_rumbling = value & 0x08 != 0;
+                } else {
+                    self.ram_bank = value & 0x0F;
+                }
+                trace!("Updating active RAM bank. {}", self.ram_bank);
+            },
+            _ => {
+                trace!("Tried to write {:#04X} to {:#06X}, MBC5 has no register there.", value, address);
+            }
+        }
+    }
+
+    fn read_ram(&self, address: u16) -> u8 {
+        if !self.ram_enabled {
+            return 0xFF;
+        }
+
+        match ram_index(&self.ram, self.ram_bank as usize, address) {
+            Some(index) => return self.ram[index],
+            None => return 0xFF
+        }
+    }
+
+    fn write_ram(&mut self, address: u16, value: u8) -> bool {
+        if !self.ram_enabled {
+            return false;
+        }
+
+        if let Some(index) = ram_index(&self.ram, self.ram_bank as usize, address) {
+            self.ram[index] = value;
+            return true;
+        }
+        return false;
+    }
+
+    fn save_data(&mut self) -> Vec<u8> {
+        return self.ram.clone();
+    }
+
+    fn load_save_data(&mut self, data: &[u8]) {
+        load_ram_data(&mut self.ram, data);
+    }
+
+    fn is_rumbling(&self) -> bool {
+        return self.is_rumbling;
+    }
+
+    fn save_state(&self, state: &mut StateWriter<Vec<u8>>) -> io::Result<()> {
+        save_ram(state, &self.ram)?;
+        state.write_bool(self.ram_enabled)?;
+        state.write_u16(self.rom_bank)?;
+        state.write_u8(self.ram_bank)?;
+        state.write_bool(self.is_rumbling)?;
+        Ok(())
+    }
+
+    fn load_state(&mut self, state: &mut StateReader<&[u8]>) -> io::Result<()> {
+        load_ram(state, &mut self.ram)?;
+        self.ram_enabled = state.read_bool()?;
+        self.rom_bank = state.read_u16()?;
+        self.ram_bank = state.read_u8()?;
+        self.is_rumbling = state.read_bool()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::mapper::{numbered_rom, visible_bank};
+
+    #[test]
+    fn nine_bit_rom_bank() {
+        let mut mbc5 = Mbc5::new(numbered_rom(512), 0, false);
+        assert_eq!(visible_bank(&mbc5, 0x4000), 1);
+
+        mbc5.write_control(0x2000, 0xFF);
+        assert_eq!(visible_bank(&mbc5, 0x4000), 0x0FF);
+        mbc5.write_control(0x3000, 0x01);
+        assert_eq!(visible_bank(&mbc5, 0x4000), 0x1FF);
+
+        // Each half of the bank number keeps the other when it's written
+        mbc5.write_control(0x2FFF, 0x23);
+        assert_eq!(visible_bank(&mbc5, 0x4000), 0x123);
+        mbc5.write_control(0x3FFF, 0xFE);
+        assert_eq!(visible_bank(&mbc5, 0x4000), 0x023);
+
+        // Bank 0 isn't remapped to 1
+        mbc5.write_control(0x2000, 0x00);
+        assert_eq!(visible_bank(&mbc5, 0x4000), 0);
+        assert_eq!(visible_bank(&mbc5, 0x0000), 0);
+    }
+
+    #[test]
+    fn rumble_takes_ram_bank_bit_3() {
+        let mut mbc5 = Mbc5::new(numbered_rom(4), 0x20000, false);
+        mbc5.write_control(0x0000, 0x0A);
+        mbc5.write_control(0x4000, 0x0F);
+        mbc5.write_ram(0xA000, 0x42);
+        mbc5.write_control(0x4000, 0x07);
+        assert_eq!(mbc5.read_ram(0xA000), 0x00);
+
+        let mut mbc5 = Mbc5::new(numbered_rom(4), 0x10000, true);
+        mbc5.write_control(0x0000, 0x0A);
+        mbc5.write_control(0x4000, 0x07);
+        mbc5.write_ram(0xA000, 0x42);
+        mbc5.write_control(0x4000, 0x0F);
+        assert!(mbc5.is_rumbling());
+        assert_eq!(mbc5.read_ram(0xA000), 0x42);
+    }
+}
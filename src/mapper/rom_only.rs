@@ -0,0 +1,52 @@
+#![allow(clippy::needless_return)]
+
+use std::io;
+
+use crate::mapper::{read_rom_bank, Mapper};
+use crate::savestate::{StateReader, StateWriter};
+
+// 32k cartridge with no MBC, the whole ROM is always mapped
+pub struct RomOnly {
+    rom: Vec<u8>
+}
+
+impl RomOnly {
+    pub fn new(rom: Vec<u8>) -> Self {
+        RomOnly {
+            rom
+        }
+    }
+}
+
+impl Mapper for RomOnly {
+    fn read_rom(&self, address: u16) -> u8 {
+        return read_rom_bank(&self.rom, (address >> 14) as usize, address);
+    }
+
+    fn write_control(&mut self, address: u16, value: u8) {
+        trace!("Tried to write {:#04X} to {:#06X} on a ROM_ONLY cartridge.", value, address);
+    }
+
+    fn read_ram(&self, _address: u16) -> u8 {
+        return 0xFF;
+    }
+
+    fn write_ram(&mut self, address: u16, value: u8) -> bool {
+        trace!("Tried to write {:#04X} to external RAM at {:#06X} on a cartridge with no RAM.", value, address);
+        return false;
+    }
+
+    fn save_data(&mut self) -> Vec<u8> {
+        return Vec::new();
+    }
+
+    fn load_save_data(&mut self, _data: &[u8]) {}
+
+    fn save_state(&self, _state: &mut StateWriter<Vec<u8>>) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn load_state(&mut self, _state: &mut StateReader<&[u8]>) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -1,18 +1,25 @@
+#![allow(clippy::needless_return)]
+
 use std::fs::File;
 use std::io;
-use std::io::Read;
-use std::process::exit;
-
-use crate::cartridge::{ROM_ONLY, MBC1, MBC1_RAM, MBC1_RAM_BATT};
+use std::io::{Read, Write};
+use crate::archive::read_rom_file;
+use crate::cartridge::Cartridge;
+use crate::error::EmuError;
 use crate::gpu::GPU;
+use crate::interrupts::InterruptController;
+use crate::mapper::{new_mapper, ROM_BANK_SIZE};
+use crate::savestate::{StateReader, StateWriter};
+use crate::serial::Serial;
 use crate::timer::Timer;
 
+const OAM_SIZE: usize = 160;
+
 pub struct MMU {
     pub gpu: GPU,
+    pub cartridge: Cartridge,           // ROM + external RAM, 0x0000 - 0x7FFF and 0xA000 - 0xBFFF, banked by the cartridge's mapper
     bios: [u8; 256],
-    rom_banks: Box<[Vec<u8>]>,          // 16k ROM Banks,   0x0000 - 0x7FFF , ROM Bank 0 + switchable ROM bank
 //  vram: [u8; 8192],                   // 8k Video RAM,    0x8000 - 0x9FFF , Video RAM, stored in GPU
-    eram: [u8; 8192],                   // 8k External RAM, 0xA000 - 0xBFFF , switchable RAM bank
     wram: [u8; 8192],                   // 8k Working RAM,  0xC000 - 0xDFFF , internal RAM
                                         // 8k Working RAM,  0xE000 - 0xFDFF , copy of internal RAM
 //  oam:  [u8;  160],                   // Object Attr Mem, 0xFE00 - 0xFE9F , Sprites, stored in GPU
@@ -20,76 +27,137 @@ pub struct MMU {
     io_ports: [u8; 64],                 // I/O Ports        0xFF00 - 0xFF3F , I/O Ports
                                         // Empty            0xFF40 - 0xFF7F , GPU Registers
     zram: [u8;  127],                   // Zero Page RAM,   0xFF80 - 0xFFFE
-    pub interrupt_enable_register: u8,  // Int Enable Reg,  0xFFFF          , Interrupt Enable/Disable Register
+    pub interrupts: InterruptController, // Int Flags 0xFF0F and Int Enable Reg 0xFFFF
 
     pub timer: Timer,
+    pub serial: Serial,
 
-    active_rom_bank: u8,
-    active_ram_bank: u8,
-    active_external_ram: bool,
-    rom_size: u8,
-    memory_mode: u8,
-    cartridge_type: u8,
-    pub is_bios_mapped: bool
+    pub is_bios_mapped: bool,
+    pub is_cgb: bool,                   // The cartridge supports CGB, which enables KEY1
+    pub is_speed_switch_armed: bool,    // KEY1 bit 0, the next STOP switches speed
+    pub is_double_speed: bool,          // KEY1 bit 7, the CPU and timer run at twice the normal clock
+    pub is_external_ram_dirty: bool,    // External RAM has been written since it was last saved
+    pub has_entered_vblank: bool,       // The GPU entered VBlank during the last instruction
+
+    is_dma_active: bool,                // An OAM DMA started by writing 0xFF46 is copying, the CPU can only reach HRAM
+    dma_source: u16,                    // Address the DMA copies OAM from
+    dma_index: u8                       // Next byte of OAM the DMA copies
 }
 
 impl MMU {
+    #[allow(clippy::new_without_default)]
     pub fn new() -> Self {
         debug!("Initializing MMU");
 
         MMU {
             gpu: GPU::new(),
+            cartridge: Cartridge::new(),
             bios: [0; 256],
-            rom_banks: vec![vec![0u8; 16384]; 128].into_boxed_slice(),
-            eram: [0; 8192],
             wram: [0; 8192],
             io_ports: [0; 64],
             zram: [0; 127],
-            interrupt_enable_register: 0,
+            interrupts: InterruptController::new(),
             timer: Timer::new(),
-            active_rom_bank: 1,
-            active_ram_bank: 0,
-            active_external_ram: false,
-            rom_size: 0,
-            memory_mode: 0,
-            cartridge_type: ROM_ONLY,
-            is_bios_mapped: false
+            serial: Serial::new(),
+            is_bios_mapped: false,
+            is_cgb: false,
+            is_speed_switch_armed: false,
+            is_double_speed: false,
+            is_external_ram_dirty: false,
+            has_entered_vblank: false,
+            is_dma_active: false,
+            dma_source: 0,
+            dma_index: 0
         }
     }
 
-    pub fn load_bios(&mut self, path: &str) -> io::Result<()> {
+    pub fn load_bios(&mut self, path: &str) -> Result<(), EmuError> {
         debug!("Loading BIOS from {}", path);
 
         let mut file = File::open(path)?;
         let mut buffer = Vec::new();
         let file_size = file.read_to_end(&mut buffer)?;
-
-        for i in 0..file_size {
-            self.bios[i] = buffer[i];
+        if file_size > self.bios.len() {
+            return Err(EmuError::BadBiosSize(file_size));
         }
 
+        self.bios[..file_size].copy_from_slice(&buffer[..file_size]);
+
         Ok(())
     }
 
-    pub fn load_rom(&mut self, path: &str) -> io::Result<()>  {
+    // rom_entry picks the file to load when the ROM is in a zip, see archive::read_rom_file
+    pub fn load_rom(&mut self, path: &str, rom_entry: Option<&str>) -> Result<(), EmuError> {
         debug!("Loading ROM: {}", path);
 
-        let mut file = File::open(path)?;
-        let mut buffer = Vec::new();
-        file.read_to_end(&mut buffer)?;
+        let mut buffer = read_rom_file(path, rom_entry)?;
 
-        self.set_rom_size(buffer[0x148]);
+        let mut cartridge = Cartridge::from_bytes(&buffer)?;
 
-        for rom_banks in 0..self.rom_size {
-            for i in 0..0x4000 {
-                self.rom_banks[rom_banks as usize][i as usize] = buffer[i + (rom_banks as u32 * 0x4000) as usize];
-            }
+        // Banks are sized from the file rather than the header, so sloppy homebrew headers still work.
+        // Real cartridges always have a power of two number of banks and ignore the address lines above
+        // that, so the image is padded out with 0xFF (open bus) and the mapper mirrors bank numbers past the end
+        if buffer.len() != cartridge.rom_size_bytes {
+            warn!("Header says the ROM is {} bytes but the file is {} bytes, sizing banks from the file",
+                cartridge.rom_size_bytes, buffer.len());
         }
+        let bank_count = buffer.len().div_ceil(ROM_BANK_SIZE).next_power_of_two().max(2);
+        buffer.resize(bank_count * ROM_BANK_SIZE, 0xFF);
+
+        cartridge.mapper = new_mapper(cartridge.cartridge_type, buffer, cartridge.ram_size_bytes)?;
+
+        self.is_cgb = cartridge.gameboy_type & 0x80 != 0;
+        self.cartridge = cartridge;
+        self.is_external_ram_dirty = false;
 
         Ok(())
     }
 
+    // Runs the timer, serial port, OAM DMA and LCD for one machine cycle. The CPU calls this before each memory
+    // access it makes, so the rest of the system sees reads and writes on the cycle they happen
+    pub fn tick(&mut self) {
+        self.timer.tick(&mut self.interrupts);
+        self.serial.step(4, &mut self.interrupts);
+        self.step_dma();
+
+        // In double speed mode the CPU, timer and serial port see twice as many cycles as the LCD
+        let gpu_cycles = if self.is_double_speed { 2 } else { 4 };
+        if self.gpu.tick(gpu_cycles, &mut self.interrupts) {
+            self.has_entered_vblank = true;
+        }
+    }
+
+    // OAM DMA copies one byte per machine cycle, 160 in all
+    fn step_dma(&mut self) {
+        if !self.is_dma_active {
+            return;
+        }
+
+        let value = self.read_mapped_byte(self.dma_source + self.dma_index as u16);
+        self.gpu.write_oam(self.dma_index, value);
+        self.dma_index += 1;
+        if self.dma_index as usize == OAM_SIZE {
+            self.is_dma_active = false;
+        }
+    }
+
+    // Sources from 0xE000 up read working RAM, like the echo at 0xE000 - 0xFDFF
+    fn start_dma(&mut self, page: u8) {
+        self.is_dma_active = true;
+        self.dma_source = if page >= 0xE0 { (page as u16 - 0x20) << 8 } else { (page as u16) << 8 };
+        self.dma_index = 0;
+    }
+
+    // While OAM DMA runs the bus is busy and the CPU can only reach HRAM. Everything else reads 0xFF
     pub fn read_byte(&mut self, address: u16) -> u8 {
+        if self.is_dma_active && !is_hram(address) {
+            return 0xFF;
+        }
+        return self.read_mapped_byte(address);
+    }
+
+    #[allow(clippy::manual_range_patterns)]
+    fn read_mapped_byte(&mut self, address: u16) -> u8 {
         if self.is_bios_mapped && address <= 0xFF {
             return self.bios[address as usize];
         } else {
@@ -100,21 +168,15 @@ impl MMU {
             let addr_nibble_4 =  address & 0x000F;
 
             match addr_nibble_1 {
-                0x0 | 0x1 | 0x2 | 0x3 => { // ROM Bank 0
-                    return self.rom_banks[0][address as usize];
-                },
+                0x0 | 0x1 | 0x2 | 0x3 | // ROM Bank 0
                 0x4 | 0x5 | 0x6 | 0x7 => { // Switchable ROM Bank
-                    return self.rom_banks[self.active_rom_bank as usize][(address - 0x4000) as usize];
+                    return self.cartridge.mapper.read_rom(address);
                 },
                 0x8 | 0x9 => { // Video RAM
                     return self.gpu.read_vram(address);
                 },
                 0xA | 0xB => { // External RAM (switchable RAM bank)
-                    if self.active_external_ram {
-                        return self.eram[(address - 0xA000) as usize];
-                    } else {
-                        return 0;
-                    }
+                    return self.cartridge.mapper.read_ram(address);
                 },
                 0xC | 0xD => { // Working RAM (internal RAM)
                     return self.wram[(address - 0xC000) as usize];
@@ -145,12 +207,10 @@ impl MMU {
                                     return self.gpu.input.read() | 0xC0; // or'd with C0 because Bit 6/7 are unmapped, unmapped bits always return as 1
                                 },
                                 (0x0, 0x1) => { // Serial Bus
-                                    //TODO - Serial Bus
-                                    return self.io_ports[(address - 0xFF00) as usize];
+                                    return self.serial.data;
                                 },
                                 (0x0, 0x2) => { // Serial Control
-                                    //TODO - Serial Control
-                                    return self.io_ports[(address - 0xFF00) as usize] | 0x7E; // or'd with 7E because Bits 1-6 are unmapped, unmapped bits always return as 1
+                                    return self.serial.read_control();
                                 },
                                 (0x0, 0x4) => { // Timer - DIV
                                     return (self.timer.div >> 8) as u8;
@@ -164,9 +224,8 @@ impl MMU {
                                 (0x0, 0x7) => { // Timer - TAC
                                     return self.timer.tac | 0xF8; // or'd with F8 because Bits 3-7 are unmapped, unmapped bits always return as 1
                                 },
-                                (0x0, 0xF) => {
-                                    // TODO - Make Interrupt Flag Request a variable
-                                    return self.io_ports[(address - 0xFF00) as usize] | 0xE0; // or'd with E0 because Bits 5-7 are unmapped, unmapped bits always return as 1
+                                (0x0, 0xF) => { // Interrupt Flags
+                                    return self.interrupts.read_flags();
                                 },
                                 (0x1, 0x0) => { // APU - NR10
                                     //TODO - APU
@@ -261,7 +320,13 @@ impl MMU {
                                     return self.gpu.read_register(address) | 0x80; // or'd with 80 because Bit 7 is unmapped, unmapped bits always return as 1
                                 },
                                 (0xF, 0xF) => { // Interrupt Enable Register
-                                    return self.interrupt_enable_register;
+                                    return self.interrupts.enable;
+                                },
+                                (0x4, 0xD) => { // KEY1, CGB speed switch
+                                    if !self.is_cgb {
+                                        return 0xFF;
+                                    }
+                                    return (self.is_double_speed as u8) << 7 | self.is_speed_switch_armed as u8 | 0x7E; // or'd with 7E because Bits 1-6 are unmapped, unmapped bits always return as 1
                                 },
                                 (0x0, _) | (0x1, _) | (0x2, _)  => { // Unused I/O Ports, return 0xFF
                                     return 0xFF;
@@ -287,15 +352,20 @@ impl MMU {
             }
         }
 
-        error!("Tried to read a byte from unmapped address {:#X}", address);
-        exit(1);
+        return 0xFF;
     }
 
     pub fn read_word(&mut self, address: u16) -> u16 {
         return self.read_byte(address) as u16 | ((self.read_byte(address + 1) as u16) << 8);
     }
 
+    #[allow(clippy::manual_range_patterns)]
     pub fn write_byte(&mut self, address: u16, value: u8) {
+        if self.is_dma_active && !is_hram(address) {
+            trace!("Dropped a write of {:#04X} to {:#06X} during OAM DMA", value, address);
+            return;
+        }
+
         if self.is_bios_mapped && address < 0xFF {
             warn!("Tried to overwrite BIOS ROM");
             return;
@@ -307,24 +377,9 @@ impl MMU {
             let addr_nibble_4 =  address & 0x000F;
 
             match addr_nibble_1 {
-                0x0 | 0x1 => {
-                    trace!("Updating external RAM setting.");
-                    self.update_external_ram(value);
-                    return;
-                },
-                0x2 | 0x3 => {
-                    trace!("Updating active ROM bank.");
-                    self.update_active_rom_bank(value);
-                    return;
-                },
-                0x4 | 0x5 => {
-                    trace!("Updating ROM/RAM bank.");
-                    self.update_active_rom_ram_bank(value);
-                    return;
-                },
-                0x6 | 0x7 => {
-                    trace!("Updating memory model.");
-                    self.update_memory_mode(value);
+                0x0 | 0x1 | 0x2 | 0x3 |
+                0x4 | 0x5 | 0x6 | 0x7 => { // Cartridge bank switching registers
+                    self.cartridge.mapper.write_control(address, value);
                     return;
                 },
                 0x8 | 0x9 => { // Video RAM
@@ -332,8 +387,8 @@ impl MMU {
                     return;
                 },
                 0xA | 0xB => { // External RAM (switchable RAM bank)
-                    if self.active_external_ram {
-                        self.eram[(address - 0xA000) as usize] = value;
+                    if self.cartridge.mapper.write_ram(address, value) {
+                        self.is_external_ram_dirty = true;
                     }
                     return;
                 },
@@ -370,25 +425,42 @@ impl MMU {
                                     self.gpu.input.write(value);
                                     return;
                                 },
+                                (0x0, 0x1) => { // Serial Bus
+                                    self.serial.data = value;
+                                    return;
+                                },
+                                (0x0, 0x2) => { // Serial Control
+                                    self.serial.write_control(value);
+                                    return;
+                                },
                                 (0x0, 0x4) => { // Timer - DIV
-                                    self.timer.div = 0;
+                                    self.timer.write_div();
                                     return;
                                 },
                                 (0x0, 0x5) => { // Timer - TIMA
-                                    self.timer.tima = value;
+                                    self.timer.write_tima(value);
                                     return;
                                 },
                                 (0x0, 0x6) => { // Timer - TMA
-                                    self.timer.tma = value;
+                                    self.timer.write_tma(value);
                                     return;
                                 },
                                 (0x0, 0x7) => { // Timer - TAC
-                                    self.timer.tac = value & 0x7;
-                                    self.timer.update();
+                                    self.timer.write_tac(value);
+                                    return;
+                                },
+                                (0x0, 0xF) => { // Interrupt Flags
+                                    self.interrupts.write_flags(value);
                                     return;
                                 },
                                 (0xF, 0xF) => { // Interrupt Enable Register
-                                    self.interrupt_enable_register = value;
+                                    self.interrupts.enable = value;
+                                    return;
+                                },
+                                (0x4, 0xD) => { // KEY1, CGB speed switch
+                                    if self.is_cgb {
+                                        self.is_speed_switch_armed = value & 0x01 != 0;
+                                    }
                                     return;
                                 },
                                 (0x0, _) | (0x1, _) | (0x2, _) | (0x3, _) => { // I/O Ports
@@ -399,6 +471,9 @@ impl MMU {
                                     if address == 0xFF50 && value == 0x01 {
                                         debug!("BIOS has finished running");
                                         self.is_bios_mapped = false;
+                                    } else if address == 0xFF46 { // OAM DMA
+                                        self.gpu.write_register(address, value);
+                                        self.start_dma(value);
                                     } else {
                                         self.gpu.write_register(address, value);
                                     }
@@ -425,125 +500,127 @@ impl MMU {
         self.write_byte(address + 1, (value >> 8) as u8);
     }
 
-    pub fn set_cartridge_type(&mut self, value: u8) {
-        self.cartridge_type = value;
+    // ROM and BIOS come from the loaded files and aren't part of the state
+    pub fn save_state<W: Write>(&self, state: &mut StateWriter<W>) -> io::Result<()> {
+        self.gpu.save_state(state)?;
+        self.timer.save_state(state)?;
+        self.serial.save_state(state)?;
+        self.interrupts.save_state(state)?;
+
+        let mut mapper_state = Vec::new();
+        self.cartridge.mapper.save_state(&mut StateWriter::new(&mut mapper_state))?;
+        state.write_u32(mapper_state.len() as u32)?;
+        state.write_bytes(&mapper_state)?;
+
+        state.write_bytes(&self.wram)?;
+        state.write_bytes(&self.io_ports)?;
+        state.write_bytes(&self.zram)?;
+        state.write_bool(self.is_bios_mapped)?;
+        state.write_bool(self.is_speed_switch_armed)?;
+        state.write_bool(self.is_double_speed)?;
+        state.write_bool(self.is_dma_active)?;
+        state.write_u16(self.dma_source)?;
+        state.write_u8(self.dma_index)?;
+        Ok(())
     }
 
-    pub fn set_rom_size(&mut self, value: u8) {
-        match value {
-            0 => self.rom_size = 2,
-            1 => self.rom_size = 4,
-            2 => self.rom_size = 8,
-            3 => self.rom_size = 16,
-            4 => self.rom_size = 32,
-            5 => self.rom_size = 64,
-            6 => self.rom_size = 128,
-            _ => {error!("Unknown ROM size {}", value); exit(1);}
+    pub fn load_state<R: Read>(&mut self, state: &mut StateReader<R>) -> io::Result<()> {
+        self.gpu.load_state(state)?;
+        self.timer.load_state(state)?;
+        self.serial.load_state(state)?;
+        self.interrupts.load_state(state)?;
+
+        // The mapper state is the same size for the life of the cartridge, anything else is corrupt
+        let mut current_mapper_state = Vec::new();
+        self.cartridge.mapper.save_state(&mut StateWriter::new(&mut current_mapper_state))?;
+        let mapper_state_size = state.read_u32()? as usize;
+        if mapper_state_size != current_mapper_state.len() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData,
+                format!("state has {} bytes of mapper state, the cartridge has {}", mapper_state_size, current_mapper_state.len())));
         }
-    }
+        let mut mapper_state = vec![0; mapper_state_size];
+        state.read_bytes(&mut mapper_state)?;
+        self.cartridge.mapper.load_state(&mut StateReader::new(&mut &mapper_state[..]))?;
+        self.is_external_ram_dirty = true;
 
-    fn update_external_ram(&mut self, value: u8) {
-        // TODO - Finish MBC2,3,5 support
-        match self.cartridge_type {
-            ROM_ONLY | MBC1 => {
-                trace!("Tried to update external RAM on a cartridge with no RAM.");
-                return;
-            },
-            MBC1_RAM | MBC1_RAM_BATT => {
-                //TODO - trace
-                debug!("Updating external RAM - {:#04X}. 0x0A = enable, all else disable", value);
-
-                if value == 0x0A {
-                    self.active_external_ram = true;
-                } else {
-                    self.active_external_ram = false;
-                }
-            },
-            _ => {
-                //TODO - trace
-                debug!("Tried to update external RAM on an unknown/unsupported cartridge type - Cart Type:{:#04X} - Value:{}", self.cartridge_type, value);
-                exit(1);
-            }
+        state.read_bytes(&mut self.wram)?;
+        state.read_bytes(&mut self.io_ports)?;
+        state.read_bytes(&mut self.zram)?;
+        self.is_bios_mapped = state.read_bool()?;
+        self.is_speed_switch_armed = state.read_bool()?;
+        self.is_double_speed = state.read_bool()?;
+        self.is_dma_active = state.read_bool()?;
+        self.dma_source = state.read_u16()?;
+        self.dma_index = state.read_u8()?;
+        if self.is_dma_active && self.dma_index as usize >= OAM_SIZE {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("OAM DMA is past the end of OAM at byte {}", self.dma_index)));
         }
+        Ok(())
     }
+}
 
-    fn update_active_rom_bank(&mut self, value: u8) {
-        // TODO - Finish MBC2,3,5 support
-        match self.cartridge_type {
-            ROM_ONLY => {
-                trace!("Tried to change active ROM bank on a ROM_ONLY cartridge.");
-                return;
-            },
-            MBC1 | MBC1_RAM | MBC1_RAM_BATT => {
-                //TODO - trace
-                debug!("Updating ROM Bank {}", value);
-
-                if value == 0 {
-                    self.active_rom_bank = 1;
-                } else if value < self.rom_size && value < 32 {
-                    self.active_rom_bank = value;
-                } else {
-                    error!("Tried to assign invalid ROM bank {}, should be 0-31.", value);
-                    exit(1);
-                }
-            },
-            _ => {
-                //TODO - trace
-                debug!("Tried to change active ROM bank on an unknown/unsupported cartridge type - Cart Type:{:#04X} - Value:{}", self.cartridge_type, value);
-                exit(1);
-            }
+// Zero Page RAM, the only memory the CPU can reach during OAM DMA
+fn is_hram(address: u16) -> bool {
+    return (0xFF80..=0xFFFE).contains(&address);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs;
+
+    #[test]
+    fn oversized_bios_is_an_error() {
+        let path = std::env::temp_dir().join(format!("rustboy-bios-test-{}.bin", std::process::id()));
+        fs::write(&path, vec![0; 257]).unwrap();
+        let result = MMU::new().load_bios(path.to_str().unwrap());
+        fs::remove_file(&path).unwrap();
+
+        match result {
+            Err(EmuError::BadBiosSize(257)) => {},
+            other => panic!("expected BadBiosSize, got {:?}", other)
         }
     }
 
-    fn update_active_rom_ram_bank(&mut self, value: u8) {
-        // TODO - Finish MBC2,3,5 support
-        match self.cartridge_type {
-            ROM_ONLY => {
-                trace!("Tried to change active ROM/RAM bank on a ROM_ONLY cartridge.");
-                return;
-            },
-            MBC1 | MBC1_RAM | MBC1_RAM_BATT => {
-                if self.memory_mode == 0 {
-                    // ROM Mode
-                    trace!("Updating active ROM bank. {}", (self.active_rom_bank & 0x1F) + ((value & 3) << 5));
-                    self.active_rom_bank = (self.active_rom_bank & 0x1F) + ((value & 3) << 5);
-                } else if self.memory_mode == 1 {
-                    // RAM Mode
-                    trace!("Updating active RAM bank. {}", value);
-                    self.active_ram_bank = value & 3;
-                }
-            },
-            _ => {
-                //TODO - trace
-                debug!("Tried to change active ROM/RAM bank on an unknown/unsupported cartridge type - Cart Type:{:#04X} - Value:{}", self.cartridge_type, value);
-                exit(1);
-            }
-        }
+    #[test]
+    fn external_ram_is_dirty_only_once_a_byte_is_stored() {
+        let mut mmu = MMU::new();
+        mmu.cartridge.mapper = new_mapper(crate::cartridge::MBC1_RAM_BATT, vec![0; 0x8000], 0x2000).unwrap();
+
+        // RAM starts out disabled, so the write is dropped
+        mmu.write_byte(0xA000, 0x42);
+        assert!(!mmu.is_external_ram_dirty);
+
+        mmu.write_byte(0x0000, 0x0A);
+        mmu.write_byte(0xA000, 0x42);
+        assert!(mmu.is_external_ram_dirty);
+        assert_eq!(mmu.read_byte(0xA000), 0x42);
     }
 
-    fn update_memory_mode(&mut self, value: u8) {
-        // TODO - Finish MBC2,3,5 support
-        match self.cartridge_type {
-            ROM_ONLY | MBC1 => {
-                trace!("Tried to change memory mode on a cartridge that only has ROM.");
-                return;
-            },
-            MBC1_RAM | MBC1_RAM_BATT => {
-                if value & 0x1 == 1 {
-                    //TODO - trace
-                    debug!("Memory Mode set to 1");
-                    self.memory_mode = 1;
-                } else {
-                    //TODO - trace
-                    debug!("Memory Mode set to 0");
-                    self.memory_mode = 0;
-                }
-            },
-            _ => {
-                //TODO - trace
-                debug!("Tried to change memory mode on an unknown/unsupported cartridge type - Cart Type:{:#04X} - Value:{}", self.cartridge_type, value);
-                exit(1);
-            }
+    #[test]
+    fn oam_dma_copies_a_byte_per_cycle_and_locks_the_cpu_out_of_everything_but_hram() {
+        let mut mmu = MMU::new();
+        for index in 0..OAM_SIZE as u16 {
+            mmu.write_byte(0xC100 + index, index as u8 ^ 0x5A);
         }
+
+        mmu.write_byte(0xFF46, 0xC1);
+        assert_eq!(mmu.read_byte(0xC100), 0xFF);
+        mmu.write_byte(0xC000, 0x42);
+        mmu.write_byte(0xFF80, 0x42);
+        assert_eq!(mmu.read_byte(0xFF80), 0x42);
+
+        for _ in 0..OAM_SIZE - 1 {
+            mmu.tick();
+        }
+        assert_eq!(mmu.read_byte(0xFE00), 0xFF);
+
+        mmu.tick();
+        for index in 0..OAM_SIZE as u16 {
+            assert_eq!(mmu.read_byte(0xFE00 + index), index as u8 ^ 0x5A, "OAM byte {}", index);
+        }
+        assert_eq!(mmu.read_byte(0xC000), 0);
+        assert_eq!(mmu.read_byte(0xFF46), 0xC1);
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,89 @@
+#![allow(clippy::needless_return)]
+
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::gameboy::CLOCK_SPEED;
+
+pub const MIN_SPEED: f64 = 0.25;
+pub const MAX_SPEED: f64 = 8.0;
+
+// How far behind real time the emulator may fall before the pacer stops trying to catch up
+const MAX_LAG: Duration = Duration::from_millis(100);
+// The OS sleep is only trusted up to this close to the deadline, the rest is spun off
+const SPIN_THRESHOLD: Duration = Duration::from_millis(2);
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Speed {
+    Multiplier(f64), // 1.0 is real time
+    Unlimited
+}
+
+impl Speed {
+    // Accepts a multiplier between MIN_SPEED and MAX_SPEED ("1", "0.5", "2x") or "unlimited"
+    pub fn parse(value: &str) -> Option<Speed> {
+        let value = value.trim();
+        if value.eq_ignore_ascii_case("unlimited") {
+            return Some(Speed::Unlimited);
+        }
+
+        let multiplier = value.trim_end_matches(['x', 'X']).parse::<f64>().ok()?;
+        if !(MIN_SPEED..=MAX_SPEED).contains(&multiplier) {
+            return None;
+        }
+
+        return Some(Speed::Multiplier(multiplier));
+    }
+}
+
+// Keeps emulation in step with wall clock time. The frontend reports how many cycles it ran and
+// the pacer sleeps until the host has caught up with the emulated machine
+pub struct FramePacer {
+    pub speed: Speed,
+    deadline: Instant
+}
+
+impl FramePacer {
+    pub fn new(speed: Speed) -> Self {
+        FramePacer {
+            speed,
+            deadline: Instant::now()
+        }
+    }
+
+    // Blocks until the given number of T-cycles worth of real time has passed since the previous call
+    pub fn wait(&mut self, cycles: u32) {
+        let multiplier = match self.speed {
+            Speed::Multiplier(multiplier) => multiplier,
+            Speed::Unlimited => {
+                self.reset();
+                return;
+            }
+        };
+
+        self.deadline += Duration::from_secs_f64(cycles as f64 / (CLOCK_SPEED as f64 * multiplier));
+
+        let now = Instant::now();
+        if now > self.deadline {
+            // Running slower than the target, don't try to make up for more than MAX_LAG at once
+            if now - self.deadline > MAX_LAG {
+                trace!("Frame pacer fell behind, resyncing");
+                self.deadline = now;
+            }
+            return;
+        }
+
+        let remaining = self.deadline - now;
+        if remaining > SPIN_THRESHOLD {
+            thread::sleep(remaining - SPIN_THRESHOLD);
+        }
+        while Instant::now() < self.deadline {
+            std::hint::spin_loop();
+        }
+    }
+
+    // Starts timing afresh, for use after the emulator has been paused or run unthrottled
+    pub fn reset(&mut self) {
+        self.deadline = Instant::now();
+    }
+}
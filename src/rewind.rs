@@ -0,0 +1,212 @@
+#![allow(clippy::needless_return)]
+
+use std::collections::VecDeque;
+
+use crate::gameboy::GameBoy;
+
+// Ring buffer of save states taken while playing, so the frontend can step back in time.
+// Snapshots are run length encoded, most of the machine's memory is long runs of the same byte
+pub struct RewindBuffer {
+    snapshots: VecDeque<Vec<u8>>,
+    memory_budget: usize,    // Bytes of compressed snapshots to keep before dropping the oldest
+    memory_used: usize,
+    interval: u32,           // Frames between snapshots
+    frames_since_capture: u32,
+    frames_to_hold: u32,     // Frames left to show the last restored snapshot for before restoring the next
+    scratch: Vec<u8>
+}
+
+impl RewindBuffer {
+    pub fn new(interval: u32, memory_budget: usize) -> Self {
+        RewindBuffer {
+            snapshots: VecDeque::new(),
+            memory_budget,
+            memory_used: 0,
+            interval: interval.max(1),
+            frames_since_capture: 0,
+            frames_to_hold: 0,
+            scratch: Vec::new()
+        }
+    }
+
+    // Call once per emulated frame. Every interval frames the machine state is captured
+    pub fn capture(&mut self, gameboy: &GameBoy) {
+        if self.memory_budget == 0 {
+            return;
+        }
+
+        self.frames_to_hold = 0;
+        self.frames_since_capture += 1;
+        if self.frames_since_capture < self.interval {
+            return;
+        }
+        self.frames_since_capture = 0;
+
+        self.scratch.clear();
+        let result = gameboy.save_state(&mut self.scratch);
+        if result.is_err() {
+            error!("Failed to capture rewind snapshot: {}", result.err().unwrap());
+            return;
+        }
+
+        let snapshot = compress(&self.scratch);
+        self.memory_used += snapshot.len();
+        self.snapshots.push_back(snapshot);
+
+        while self.memory_used > self.memory_budget {
+            match self.snapshots.pop_front() {
+                Some(oldest) => self.memory_used -= oldest.len(),
+                None => break
+            }
+        }
+    }
+
+    // Call once per frame while rewinding. Restores the most recent snapshot and drops it from the buffer,
+    // so repeated calls walk backwards in time. Each snapshot is held for interval frames, the same spacing
+    // they were captured at, so playback runs at normal speed. Returns whether a snapshot was restored
+    pub fn rewind(&mut self, gameboy: &mut GameBoy) -> bool {
+        if self.frames_to_hold > 0 {
+            self.frames_to_hold -= 1;
+            return false;
+        }
+
+        let snapshot = match self.snapshots.pop_back() {
+            Some(snapshot) => snapshot,
+            None => return false
+        };
+        self.memory_used -= snapshot.len();
+        self.frames_since_capture = 0;
+        self.frames_to_hold = self.interval - 1;
+
+        decompress(&snapshot, &mut self.scratch);
+        let result = gameboy.load_state(&mut &self.scratch[..]);
+        if result.is_err() {
+            error!("Failed to restore rewind snapshot, clearing rewind buffer: {}", result.err().unwrap());
+            self.clear();
+            return false;
+        }
+
+        return true;
+    }
+
+    pub fn clear(&mut self) {
+        self.snapshots.clear();
+        self.memory_used = 0;
+        self.frames_since_capture = 0;
+        self.frames_to_hold = 0;
+    }
+}
+
+// PackBits style RLE. A header byte of 0-127 is followed by that many + 1 literal bytes,
+// 129-255 means the next byte repeats 257 - header times
+fn compress(input: &[u8]) -> Vec<u8> {
+    let mut output = Vec::with_capacity(input.len() / 4);
+    let mut index = 0;
+
+    while index < input.len() {
+        // Length of the run starting here
+        let mut run = 1;
+        while index + run < input.len() && run < 128 && input[index + run] == input[index] {
+            run += 1;
+        }
+
+        if run >= 3 {
+            output.push((257 - run) as u8);
+            output.push(input[index]);
+            index += run;
+            continue;
+        }
+
+        // Gather literals until the next run worth encoding
+        let start = index;
+        while index < input.len() && index - start < 128 {
+            if index + 2 < input.len() && input[index] == input[index + 1] && input[index] == input[index + 2] {
+                break;
+            }
+            index += 1;
+        }
+        output.push((index - start - 1) as u8);
+        output.extend_from_slice(&input[start..index]);
+    }
+
+    return output;
+}
+
+fn decompress(input: &[u8], output: &mut Vec<u8>) {
+    output.clear();
+    let mut index = 0;
+
+    while index < input.len() {
+        let header = input[index] as usize;
+        index += 1;
+
+        if header < 128 {
+            let count = header + 1;
+            output.extend_from_slice(&input[index..index + count]);
+            index += count;
+        } else {
+            let count = 257 - header;
+            output.resize(output.len() + count, input[index]);
+            index += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(input: &[u8]) -> Vec<u8> {
+        let compressed = compress(input);
+        let mut output = Vec::new();
+        decompress(&compressed, &mut output);
+        assert_eq!(output, input);
+        return compressed;
+    }
+
+    #[test]
+    fn empty_input() {
+        assert!(round_trip(&[]).is_empty());
+    }
+
+    #[test]
+    fn runs_longer_than_128_bytes_are_split() {
+        // 128 + 128 + 44
+        assert_eq!(round_trip(&[0xAA; 300]), vec![129, 0xAA, 129, 0xAA, 213, 0xAA]);
+
+        // A leftover of 1 or 2 bytes goes out as literals
+        assert_eq!(round_trip(&[0x55; 129]), vec![129, 0x55, 0, 0x55]);
+        assert_eq!(round_trip(&[0x55; 130]), vec![129, 0x55, 1, 0x55, 0x55]);
+    }
+
+    #[test]
+    fn literals_next_to_runs() {
+        let input = [1, 2, 3, 3, 3, 3, 4, 4, 5, 5, 5];
+        assert_eq!(round_trip(&input), vec![1, 1, 2, 253, 3, 1, 4, 4, 254, 5]);
+
+        let mut input: Vec<u8> = (0..200).map(|value| value as u8).collect();
+        input.extend_from_slice(&[7; 10]);
+        input.extend((0..10).map(|value| value as u8));
+        round_trip(&input);
+    }
+
+    #[test]
+    fn playback_holds_each_snapshot_for_the_interval() {
+        let mut gameboy = GameBoy::new();
+        let mut rewind = RewindBuffer::new(3, usize::MAX);
+        for frame in 1..=6 {
+            gameboy.cpu.program_counter = frame;
+            rewind.capture(&gameboy);
+        }
+
+        let mut restored = Vec::new();
+        for _ in 0..7 {
+            if rewind.rewind(&mut gameboy) {
+                restored.push(gameboy.cpu.program_counter);
+            } else {
+                restored.push(0);
+            }
+        }
+        assert_eq!(restored, vec![6, 0, 0, 3, 0, 0, 0]);
+    }
+}
@@ -0,0 +1,285 @@
+#![allow(clippy::needless_return)]
+
+use std::io::{self, Read, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::savestate::{StateReader, StateWriter};
+
+// RTC register numbers, selected by writing them to 0x4000 - 0x5FFF on an MBC3
+pub const RTC_SECONDS: u8   = 0x08;
+pub const RTC_MINUTES: u8   = 0x09;
+pub const RTC_HOURS: u8     = 0x0A;
+pub const RTC_DAY_LOW: u8   = 0x0B;
+pub const RTC_DAY_HIGH: u8  = 0x0C; // Bit 0 = day bit 8, bit 6 = halt, bit 7 = day counter carry
+
+const DAY_HIGH_BIT: u8  = 0x01;
+const HALT_BIT: u8      = 0x40;
+const DAY_CARRY_BIT: u8 = 0x80;
+
+// Size of the clock data appended to .sav files. Same layout as other emulators use: the five clock
+// registers and the five latched registers as 32 bit values, then a 64 bit unix timestamp
+pub const RTC_SAVE_SIZE: usize = 48;
+
+// MBC3 real time clock. It runs off the host's wall clock, so time keeps passing while the emulator is closed
+pub struct Rtc {
+    seconds: u8,
+    minutes: u8,
+    hours: u8,
+    days: u16,          // 9 bit day counter
+    halted: bool,
+    day_carry: bool,
+    latched: [u8; 5],   // Registers as of the last latch, this is what the game reads
+    latch_armed: bool,  // 0x00 was written to the latch register, a 0x01 will latch
+    last_update: u64    // Unix time the registers were last brought up to date
+}
+
+impl Rtc {
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Self {
+        Rtc {
+            seconds: 0,
+            minutes: 0,
+            hours: 0,
+            days: 0,
+            halted: false,
+            day_carry: false,
+            latched: [0; 5],
+            latch_armed: false,
+            last_update: now()
+        }
+    }
+
+    // Writing 0x00 then 0x01 to 0x6000 - 0x7FFF copies the running clock into the readable registers
+    pub fn write_latch(&mut self, value: u8) {
+        if self.latch_armed && value == 0x01 {
+            self.update();
+            for register in RTC_SECONDS..=RTC_DAY_HIGH {
+                self.latched[(register - RTC_SECONDS) as usize] = self.read_live(register);
+            }
+        }
+        self.latch_armed = value == 0x00;
+    }
+
+    pub fn read(&self, register: u8) -> u8 {
+        return self.latched[(register - RTC_SECONDS) as usize];
+    }
+
+    pub fn write(&mut self, register: u8, value: u8) {
+        self.update();
+
+        match register {
+            RTC_SECONDS => {
+                self.seconds = value & 0x3F;
+                // Writing the seconds restarts the current second
+                self.last_update = now();
+            },
+            RTC_MINUTES => self.minutes = value & 0x3F,
+            RTC_HOURS => self.hours = value & 0x1F,
+            RTC_DAY_LOW => self.days = (self.days & 0x100) | value as u16,
+            RTC_DAY_HIGH => {
+                self.days = (self.days & 0xFF) | (((value & DAY_HIGH_BIT) as u16) << 8);
+                self.halted = value & HALT_BIT != 0;
+                self.day_carry = value & DAY_CARRY_BIT != 0;
+            },
+            _ => warn!("Tried to write unknown RTC register {:#04X}", register)
+        }
+
+        // Writes show up in the latched registers straight away
+        self.latched[(register - RTC_SECONDS) as usize] = self.read_live(register);
+    }
+
+    fn read_live(&self, register: u8) -> u8 {
+        match register {
+            RTC_SECONDS => return self.seconds,
+            RTC_MINUTES => return self.minutes,
+            RTC_HOURS => return self.hours,
+            RTC_DAY_LOW => return (self.days & 0xFF) as u8,
+            RTC_DAY_HIGH => {
+                let mut value = (self.days >> 8) as u8 & DAY_HIGH_BIT;
+                if self.halted {
+                    value |= HALT_BIT;
+                }
+                if self.day_carry {
+                    value |= DAY_CARRY_BIT;
+                }
+                return value;
+            },
+            _ => return 0xFF
+        }
+    }
+
+    // Advances the clock by however much wall clock time has passed since the last update
+    pub fn update(&mut self) {
+        let now = now();
+        if now <= self.last_update {
+            return;
+        }
+
+        let elapsed = now - self.last_update;
+        self.last_update = now;
+        if !self.halted {
+            self.advance(elapsed);
+        }
+    }
+
+    fn advance(&mut self, elapsed: u64) {
+        let total = self.seconds as u64 + elapsed;
+        self.seconds = (total % 60) as u8;
+
+        let total = self.minutes as u64 + total / 60;
+        self.minutes = (total % 60) as u8;
+
+        let total = self.hours as u64 + total / 60;
+        self.hours = (total % 24) as u8;
+
+        let total = self.days as u64 + total / 24;
+        if total > 0x1FF {
+            self.day_carry = true;
+        }
+        self.days = (total % 0x200) as u16;
+    }
+
+    // Clock data for the end of a .sav file
+    pub fn save_data(&mut self) -> Vec<u8> {
+        self.update();
+
+        let mut data = Vec::with_capacity(RTC_SAVE_SIZE);
+        for register in RTC_SECONDS..=RTC_DAY_HIGH {
+            data.extend_from_slice(&(self.read_live(register) as u32).to_le_bytes());
+        }
+        for latched in self.latched.iter() {
+            data.extend_from_slice(&(*latched as u32).to_le_bytes());
+        }
+        data.extend_from_slice(&self.last_update.to_le_bytes());
+        return data;
+    }
+
+    // Restores the clock from the end of a .sav file. Some emulators only store a 32 bit timestamp,
+    // so 44 bytes is accepted too
+    pub fn load_data(&mut self, data: &[u8]) {
+        if data.len() < RTC_SAVE_SIZE - 4 {
+            warn!("RTC save data is {} bytes, expected {}. Ignoring it", data.len(), RTC_SAVE_SIZE);
+            return;
+        }
+
+        let word = |index: usize| data[index * 4] as u32 | (data[index * 4 + 1] as u32) << 8 |
+            (data[index * 4 + 2] as u32) << 16 | (data[index * 4 + 3] as u32) << 24;
+
+        self.halted = false;
+        for (index, register) in (RTC_SECONDS..=RTC_DAY_HIGH).enumerate() {
+            let value = word(index) as u8;
+            match register {
+                RTC_SECONDS => self.seconds = value & 0x3F,
+                RTC_MINUTES => self.minutes = value & 0x3F,
+                RTC_HOURS => self.hours = value & 0x1F,
+                RTC_DAY_LOW => self.days = (self.days & 0x100) | value as u16,
+                _ => {
+                    self.days = (self.days & 0xFF) | (((value & DAY_HIGH_BIT) as u16) << 8);
+                    self.halted = value & HALT_BIT != 0;
+                    self.day_carry = value & DAY_CARRY_BIT != 0;
+                }
+            }
+        }
+        for index in 0..5 {
+            self.latched[index] = word(5 + index) as u8;
+        }
+
+        self.last_update = word(10) as u64;
+        if data.len() >= RTC_SAVE_SIZE {
+            self.last_update |= (word(11) as u64) << 32;
+        }
+
+        // Catch up on the time that passed while the emulator wasn't running
+        self.update();
+    }
+
+    pub fn save_state<W: Write>(&self, state: &mut StateWriter<W>) -> io::Result<()> {
+        state.write_u8(self.seconds)?;
+        state.write_u8(self.minutes)?;
+        state.write_u8(self.hours)?;
+        state.write_u16(self.days)?;
+        state.write_bool(self.halted)?;
+        state.write_bool(self.day_carry)?;
+        state.write_bytes(&self.latched)?;
+        state.write_bool(self.latch_armed)?;
+        state.write_bytes(&self.last_update.to_le_bytes())?;
+        Ok(())
+    }
+
+    pub fn load_state<R: Read>(&mut self, state: &mut StateReader<R>) -> io::Result<()> {
+        self.seconds = state.read_u8()?;
+        self.minutes = state.read_u8()?;
+        self.hours = state.read_u8()?;
+        self.days = state.read_u16()?;
+        self.halted = state.read_bool()?;
+        self.day_carry = state.read_bool()?;
+        state.read_bytes(&mut self.latched)?;
+        self.latch_armed = state.read_bool()?;
+        let mut last_update = [0; 8];
+        state.read_bytes(&mut last_update)?;
+        self.last_update = u64::from_le_bytes(last_update);
+        Ok(())
+    }
+}
+
+fn now() -> u64 {
+    match SystemTime::now().duration_since(UNIX_EPOCH) {
+        Ok(duration) => return duration.as_secs(),
+        Err(_) => return 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_hold_still_until_latched() {
+        let mut rtc = Rtc::new();
+        rtc.halted = true;
+        rtc.minutes = 10;
+        assert_eq!(rtc.read(RTC_MINUTES), 0);
+
+        // 0x01 only latches right after a 0x00
+        rtc.write_latch(0x01);
+        assert_eq!(rtc.read(RTC_MINUTES), 0);
+        rtc.write_latch(0x00);
+        rtc.write_latch(0x01);
+        assert_eq!(rtc.read(RTC_MINUTES), 10);
+        assert_eq!(rtc.read(RTC_DAY_HIGH), HALT_BIT);
+
+        rtc.minutes = 11;
+        rtc.write_latch(0x01);
+        assert_eq!(rtc.read(RTC_MINUTES), 10);
+    }
+
+    #[test]
+    fn rolls_over_into_the_day_carry() {
+        let mut rtc = Rtc::new();
+        rtc.seconds = 59;
+        rtc.minutes = 59;
+        rtc.hours = 23;
+        rtc.days = 0x1FF;
+
+        rtc.advance(1);
+        assert_eq!((rtc.seconds, rtc.minutes, rtc.hours, rtc.days), (0, 0, 0, 0));
+        assert!(rtc.day_carry);
+
+        // The carry sticks until the game clears it
+        rtc.advance(24 * 60 * 60 + 61);
+        assert_eq!((rtc.seconds, rtc.minutes, rtc.hours, rtc.days), (1, 1, 0, 1));
+        assert_eq!(rtc.read_live(RTC_DAY_HIGH), DAY_CARRY_BIT);
+        rtc.write(RTC_DAY_HIGH, 0);
+        assert!(!rtc.day_carry);
+    }
+
+    #[test]
+    fn day_counter_carries_into_bit_8() {
+        let mut rtc = Rtc::new();
+        rtc.days = 0xFF;
+        rtc.advance(24 * 60 * 60);
+        assert_eq!(rtc.read_live(RTC_DAY_LOW), 0x00);
+        assert_eq!(rtc.read_live(RTC_DAY_HIGH), DAY_HIGH_BIT);
+        assert!(!rtc.day_carry);
+    }
+}
@@ -0,0 +1,86 @@
+#![allow(clippy::needless_return)]
+
+use std::io::{self, Read, Write};
+
+// Save state files start with this magic followed by the format version. Bump the version when the
+// layout written by the save_state functions changes after a release, so older files are turned away
+pub const MAGIC: [u8; 4] = *b"RBST";
+pub const VERSION: u8 = 1;
+
+// Little endian writer for the save_state functions of each component
+pub struct StateWriter<'a, W: Write> {
+    writer: &'a mut W
+}
+
+impl<'a, W: Write> StateWriter<'a, W> {
+    pub fn new(writer: &'a mut W) -> Self {
+        StateWriter { writer }
+    }
+
+    pub fn write_u8(&mut self, value: u8) -> io::Result<()> {
+        return self.writer.write_all(&[value]);
+    }
+
+    pub fn write_bool(&mut self, value: bool) -> io::Result<()> {
+        return self.write_u8(value as u8);
+    }
+
+    pub fn write_u16(&mut self, value: u16) -> io::Result<()> {
+        return self.writer.write_all(&value.to_le_bytes());
+    }
+
+    pub fn write_u32(&mut self, value: u32) -> io::Result<()> {
+        return self.writer.write_all(&value.to_le_bytes());
+    }
+
+    pub fn write_i16(&mut self, value: i16) -> io::Result<()> {
+        return self.writer.write_all(&value.to_le_bytes());
+    }
+
+    pub fn write_bytes(&mut self, bytes: &[u8]) -> io::Result<()> {
+        return self.writer.write_all(bytes);
+    }
+}
+
+// Counterpart to StateWriter for the load_state functions
+pub struct StateReader<'a, R: Read> {
+    reader: &'a mut R
+}
+
+impl<'a, R: Read> StateReader<'a, R> {
+    pub fn new(reader: &'a mut R) -> Self {
+        StateReader { reader }
+    }
+
+    pub fn read_u8(&mut self) -> io::Result<u8> {
+        let mut buffer = [0; 1];
+        self.reader.read_exact(&mut buffer)?;
+        return Ok(buffer[0]);
+    }
+
+    pub fn read_bool(&mut self) -> io::Result<bool> {
+        return Ok(self.read_u8()? != 0);
+    }
+
+    pub fn read_u16(&mut self) -> io::Result<u16> {
+        let mut buffer = [0; 2];
+        self.reader.read_exact(&mut buffer)?;
+        return Ok(u16::from_le_bytes(buffer));
+    }
+
+    pub fn read_u32(&mut self) -> io::Result<u32> {
+        let mut buffer = [0; 4];
+        self.reader.read_exact(&mut buffer)?;
+        return Ok(u32::from_le_bytes(buffer));
+    }
+
+    pub fn read_i16(&mut self) -> io::Result<i16> {
+        let mut buffer = [0; 2];
+        self.reader.read_exact(&mut buffer)?;
+        return Ok(i16::from_le_bytes(buffer));
+    }
+
+    pub fn read_bytes(&mut self, bytes: &mut [u8]) -> io::Result<()> {
+        return self.reader.read_exact(bytes);
+    }
+}
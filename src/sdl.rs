@@ -0,0 +1,289 @@
+#![allow(clippy::needless_return)]
+
+use std::cell::Cell;
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::rc::Rc;
+
+use sdl2::EventPump;
+use sdl2::Sdl;
+use sdl2::audio::{AudioQueue, AudioSpecDesired};
+use sdl2::event::Event;
+use sdl2::keyboard::{Keycode, Mod};
+use sdl2::pixels::PixelFormatEnum;
+use sdl2::render::{Canvas, Texture};
+use sdl2::video::Window;
+
+use rustboy::GameBoy;
+use rustboy::frontend::{AudioSink, InputSource, VideoSink};
+use rustboy::input::{BUTTON_A, BUTTON_B, BUTTON_DOWN, BUTTON_LEFT, BUTTON_RIGHT, BUTTON_SELECT, BUTTON_START, BUTTON_UP};
+use rustboy::gpu::{SCREEN_HEIGHT, SCREEN_WIDTH, SHADE_COLORS, VRAM_DEBUG_HEIGHT, VRAM_DEBUG_WIDTH};
+
+// A window plus the streaming texture its frames are uploaded to
+struct Display {
+    canvas: Canvas<Window>,
+    texture: Texture,
+    pitch: usize
+}
+
+impl Display {
+    fn new(sdl_context: &Sdl, title: &str, width: usize, height: usize, position: (i32, i32)) -> Self {
+        let canvas = sdl_context.video().unwrap()
+            .window(title, width as u32, height as u32).position(position.0, position.1).build().unwrap()
+            .into_canvas().accelerated().build().unwrap();
+        let texture = canvas.create_texture_streaming(PixelFormatEnum::RGB24, width as u32, height as u32).unwrap();
+
+        Display {
+            canvas,
+            texture,
+            pitch: width * 3
+        }
+    }
+
+    fn present(&mut self, rgb: &[u8]) {
+        let result = self.texture.update(None, rgb, self.pitch);
+        if result.is_err() {
+            error!("Failed to update texture: {:?}", result.err());
+            return;
+        }
+
+        self.canvas.clear();
+        let result = self.canvas.copy(&self.texture, None, None);
+        if result.is_err() {
+            error!("Failed to copy texture: {:?}", result.err());
+            return;
+        }
+        self.canvas.present();
+    }
+}
+
+pub struct SdlVideoSink {
+    display: Display,
+    rgb_buffer: Vec<u8>
+}
+
+impl VideoSink for SdlVideoSink {
+    fn present_frame(&mut self, frame_buffer: &[u8]) {
+        for (pixel, shade) in self.rgb_buffer.chunks_mut(3).zip(frame_buffer) {
+            pixel.copy_from_slice(&SHADE_COLORS[*shade as usize]);
+        }
+
+        self.display.present(&self.rgb_buffer);
+    }
+}
+
+pub struct SdlAudioSink {
+    queue: AudioQueue<i16>,
+    max_queued_bytes: u32
+}
+
+impl AudioSink for SdlAudioSink {
+    fn sample_rate(&self) -> u32 {
+        return self.queue.spec().freq as u32;
+    }
+
+    fn queue_samples(&mut self, samples: &[i16]) {
+        // Drop samples rather than let the queue (and latency) grow when running faster than real time
+        if self.queue.size() > self.max_queued_bytes {
+            return;
+        }
+
+        if !self.queue.queue(samples) {
+            warn!("Failed to queue audio: {}", sdl2::get_error());
+        }
+    }
+}
+
+// Joypad state is written by SdlFrontend::handle_events and read by the core through this
+pub struct SdlInputSource {
+    pressed: Rc<Cell<u8>>
+}
+
+impl InputSource for SdlInputSource {
+    fn poll(&mut self) -> u8 {
+        return self.pressed.get();
+    }
+}
+
+pub struct SdlFrontend {
+    _sdl_context: Sdl,
+    event_pump: EventPump,
+    vram_debug_display: Option<Display>,
+    pressed: Rc<Cell<u8>>,
+    rom_path: String,
+    pub fast_forward: bool, // Fast-forward hotkey is held
+    pub rewinding: bool,    // Rewind hotkey is held
+    pub quit: bool          // Window was closed or Escape pressed
+}
+
+impl SdlFrontend {
+    // Opens the game window (and the VRAM debug window if requested) and installs the SDL sinks on the GameBoy
+    pub fn new(gameboy: &mut GameBoy, debug: bool, rom_path: &str) -> Self {
+        debug!("Initializing SDL");
+
+        let sdl_context = sdl2::init().unwrap();
+
+        gameboy.video_sink = Box::new(SdlVideoSink {
+            display: Display::new(&sdl_context, "RustBoy", SCREEN_WIDTH, SCREEN_HEIGHT, (800, 100)),
+            rgb_buffer: vec![0; SCREEN_WIDTH * SCREEN_HEIGHT * 3]
+        });
+
+        let audio_spec = AudioSpecDesired {
+            freq: Some(44100),
+            channels: Some(2),
+            samples: None
+        };
+        let audio_queue = sdl_context.audio().and_then(|audio| audio.open_queue::<i16, _>(None, &audio_spec));
+        match audio_queue {
+            Ok(queue) => {
+                queue.resume();
+                let max_queued_bytes = queue.spec().freq as u32 / 4 * 2 * 2; // 250ms of 16 bit stereo
+                gameboy.audio_sink = Box::new(SdlAudioSink { queue, max_queued_bytes });
+            },
+            Err(error) => warn!("Failed to open audio device, continuing without sound: {}", error)
+        }
+
+        let pressed = Rc::new(Cell::new(0));
+        gameboy.input_source = Box::new(SdlInputSource { pressed: pressed.clone() });
+
+        let mut vram_debug_display = None;
+        if debug {
+            vram_debug_display = Some(Display::new(&sdl_context, "GPU", VRAM_DEBUG_WIDTH, VRAM_DEBUG_HEIGHT, (800, 300)));
+        }
+
+        let event_pump = sdl_context.event_pump().unwrap();
+
+        SdlFrontend {
+            _sdl_context: sdl_context,
+            event_pump,
+            vram_debug_display,
+            pressed,
+            rom_path: String::from(rom_path),
+            fast_forward: false,
+            rewinding: false,
+            quit: false
+        }
+    }
+
+    // Redraws the VRAM debug window, if open
+    pub fn present_vram_debug(&mut self, gameboy: &GameBoy) {
+        if let Some(vram_debug_display) = self.vram_debug_display.as_mut() {
+            vram_debug_display.present(&gameboy.mmu.gpu.render_vram_debug());
+        }
+    }
+
+    // Handles everything that is waiting in the event queue without blocking
+    pub fn handle_events(&mut self, gameboy: &mut GameBoy) {
+        while let Some(event) = self.event_pump.poll_event() {
+            self.handle_event(event, gameboy);
+        }
+    }
+
+    // Sleeps until at least one event arrives, so a paused emulator doesn't spin the CPU
+    pub fn wait_for_events(&mut self, gameboy: &mut GameBoy) {
+        let event = self.event_pump.wait_event();
+        self.handle_event(event, gameboy);
+        self.handle_events(gameboy);
+    }
+
+    fn handle_event(&mut self, event: Event, gameboy: &mut GameBoy) {
+        let mut pressed = self.pressed.get();
+
+        match event {
+            Event::Quit    {..} => self.quit = true,
+            Event::KeyDown { keycode: Some(Keycode::Escape), ..} => self.quit = true,
+
+            Event::KeyDown { keycode: Some(Keycode::P), repeat: false, ..} => {
+                gameboy.is_paused = !gameboy.is_paused;
+                debug!("Paused: {}", gameboy.is_paused);
+            },
+            Event::KeyDown { keycode: Some(keycode), keymod, repeat: false, ..} if state_slot(keycode).is_some() => {
+                let slot = state_slot(keycode).unwrap();
+                if keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) {
+                    self.load_state(gameboy, slot);
+                } else {
+                    self.save_state(gameboy, slot);
+                }
+            },
+            Event::KeyDown { keycode: Some(Keycode::Tab), ..} => self.fast_forward = true,
+            Event::KeyUp   { keycode: Some(Keycode::Tab), ..} => self.fast_forward = false,
+            Event::KeyDown { keycode: Some(Keycode::Backspace), ..} => self.rewinding = true,
+            Event::KeyUp   { keycode: Some(Keycode::Backspace), ..} => self.rewinding = false,
+
+            Event::KeyDown { keycode: Some(keycode), ..} => pressed |= map_button(keycode),
+            Event::KeyUp   { keycode: Some(keycode), ..} => pressed &= !map_button(keycode),
+            _ => {}
+        }
+
+        self.pressed.set(pressed);
+    }
+
+    // Save states live next to the ROM as <rom>.ss<slot>
+    fn state_path(&self, slot: u8) -> Option<String> {
+        if self.rom_path.is_empty() {
+            warn!("Save states need a ROM to be loaded");
+            return None;
+        }
+        return Some(format!("{}.ss{}", self.rom_path, slot));
+    }
+
+    fn save_state(&self, gameboy: &GameBoy, slot: u8) {
+        let path = match self.state_path(slot) {
+            Some(path) => path,
+            None => return
+        };
+
+        let result = File::create(&path)
+            .map_err(|error| error.into())
+            .and_then(|file| gameboy.save_state(&mut BufWriter::new(file)));
+        match result {
+            Ok(_) => info!("Saved state to slot {} ({})", slot, path),
+            Err(error) => error!("Failed to save state to {}: {}", path, error)
+        }
+    }
+
+    fn load_state(&self, gameboy: &mut GameBoy, slot: u8) {
+        let path = match self.state_path(slot) {
+            Some(path) => path,
+            None => return
+        };
+
+        let result = File::open(&path)
+            .map_err(|error| error.into())
+            .and_then(|file| gameboy.load_state(&mut BufReader::new(file)));
+        match result {
+            Ok(_) => info!("Loaded state from slot {} ({})", slot, path),
+            Err(error) => error!("Failed to load state from {}: {}", path, error)
+        }
+    }
+}
+
+// F1 - F9 save to slots 1 - 9, with shift held they load from them
+fn state_slot(keycode: Keycode) -> Option<u8> {
+    match keycode {
+        Keycode::F1 => Some(1),
+        Keycode::F2 => Some(2),
+        Keycode::F3 => Some(3),
+        Keycode::F4 => Some(4),
+        Keycode::F5 => Some(5),
+        Keycode::F6 => Some(6),
+        Keycode::F7 => Some(7),
+        Keycode::F8 => Some(8),
+        Keycode::F9 => Some(9),
+        _ => None
+    }
+}
+
+fn map_button(keycode: Keycode) -> u8 {
+    match keycode {
+        Keycode::Right   => BUTTON_RIGHT,
+        Keycode::Left    => BUTTON_LEFT,
+        Keycode::Up      => BUTTON_UP,
+        Keycode::Down    => BUTTON_DOWN,
+        Keycode::Z       => BUTTON_A,
+        Keycode::X       => BUTTON_B,
+        Keycode::Space   => BUTTON_SELECT,
+        Keycode::KpEnter => BUTTON_START,
+        _ => 0
+    }
+}
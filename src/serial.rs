@@ -0,0 +1,72 @@
+#![allow(clippy::needless_return)]
+
+use std::io::{self, Read, Write};
+
+use crate::interrupts::{Interrupt, InterruptController};
+use crate::savestate::{StateReader, StateWriter};
+
+const TRANSFER_START_BIT: u8 = 0x80;
+const INTERNAL_CLOCK_BIT: u8 = 0x01;
+
+// T-cycles to shift out one byte with the internal 8192Hz clock
+const TRANSFER_CYCLES: u16 = 4096;
+
+// Serial port with nothing plugged into it. Transfers using the internal clock complete on time and
+// shift in 0xFF, transfers waiting on an external clock never finish, same as on hardware
+pub struct Serial {
+    pub data: u8,    // SB, 0xFF01
+    pub control: u8, // SC, 0xFF02
+    counter: u16
+}
+
+impl Serial {
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Self {
+        Serial {
+            data: 0,
+            control: 0,
+            counter: 0
+        }
+    }
+
+    pub fn read_control(&self) -> u8 {
+        return self.control | 0x7E; // or'd with 7E because Bits 1-6 are unmapped, unmapped bits always return as 1
+    }
+
+    pub fn write_control(&mut self, value: u8) {
+        self.control = value & (TRANSFER_START_BIT | INTERNAL_CLOCK_BIT);
+        if self.control & TRANSFER_START_BIT != 0 {
+            trace!("Serial transfer started, SB: {:#04X}", self.data);
+            self.counter = 0;
+        }
+    }
+
+    pub fn step(&mut self, clock_t: u8, interrupts: &mut InterruptController) {
+        if self.control != TRANSFER_START_BIT | INTERNAL_CLOCK_BIT {
+            return;
+        }
+
+        self.counter += clock_t as u16;
+        if self.counter >= TRANSFER_CYCLES {
+            debug!("Serial transfer complete, sent {:#04X}", self.data);
+            self.counter = 0;
+            self.data = 0xFF;
+            self.control &= !TRANSFER_START_BIT;
+            interrupts.request(Interrupt::Serial);
+        }
+    }
+
+    pub fn save_state<W: Write>(&self, state: &mut StateWriter<W>) -> io::Result<()> {
+        state.write_u8(self.data)?;
+        state.write_u8(self.control)?;
+        state.write_u16(self.counter)?;
+        Ok(())
+    }
+
+    pub fn load_state<R: Read>(&mut self, state: &mut StateReader<R>) -> io::Result<()> {
+        self.data = state.read_u8()?;
+        self.control = state.read_u8()?;
+        self.counter = state.read_u16()?;
+        Ok(())
+    }
+}
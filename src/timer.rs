@@ -1,60 +1,271 @@
+#![allow(clippy::needless_return)]
+
+use std::io::{self, Read, Write};
+
+use crate::interrupts::{Interrupt, InterruptController};
+use crate::savestate::{StateReader, StateWriter};
+
 pub struct Timer {
-    pub div: u16,
+    pub div: u16, // Internal divider, counts T-cycles. The DIV register is the top byte
     pub tima: u8,
-    tima_speed: u16,
     pub tma: u8,
     pub tac: u8,
-    counter: u16,
-    div_counter: u8,
-    tima_overflow_last_step: bool
+    is_overflow_pending: bool, // TIMA overflowed on the last M-cycle, TMA gets loaded on this one
+    is_reloading: bool         // TMA was loaded into TIMA on the current M-cycle
 }
 
 impl Timer {
+    #[allow(clippy::new_without_default)]
     pub fn new() -> Self {
         Timer {
             div: 0,
             tima: 0,
-            tima_speed: 256,
             tma: 0,
             tac: 0,
-            counter: 0,
-            div_counter: 0,
-            tima_overflow_last_step: false
+            is_overflow_pending: false,
+            is_reloading: false
+        }
+    }
+
+    // TIMA counts the falling edges of one bit of the divider, ANDed with the enable bit of TAC.
+    // The bit picks the rate: 4096, 262144, 65536 and 16384 Hz for clock selects 0 to 3
+    fn timer_signal(&self) -> bool {
+        let bit = match self.tac & 0x3 {
+            0 => 9,
+            1 => 3,
+            2 => 5,
+            _ => 7
+        };
+        return self.tac & 0x4 != 0 && self.div & (1 << bit) != 0;
+    }
+
+    fn increment_tima(&mut self) {
+        if self.tima == 0xFF {
+            self.tima = 0;
+            self.is_overflow_pending = true;
+        } else {
+            self.tima += 1;
+        }
+    }
+
+    // Runs the timer for one M-cycle. Called before the CPU's memory access on that cycle
+    pub fn tick(&mut self, interrupts: &mut InterruptController) {
+        // TIMA reads 0 for an M-cycle after overflowing, then TMA is loaded and the interrupt is requested
+        self.is_reloading = false;
+        if self.is_overflow_pending {
+            self.is_overflow_pending = false;
+            self.is_reloading = true;
+            self.tima = self.tma;
+            interrupts.request(Interrupt::Timer);
+        }
+
+        let signal = self.timer_signal();
+        self.div = self.div.wrapping_add(4);
+        if signal && !self.timer_signal() {
+            self.increment_tima();
+        }
+    }
+
+    // Any write clears the divider, which counts as a falling edge if the selected bit was set
+    pub fn write_div(&mut self) {
+        let signal = self.timer_signal();
+        self.div = 0;
+        if signal {
+            self.increment_tima();
+        }
+    }
+
+    // A write in the M-cycle between the overflow and the reload cancels the reload. On the reload cycle
+    // itself TMA wins and the write is lost
+    pub fn write_tima(&mut self, value: u8) {
+        if self.is_reloading {
+            return;
         }
+        self.is_overflow_pending = false;
+        self.tima = value;
     }
 
-    pub fn update(&mut self) {
-        match self.tac & 0x3 {
-            0 => {self.tima_speed = 256},
-            1 => {self.tima_speed = 4},
-            2 => {self.tima_speed = 16},
-            3 => {self.tima_speed = 64},
-            _ => {}
+    // TIMA picks up a TMA write made on the reload cycle too
+    pub fn write_tma(&mut self, value: u8) {
+        self.tma = value;
+        if self.is_reloading {
+            self.tima = value;
         }
     }
 
-    pub fn step(&mut self, clock_t: u8) {
-        self.div_counter += clock_t;
-        if self.div_counter >= 16 {
-            self.div_counter = 0;
-            self.div = self.div.wrapping_add(1);
+    // Turning the timer off or moving to a bit that's clear can make the signal fall, which ticks TIMA
+    pub fn write_tac(&mut self, value: u8) {
+        let signal = self.timer_signal();
+        self.tac = value & 0x7;
+        if signal && !self.timer_signal() {
+            self.increment_tima();
         }
+    }
+
+    pub fn save_state<W: Write>(&self, state: &mut StateWriter<W>) -> io::Result<()> {
+        state.write_u16(self.div)?;
+        state.write_u8(self.tima)?;
+        state.write_u8(self.tma)?;
+        state.write_u8(self.tac)?;
+        state.write_bool(self.is_overflow_pending)?;
+        state.write_bool(self.is_reloading)?;
+        Ok(())
+    }
+
+    pub fn load_state<R: Read>(&mut self, state: &mut StateReader<R>) -> io::Result<()> {
+        self.div = state.read_u16()?;
+        self.tima = state.read_u8()?;
+        self.tma = state.read_u8()?;
+        self.tac = state.read_u8()? & 0x7;
+        self.is_overflow_pending = state.read_bool()?;
+        self.is_reloading = state.read_bool()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::interrupts::TIMER_INTERRUPT_BIT;
+
+    // Ticks one M-cycle at a time until the timer interrupt is requested, returns the T-cycles taken
+    fn cycles_until_interrupt(timer: &mut Timer, interrupts: &mut InterruptController) -> u32 {
+        let mut cycles = 0;
+        while interrupts.flags & TIMER_INTERRUPT_BIT == 0 {
+            timer.tick(interrupts);
+            cycles += 4;
+            assert!(cycles < 0x100000, "the timer interrupt was never requested");
+        }
+        interrupts.flags &= !TIMER_INTERRUPT_BIT;
+        return cycles;
+    }
 
-        if self.tac & 0x4 != 0 {
-            self.counter += clock_t as u16;
-            if self.counter >= self.tima_speed {
-                self.counter = 0;
-                if self.tima_overflow_last_step {
-                    self.tima = self.tma;
-                } else if (self.tima as u16 + 1) & 0xFF == 0 {
-                    //TODO - trace
-                    debug!("Requesting Timer Interrupt");
-                    //TODO - fire IFR for timer
-                    self.tima_overflow_last_step = true;
-                } else {
-                    self.tima += 1;
-                }
-            }
+    // Timer running at 16 T-cycles per increment, on the M-cycle where TIMA has just overflowed
+    fn overflowed_timer(interrupts: &mut InterruptController) -> Timer {
+        let mut timer = Timer::new();
+        timer.write_tac(0x05);
+        timer.tma = 0x10;
+        timer.tima = 0xFF;
+        while timer.tima != 0 {
+            timer.tick(interrupts);
         }
+        return timer;
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn div_counts_every_256_cycles() {
+        let mut timer = Timer::new();
+        let mut interrupts = InterruptController::new();
+        for _ in 0..64 {
+            timer.tick(&mut interrupts);
+        }
+        assert_eq!(timer.div >> 8, 1);
+    }
+
+    #[test]
+    fn tima_overflow_period() {
+        for (tac, period) in [(0x4, 1024), (0x5, 16), (0x6, 64), (0x7, 256)].iter() {
+            let mut timer = Timer::new();
+            let mut interrupts = InterruptController::new();
+            timer.write_tac(*tac);
+
+            // The interrupt is requested one M-cycle after TIMA overflows
+            assert_eq!(cycles_until_interrupt(&mut timer, &mut interrupts), 256 * period + 4, "TAC {:#04X}", tac);
+            assert_eq!(cycles_until_interrupt(&mut timer, &mut interrupts), 256 * period, "TAC {:#04X}", tac);
+
+            // TMA shortens the period after the first overflow
+            timer.tma = 0xF0;
+            cycles_until_interrupt(&mut timer, &mut interrupts);
+            assert_eq!(cycles_until_interrupt(&mut timer, &mut interrupts), 16 * period, "TAC {:#04X}", tac);
+        }
+    }
+
+    #[test]
+    fn div_write_ticks_tima_while_the_selected_bit_is_set() {
+        let mut timer = Timer::new();
+        let mut interrupts = InterruptController::new();
+        timer.write_tac(0x05);
+
+        // Bit 3 of the divider is clear after one M-cycle and set after two
+        timer.tick(&mut interrupts);
+        timer.write_div();
+        assert_eq!(timer.tima, 0);
+
+        timer.tick(&mut interrupts);
+        timer.tick(&mut interrupts);
+        timer.write_div();
+        assert_eq!(timer.tima, 1);
+        assert_eq!(timer.div, 0);
+    }
+
+    #[test]
+    fn tac_write_ticks_tima_when_the_signal_falls() {
+        let mut timer = Timer::new();
+        let mut interrupts = InterruptController::new();
+        timer.write_tac(0x05);
+        timer.tick(&mut interrupts);
+        timer.tick(&mut interrupts);
+
+        // Bit 3 is set and bit 9 is clear, so both turning the timer off and switching to bit 9 tick TIMA
+        timer.write_tac(0x04);
+        assert_eq!(timer.tima, 1);
+        timer.write_tac(0x05);
+        timer.write_tac(0x01);
+        assert_eq!(timer.tima, 2);
+
+        // Turning it on doesn't, and neither does turning it off while the bit is clear
+        timer.write_div();
+        timer.write_tac(0x05);
+        timer.write_tac(0x01);
+        assert_eq!(timer.tima, 2);
+    }
+
+    #[test]
+    fn tima_write_before_the_reload_cancels_it() {
+        let mut interrupts = InterruptController::new();
+        let mut timer = overflowed_timer(&mut interrupts);
+
+        timer.write_tima(0x42);
+        timer.tick(&mut interrupts);
+        assert_eq!(timer.tima, 0x42);
+        assert_eq!(interrupts.flags & TIMER_INTERRUPT_BIT, 0);
+    }
+
+    #[test]
+    fn tima_write_on_the_reload_cycle_is_lost() {
+        let mut interrupts = InterruptController::new();
+        let mut timer = overflowed_timer(&mut interrupts);
+
+        timer.tick(&mut interrupts);
+        assert_ne!(interrupts.flags & TIMER_INTERRUPT_BIT, 0);
+        timer.write_tima(0x42);
+        assert_eq!(timer.tima, 0x10);
+
+        // TMA written on the same cycle goes straight through to TIMA
+        timer.write_tma(0x20);
+        assert_eq!(timer.tima, 0x20);
+
+        // Both writes work normally a cycle later
+        timer.tick(&mut interrupts);
+        timer.write_tma(0x30);
+        timer.write_tima(0x42);
+        assert_eq!(timer.tima, 0x42);
+    }
+
+    #[test]
+    fn timer_round_trips_through_save_state() {
+        let mut interrupts = InterruptController::new();
+        let timer = overflowed_timer(&mut interrupts);
+
+        let mut buffer = Vec::new();
+        timer.save_state(&mut StateWriter::new(&mut buffer)).unwrap();
+        let mut loaded = Timer::new();
+        loaded.load_state(&mut StateReader::new(&mut &buffer[..])).unwrap();
+
+        // The reload still happens on the next M-cycle
+        loaded.tick(&mut interrupts);
+        assert_eq!(loaded.tima, 0x10);
+        assert_ne!(interrupts.flags & TIMER_INTERRUPT_BIT, 0);
+    }
+}
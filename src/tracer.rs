@@ -0,0 +1,88 @@
+#![allow(clippy::needless_return)]
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+use crate::cpu::CPU;
+use crate::error::EmuError;
+use crate::mmu::MMU;
+
+// Logs the CPU state before every instruction, one line each, in the format gameboy-doctor compares against:
+// A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0100 PCMEM:00,C3,13,02
+pub struct Tracer {
+    writer: Box<dyn Write>,
+    pub start_pc: Option<u16>,  // Nothing is logged until the CPU reaches this PC
+    pub stop_pc: Option<u16>,   // Logging ends when the CPU reaches this PC, the instruction there isn't logged
+    pub max_lines: Option<u64>, // Logging ends after this many lines
+    line_count: u64,
+    is_started: bool,
+    is_finished: bool
+}
+
+impl Tracer {
+    pub fn new(writer: Box<dyn Write>) -> Self {
+        Tracer {
+            writer,
+            start_pc: None,
+            stop_pc: None,
+            max_lines: None,
+            line_count: 0,
+            is_started: false,
+            is_finished: false
+        }
+    }
+
+    pub fn create(path: &str) -> Result<Tracer, EmuError> {
+        let file = File::create(path)?;
+        return Ok(Tracer::new(Box::new(BufWriter::new(file))));
+    }
+
+    pub fn is_finished(&self) -> bool {
+        return self.is_finished;
+    }
+
+    // Called when the CPU is about to fetch the instruction at PC
+    pub fn log(&mut self, cpu: &CPU, mmu: &mut MMU) -> io::Result<()> {
+        if self.is_finished {
+            return Ok(());
+        }
+
+        let program_counter = cpu.program_counter;
+
+        if !self.is_started {
+            if let Some(start_pc) = self.start_pc {
+                if program_counter != start_pc {
+                    return Ok(());
+                }
+            }
+            self.is_started = true;
+        }
+
+        if self.stop_pc == Some(program_counter) {
+            return self.finish();
+        }
+
+        writeln!(self.writer, "A:{:02X} F:{:02X} B:{:02X} C:{:02X} D:{:02X} E:{:02X} H:{:02X} L:{:02X} SP:{:04X} PC:{:04X} PCMEM:{:02X},{:02X},{:02X},{:02X}",
+                 cpu.read_register_a(), cpu.read_register_f(), cpu.read_register_b(), cpu.read_register_c(),
+                 cpu.read_register_d(), cpu.read_register_e(), cpu.read_register_h(), cpu.read_register_l(),
+                 cpu.stack_pointer, program_counter,
+                 mmu.read_byte(program_counter), mmu.read_byte(program_counter.wrapping_add(1)),
+                 mmu.read_byte(program_counter.wrapping_add(2)), mmu.read_byte(program_counter.wrapping_add(3)))?;
+        self.line_count += 1;
+
+        if self.max_lines == Some(self.line_count) {
+            return self.finish();
+        }
+        return Ok(());
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        return self.writer.flush();
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        info!("Instruction trace finished after {} lines", self.line_count);
+        self.is_finished = true;
+        return self.writer.flush();
+    }
+}